@@ -0,0 +1,106 @@
+//! Measures peak memory use while loading a generated dataset.
+//!
+//! Run it with e.g. `cargo run --release --example memory_benchmark -- 200000`,
+//! where the argument is the number of registrations to generate. Each one
+//! gets three contacts, drawn from pools of names and addresses that are
+//! sized so that most of them are shared between registrations, as in the
+//! real data. Peak RSS is only available on Linux.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use hpd::dataset::{CONTACTS_FILENAME, REGISTRATIONS_FILENAME};
+use hpd::{CountBy, DatasetBuilder};
+
+/// A tiny deterministic PRNG, so every run generates the same data.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, max: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) % max
+    }
+}
+
+fn generate(dir: &Path, registrations: u64) -> std::io::Result<()> {
+    let mut rng = Lcg(1);
+    let names = registrations / 2;
+    let addrs = registrations / 4;
+
+    let mut regs = BufWriter::new(File::create(dir.join(REGISTRATIONS_FILENAME))?);
+    writeln!(
+        regs,
+        "RegistrationID,BoroID,Block,Lot,BIN,LastRegistrationDate,RegistrationEndDate"
+    )?;
+    for id in 0..registrations {
+        writeln!(
+            regs,
+            "{},{},{},{},{},01/01/2020,01/01/2099",
+            id,
+            1 + rng.next(5),
+            1 + rng.next(20000),
+            1 + rng.next(200),
+            1000000 + id
+        )?;
+    }
+
+    let mut contacts = BufWriter::new(File::create(dir.join(CONTACTS_FILENAME))?);
+    writeln!(contacts, "RegistrationContactID,RegistrationID,Type,CorporationName,FirstName,LastName,BusinessHouseNumber,BusinessStreetName,BusinessApartment,BusinessCity,BusinessState")?;
+    for id in 0..registrations * 3 {
+        let name = rng.next(names);
+        let addr = rng.next(addrs);
+        writeln!(
+            contacts,
+            "{},{},HeadOfficer,,FIRSTNAME{},LASTNAME{},{},SOME LONG STREET NAME {},SUITE {},NEW YORK,NY",
+            id,
+            id / 3,
+            name,
+            name,
+            addr % 1000,
+            addr,
+            addr % 7
+        )?;
+    }
+
+    regs.flush()?;
+    contacts.flush()
+}
+
+/// Returns the given field of /proc/self/status, e.g. "VmHWM" for peak RSS.
+fn proc_status(field: &str) -> Option<String> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find(|line| line.starts_with(field))
+        .map(|line| line[field.len() + 1..].trim().to_owned())
+}
+
+fn main() {
+    let registrations: u64 = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("argument should be a number"))
+        .unwrap_or(200_000);
+    let dir = std::env::temp_dir().join(format!("hpd-memory-benchmark-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    generate(&dir, registrations).unwrap();
+
+    let dataset = DatasetBuilder::new().read_dir(&dir).unwrap();
+    let portfolios = dataset.portfolios();
+    let ranking = portfolios.rank_by_building_count(&dataset.regs, CountBy::default(), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    println!(
+        "{} names, {} addresses, {} portfolios",
+        dataset.hpd.name_nodes.len(),
+        dataset.hpd.addr_nodes.len(),
+        ranking.len()
+    );
+    println!(
+        "Peak RSS: {}",
+        proc_status("VmHWM").unwrap_or_else(|| "unknown".to_owned())
+    );
+}
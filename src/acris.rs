@@ -0,0 +1,325 @@
+//! Deeds from the Department of Finance's Automated City Register
+//! Information System (ACRIS), whose grantees can link owners that HPD
+//! registration contacts miss.
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::bbl::BBL;
+use super::csv_batches::parse_records;
+use super::dataset::open_csv;
+use super::error::HpdError;
+use super::hpd_registrations::parse_date;
+
+/// The filename of ACRIS Real Property Master, as downloaded from NYC Open
+/// Data.
+pub static MASTER_FILENAME: &str = "ACRIS_-_Real_Property_Master.csv";
+
+/// The filename of ACRIS Real Property Legals.
+pub static LEGALS_FILENAME: &str = "ACRIS_-_Real_Property_Legals.csv";
+
+/// The filename of ACRIS Real Property Parties.
+pub static PARTIES_FILENAME: &str = "ACRIS_-_Real_Property_Parties.csv";
+
+/// The party type of a deed's grantees, i.e. its buyers.
+const GRANTEE_PARTY_TYPE: &str = "2";
+
+#[derive(Deserialize)]
+struct RawMaster<'a> {
+    #[serde(alias = "DOCUMENT ID")]
+    document_id: &'a str,
+
+    #[serde(alias = "DOC. TYPE")]
+    doc_type: &'a str,
+
+    #[serde(alias = "DOCUMENT DATE", default)]
+    document_date: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct RawLegal<'a> {
+    #[serde(alias = "DOCUMENT ID")]
+    document_id: &'a str,
+
+    #[serde(alias = "BOROUGH")]
+    boro: u8,
+
+    #[serde(alias = "BLOCK")]
+    block: u32,
+
+    #[serde(alias = "LOT")]
+    lot: u16,
+}
+
+#[derive(Deserialize)]
+struct RawParty<'a> {
+    #[serde(alias = "DOCUMENT ID")]
+    document_id: &'a str,
+
+    #[serde(alias = "PARTY TYPE")]
+    party_type: &'a str,
+
+    #[serde(alias = "NAME")]
+    name: &'a str,
+
+    #[serde(alias = "ADDRESS 1", default)]
+    address_1: &'a str,
+
+    #[serde(alias = "ADDRESS 2", default)]
+    address_2: &'a str,
+
+    #[serde(alias = "CITY", default)]
+    city: &'a str,
+
+    #[serde(alias = "STATE", default)]
+    state: &'a str,
+}
+
+/// A deed recorded in ACRIS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeedInfo {
+    pub document_id: String,
+    /// ACRIS's type of the document, e.g. "DEED".
+    pub doc_type: String,
+    pub document_date: NaiveDate,
+    /// The tax lots the deed transferred, sorted.
+    pub bbls: Vec<BBL>,
+}
+
+/// A grantee of a deed, and the address ACRIS gives for them.
+pub struct DeedGrantee {
+    pub name: String,
+    pub addr: String,
+    pub deed: Arc<DeedInfo>,
+}
+
+/// The grantees of the most recent deed of every tax lot, i.e. their
+/// current owners according to ACRIS.
+pub struct AcrisDeeds {
+    pub grantees: Vec<DeedGrantee>,
+    /// The number of lots skipped because their BBLs were invalid.
+    invalid_bbls: usize,
+}
+
+impl AcrisDeeds {
+    /// Loads deeds from CSV data in the format of ACRIS Real Property
+    /// Master, Legals and Parties, respectively. Only documents whose type
+    /// starts with "DEED" are considered, and lots whose BBLs are invalid
+    /// are skipped.
+    pub fn from_csv<M: std::io::Read, L: std::io::Read, P: std::io::Read>(
+        master: csv::Reader<M>,
+        legals: csv::Reader<L>,
+        parties: csv::Reader<P>,
+    ) -> Result<Self, HpdError> {
+        let mut deeds = HashMap::<String, (String, NaiveDate)>::new();
+        parse_records(
+            master,
+            |record, headers| {
+                let r: RawMaster = record.deserialize(Some(headers))?;
+                if !r.doc_type.starts_with("DEED") {
+                    return Ok(None);
+                }
+                let date = match r.document_date {
+                    Some(date) if !date.is_empty() => parse_date(date)?,
+                    _ => return Ok(None),
+                };
+                Ok(Some((
+                    r.document_id.to_owned(),
+                    r.doc_type.to_owned(),
+                    date,
+                )))
+            },
+            |deed| {
+                if let Some((document_id, doc_type, date)) = deed {
+                    deeds.insert(document_id, (doc_type, date));
+                }
+                Ok(())
+            },
+        )?;
+
+        let mut latest = HashMap::<BBL, (NaiveDate, String)>::new();
+        let mut invalid_bbls = 0;
+        parse_records(
+            legals,
+            |record, headers| {
+                let r: RawLegal = record.deserialize(Some(headers))?;
+                Ok((
+                    r.document_id.to_owned(),
+                    BBL::from_numbers(r.boro, r.block, r.lot).ok(),
+                ))
+            },
+            |(document_id, bbl)| {
+                let date = match deeds.get(&document_id) {
+                    Some((_, date)) => *date,
+                    None => return Ok(()),
+                };
+                let bbl = match bbl {
+                    Some(bbl) => bbl,
+                    None => {
+                        invalid_bbls += 1;
+                        return Ok(());
+                    }
+                };
+                let candidate = (date, document_id);
+                if latest.get(&bbl).is_none_or(|current| candidate > *current) {
+                    latest.insert(bbl, candidate);
+                }
+                Ok(())
+            },
+        )?;
+
+        let mut bbls_by_deed = HashMap::<String, Vec<BBL>>::new();
+        for (bbl, (_, document_id)) in latest {
+            bbls_by_deed.entry(document_id).or_default().push(bbl);
+        }
+        let current: HashMap<String, Arc<DeedInfo>> = bbls_by_deed
+            .into_iter()
+            .map(|(document_id, mut bbls)| {
+                bbls.sort();
+                let (doc_type, document_date) = deeds.remove(&document_id).unwrap();
+                let deed = DeedInfo {
+                    document_id: document_id.clone(),
+                    doc_type,
+                    document_date,
+                    bbls,
+                };
+                (document_id, Arc::new(deed))
+            })
+            .collect();
+
+        let mut grantees = vec![];
+        parse_records(
+            parties,
+            |record, headers| {
+                let r: RawParty = record.deserialize(Some(headers))?;
+                if r.party_type != GRANTEE_PARTY_TYPE || r.name.is_empty() || r.address_1.is_empty()
+                {
+                    return Ok(None);
+                }
+                let mut addr = format!(
+                    "{}{}{}, {} {}",
+                    r.address_1,
+                    if !r.address_2.is_empty() { " " } else { "" },
+                    r.address_2,
+                    r.city,
+                    r.state
+                );
+                addr.make_ascii_uppercase();
+                Ok(Some((
+                    r.document_id.to_owned(),
+                    r.name.to_uppercase(),
+                    addr,
+                )))
+            },
+            |party| {
+                if let Some((document_id, name, addr)) = party {
+                    if let Some(deed) = current.get(&document_id) {
+                        grantees.push(DeedGrantee {
+                            name,
+                            addr,
+                            deed: Arc::clone(deed),
+                        });
+                    }
+                }
+                Ok(())
+            },
+        )?;
+
+        eprintln!(
+            "Loaded {} grantees of {} current deeds from ACRIS (skipped {} invalid BBLs).",
+            grantees.len(),
+            current.len(),
+            invalid_bbls
+        );
+        Ok(AcrisDeeds {
+            grantees,
+            invalid_bbls,
+        })
+    }
+
+    /// The number of lots skipped because their BBLs were invalid.
+    pub fn invalid_bbls(&self) -> usize {
+        self.invalid_bbls
+    }
+
+    /// Loads deeds from a directory containing `MASTER_FILENAME`,
+    /// `LEGALS_FILENAME` and `PARTIES_FILENAME`.
+    pub fn read_dir<P: AsRef<Path>>(dir: P) -> Result<Self, HpdError> {
+        let dir = dir.as_ref();
+        AcrisDeeds::from_csv(
+            open_csv(dir.join(MASTER_FILENAME))?,
+            open_csv(dir.join(LEGALS_FILENAME))?,
+            open_csv(dir.join(PARTIES_FILENAME))?,
+        )
+    }
+}
+
+#[test]
+fn test_from_csv_only_uses_the_latest_deed_of_each_lot() {
+    let deeds = AcrisDeeds::from_csv(
+        csv::Reader::from_reader(
+            "DOCUMENT ID,DOC. TYPE,DOCUMENT DATE\n\
+             OLD,DEED,01/01/2000\n\
+             NEW,DEED,01/01/2010\n\
+             MORTGAGE,MTGE,01/01/2020\n"
+                .as_bytes(),
+        ),
+        csv::Reader::from_reader(
+            "DOCUMENT ID,BOROUGH,BLOCK,LOT\n\
+             OLD,3,1234,10\n\
+             NEW,3,1234,10\n\
+             MORTGAGE,3,1234,10\n\
+             OLD,3,1234,11\n\
+             OLD,9,1234,12\n"
+                .as_bytes(),
+        ),
+        csv::Reader::from_reader(
+            "DOCUMENT ID,PARTY TYPE,NAME,ADDRESS 1,ADDRESS 2,CITY,STATE\n\
+             OLD,1,SELLER,1 SELL ST,,NEW YORK,NY\n\
+             OLD,2,Old Buyer LLC,1 Old St,Ste 2,New York,NY\n\
+             NEW,2,NEW BUYER LLC,2 NEW ST,,BROOKLYN,NY\n\
+             NEW,2,NO ADDRESS LLC,,,,\n\
+             MORTGAGE,2,BANK,3 BANK ST,,NEW YORK,NY\n"
+                .as_bytes(),
+        ),
+    )
+    .unwrap();
+    assert_eq!(deeds.invalid_bbls(), 1);
+    let grantees: Vec<(&str, &str, &str, Vec<String>)> = deeds
+        .grantees
+        .iter()
+        .map(|grantee| {
+            (
+                grantee.name.as_str(),
+                grantee.addr.as_str(),
+                grantee.deed.document_id.as_str(),
+                grantee
+                    .deed
+                    .bbls
+                    .iter()
+                    .map(|bbl| bbl.to_string())
+                    .collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        grantees,
+        vec![
+            (
+                "OLD BUYER LLC",
+                "1 OLD ST STE 2, NEW YORK NY",
+                "OLD",
+                vec!["3012340011".to_owned()]
+            ),
+            (
+                "NEW BUYER LLC",
+                "2 NEW ST, BROOKLYN NY",
+                "NEW",
+                vec!["3012340010".to_owned()]
+            ),
+        ]
+    );
+}
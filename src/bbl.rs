@@ -1,9 +1,23 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use super::error::HpdError;
 
 // https://en.wikipedia.org/wiki/Borough,_Block_and_Lot
 
+/// The largest valid block number.
+pub const MAX_BLOCK: u32 = 99999;
+
+/// The largest valid lot number.
+pub const MAX_LOT: u16 = 9999;
+
 #[repr(u8)]
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
 pub enum Boro {
     Manhattan = 1,
     Bronx = 2,
@@ -12,6 +26,37 @@ pub enum Boro {
     StatenIsland = 5,
 }
 
+impl Boro {
+    pub const ALL: [Boro; 5] = [
+        Boro::Manhattan,
+        Boro::Bronx,
+        Boro::Brooklyn,
+        Boro::Queens,
+        Boro::StatenIsland,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boro::Manhattan => "Manhattan",
+            Boro::Bronx => "Bronx",
+            Boro::Brooklyn => "Brooklyn",
+            Boro::Queens => "Queens",
+            Boro::StatenIsland => "Staten Island",
+        }
+    }
+
+    /// The two-letter abbreviation used by e.g. NYC Open Data.
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            Boro::Manhattan => "MN",
+            Boro::Bronx => "BX",
+            Boro::Brooklyn => "BK",
+            Boro::Queens => "QN",
+            Boro::StatenIsland => "SI",
+        }
+    }
+}
+
 impl TryFrom<u8> for Boro {
     type Error = &'static str;
 
@@ -27,7 +72,44 @@ impl TryFrom<u8> for Boro {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+impl fmt::Display for Boro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Boro {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl FromStr for Boro {
+    type Err = HpdError;
+
+    /// Parses a borough's ID (e.g. "3"), name (e.g. "Brooklyn") or
+    /// abbreviation (e.g. "BK"), ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(id) = s.parse::<u8>() {
+            if let Ok(boro) = Boro::try_from(id) {
+                return Ok(boro);
+            }
+        }
+        let normalized = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        Boro::ALL
+            .iter()
+            .find(|boro| {
+                normalized.eq_ignore_ascii_case(boro.name())
+                    || normalized.eq_ignore_ascii_case(boro.abbreviation())
+            })
+            .copied()
+            .ok_or_else(|| HpdError::Parse(format!("unknown borough '{}'", s)))
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
 pub struct BBL {
     boro: Boro,
     block: u32,
@@ -36,6 +118,12 @@ pub struct BBL {
 
 impl BBL {
     pub fn from_numbers(boro: u8, block: u32, lot: u16) -> Result<BBL, &'static str> {
+        if block > MAX_BLOCK {
+            return Err("Invalid block");
+        }
+        if lot > MAX_LOT {
+            return Err("Invalid lot");
+        }
         Ok(BBL {
             boro: Boro::try_from(boro)?,
             block,
@@ -43,8 +131,106 @@ impl BBL {
         })
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{}{:0>5}{:0>4}", self.boro as u8, self.block, self.lot)
+    pub fn boro(&self) -> Boro {
+        self.boro
+    }
+
+    pub fn block(&self) -> u32 {
+        self.block
+    }
+
+    pub fn lot(&self) -> u16 {
+        self.lot
+    }
+
+    /// Whether the lot is one of a condominium's unit lots (1001-6999) or
+    /// its billing lot (7501-7599).
+    pub fn is_condo_lot(&self) -> bool {
+        matches!(self.lot, 1001..=6999 | 7501..=7599)
+    }
+}
+
+impl fmt::Display for BBL {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:0>5}{:0>4}", self.boro as u8, self.block, self.lot)
+    }
+}
+
+impl FromStr for BBL {
+    type Err = HpdError;
+
+    /// Parses a BBL in any of these forms:
+    ///
+    /// * ten digits, e.g. "3012340056";
+    /// * separated by dashes, e.g. "3-01234-0056" or "3-1234-56";
+    /// * separated by spaces, with the borough's ID, name or abbreviation,
+    ///   e.g. "Brooklyn 1234 56" or "BK 1234 56".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || HpdError::InvalidBBL(s.to_owned());
+        let trimmed = s.trim();
+        let words: Vec<&str> = trimmed.split_whitespace().collect();
+        let (boro, block, lot) = if trimmed.contains('-') {
+            let parts: Vec<&str> = trimmed.split('-').map(|part| part.trim()).collect();
+            match parts[..] {
+                [boro, block, lot] => (boro.to_owned(), block, lot),
+                _ => return Err(invalid()),
+            }
+        } else if words.len() >= 3 {
+            let (boro, rest) = words.split_at(words.len() - 2);
+            (boro.join(" "), rest[0], rest[1])
+        } else if trimmed.len() == 10 && trimmed.is_ascii() {
+            (trimmed[0..1].to_owned(), &trimmed[1..6], &trimmed[6..10])
+        } else {
+            return Err(invalid());
+        };
+
+        let number = |part: &str| -> Result<u32, HpdError> {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                Err(invalid())
+            } else {
+                part.parse().map_err(|_| invalid())
+            }
+        };
+        let boro: Boro = boro.parse().map_err(|_| invalid())?;
+        let lot = u16::try_from(number(lot)?).map_err(|_| invalid())?;
+        BBL::from_numbers(boro as u8, number(block)?, lot).map_err(|_| invalid())
+    }
+}
+
+impl Serialize for BBL {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for BBL {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for BBL {
+    fn schema_name() -> String {
+        "BBL".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "A borough, block and lot, as ten digits, e.g. \"3012340056\".".to_owned(),
+                ),
+                ..Default::default()
+            })),
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^[1-5][0-9]{9}$".to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
     }
 }
 
@@ -70,3 +256,115 @@ fn test_to_string_works() {
         "4123456789".to_owned()
     );
 }
+
+#[test]
+fn test_from_str_works() {
+    let bbl: BBL = "3012340010".parse().unwrap();
+    assert_eq!(bbl.to_string(), "3012340010");
+    assert!("6012340010".parse::<BBL>().is_err());
+    assert!("301234001".parse::<BBL>().is_err());
+    assert!("30123400x0".parse::<BBL>().is_err());
+}
+
+#[test]
+fn test_from_str_accepts_other_forms() {
+    let expected = BBL::from_numbers(3, 1234, 56).unwrap();
+    for s in &[
+        "3012340056",
+        "3-01234-0056",
+        "3-1234-56",
+        "Brooklyn 1234 56",
+        "brooklyn  1234 56",
+        "BK 1234 56",
+        "3 1234 56",
+        " 3012340056 ",
+    ] {
+        assert_eq!(s.parse::<BBL>().unwrap(), expected, "{}", s);
+    }
+    assert_eq!(
+        "Staten Island 1 2".parse::<BBL>().unwrap(),
+        BBL::from_numbers(5, 1, 2).unwrap()
+    );
+
+    for s in &[
+        "",
+        "3-1234",
+        "3-1234-56-7",
+        "3--56",
+        "Narnia 1234 56",
+        "Brooklyn 1234",
+        "Brooklyn -1234 56",
+        "3 123456 56",
+        "3 1234 12345",
+        "3-1234-65537",
+        "é12345678",
+    ] {
+        assert!(s.parse::<BBL>().is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn test_from_numbers_validates_ranges() {
+    assert!(BBL::from_numbers(1, MAX_BLOCK, MAX_LOT).is_ok());
+    assert!(BBL::from_numbers(1, MAX_BLOCK + 1, 1).is_err());
+    assert!(BBL::from_numbers(1, 1, MAX_LOT + 1).is_err());
+    assert!(BBL::from_numbers(0, 1, 1).is_err());
+}
+
+#[test]
+fn test_is_condo_lot_works() {
+    assert!(!BBL::from_numbers(1, 1, 1000).unwrap().is_condo_lot());
+    assert!(BBL::from_numbers(1, 1, 1001).unwrap().is_condo_lot());
+    assert!(!BBL::from_numbers(1, 1, 7500).unwrap().is_condo_lot());
+    assert!(BBL::from_numbers(1, 1, 7501).unwrap().is_condo_lot());
+}
+
+#[test]
+fn test_serde_uses_strings() {
+    let bbl = BBL::from_numbers(3, 1234, 56).unwrap();
+    assert_eq!(serde_json::to_string(&bbl).unwrap(), "\"3012340056\"");
+    let parsed: BBL = serde_json::from_str("\"Brooklyn 1234 56\"").unwrap();
+    assert_eq!(parsed, bbl);
+    assert!(serde_json::from_str::<BBL>("\"3-1234-123456\"").is_err());
+    assert!(serde_json::from_str::<BBL>("3012340056").is_err());
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn any_bbl() -> impl Strategy<Value = BBL> {
+        (1u8..=5, 0..=MAX_BLOCK, 0..=MAX_LOT)
+            .prop_map(|(boro, block, lot)| BBL::from_numbers(boro, block, lot).unwrap())
+    }
+
+    proptest! {
+        #[test]
+        fn parse_inverts_display(bbl in any_bbl()) {
+            prop_assert_eq!(bbl.to_string().parse::<BBL>().unwrap(), bbl);
+        }
+
+        #[test]
+        fn every_form_parses_to_the_same_bbl(bbl in any_bbl()) {
+            let dashed = format!("{}-{}-{}", bbl.boro() as u8, bbl.block(), bbl.lot());
+            let named = format!("{} {} {}", bbl.boro(), bbl.block(), bbl.lot());
+            prop_assert_eq!(dashed.parse::<BBL>().unwrap(), bbl);
+            prop_assert_eq!(named.parse::<BBL>().unwrap(), bbl);
+        }
+
+        #[test]
+        fn serde_round_trips(bbl in any_bbl()) {
+            let json = serde_json::to_string(&bbl).unwrap();
+            prop_assert_eq!(serde_json::from_str::<BBL>(&json).unwrap(), bbl);
+        }
+
+        #[test]
+        fn out_of_range_numbers_are_rejected(block in MAX_BLOCK + 1.., lot in MAX_LOT + 1..) {
+            prop_assert!(BBL::from_numbers(1, block, 1).is_err());
+            prop_assert!(BBL::from_numbers(1, 1, lot).is_err());
+            let too_big = format!("1-{}-{}", block, lot);
+            prop_assert!(too_big.parse::<BBL>().is_err());
+        }
+    }
+}
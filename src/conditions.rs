@@ -0,0 +1,257 @@
+//! Housing conditions, from HPD's Housing Maintenance Code Violations and
+//! Complaints, counted by building.
+
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::bbl::BBL;
+use super::csv_batches::parse_records;
+use super::error::HpdError;
+use super::hpd_registrations::parse_date;
+
+#[derive(Deserialize)]
+struct RawViolation<'a> {
+    #[serde(alias = "BoroID")]
+    boro: u8,
+
+    #[serde(alias = "Block")]
+    block: u32,
+
+    #[serde(alias = "Lot")]
+    lot: u16,
+
+    #[serde(alias = "Class")]
+    class: &'a str,
+
+    #[serde(alias = "ViolationStatus")]
+    status: &'a str,
+}
+
+/// The number of open class C ("immediately hazardous") violations on each
+/// tax lot.
+pub struct ViolationCounts {
+    open_class_c: HashMap<BBL, u32>,
+    /// The number of violations skipped because their BBLs were invalid.
+    invalid_bbls: usize,
+}
+
+impl ViolationCounts {
+    /// Reads violations in the format of NYC HPD Housing Maintenance Code
+    /// Violations, skipping those whose BBLs are invalid.
+    pub fn from_csv<T: std::io::Read>(rdr: csv::Reader<T>) -> Result<Self, HpdError> {
+        let mut open_class_c = HashMap::new();
+        let mut invalid_bbls = 0;
+        parse_records(
+            rdr,
+            |record, headers| {
+                let r: RawViolation = record.deserialize(Some(headers))?;
+                let is_open_class_c = r.class == "C" && r.status == "Open";
+                let bbl = BBL::from_numbers(r.boro, r.block, r.lot).ok();
+                Ok((bbl, is_open_class_c))
+            },
+            |(bbl, is_open_class_c)| {
+                match bbl {
+                    Some(bbl) if is_open_class_c => *open_class_c.entry(bbl).or_insert(0) += 1,
+                    Some(_) => {}
+                    None => invalid_bbls += 1,
+                }
+                Ok(())
+            },
+        )?;
+        eprintln!(
+            "Loaded open class C violations on {} tax lots (skipped {} invalid BBLs).",
+            open_class_c.len(),
+            invalid_bbls
+        );
+        Ok(ViolationCounts {
+            open_class_c,
+            invalid_bbls,
+        })
+    }
+
+    /// The number of violations skipped because their BBLs were invalid.
+    pub fn invalid_bbls(&self) -> usize {
+        self.invalid_bbls
+    }
+
+    pub fn open_class_c(&self, bbl: BBL) -> u32 {
+        self.open_class_c.get(&bbl).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawComplaint<'a> {
+    #[serde(alias = "BoroughID")]
+    boro: u8,
+
+    #[serde(alias = "Block")]
+    block: u32,
+
+    #[serde(alias = "Lot")]
+    lot: u16,
+
+    #[serde(alias = "ReceivedDate")]
+    received_date: &'a str,
+}
+
+/// The number of complaints received about each tax lot since a given date.
+pub struct ComplaintCounts {
+    since: NaiveDate,
+    by_bbl: HashMap<BBL, u32>,
+    /// The number of complaints skipped because their BBLs were invalid.
+    invalid_bbls: usize,
+}
+
+impl ComplaintCounts {
+    /// Reads complaints in the format of NYC HPD Housing Maintenance Code
+    /// Complaints, only counting those received on or after `since`, and
+    /// skipping those whose BBLs are invalid.
+    pub fn from_csv<T: std::io::Read>(
+        rdr: csv::Reader<T>,
+        since: NaiveDate,
+    ) -> Result<Self, HpdError> {
+        let mut by_bbl = HashMap::new();
+        let mut invalid_bbls = 0;
+        parse_records(
+            rdr,
+            |record, headers| {
+                let r: RawComplaint = record.deserialize(Some(headers))?;
+                let bbl = BBL::from_numbers(r.boro, r.block, r.lot).ok();
+                Ok((bbl, parse_date(r.received_date)?))
+            },
+            |(bbl, received_date)| {
+                match bbl {
+                    Some(bbl) if received_date >= since => *by_bbl.entry(bbl).or_insert(0) += 1,
+                    Some(_) => {}
+                    None => invalid_bbls += 1,
+                }
+                Ok(())
+            },
+        )?;
+        eprintln!(
+            "Loaded complaints about {} tax lots since {} (skipped {} invalid BBLs).",
+            by_bbl.len(),
+            since,
+            invalid_bbls
+        );
+        Ok(ComplaintCounts {
+            since,
+            by_bbl,
+            invalid_bbls,
+        })
+    }
+
+    /// The number of complaints skipped because their BBLs were invalid.
+    pub fn invalid_bbls(&self) -> usize {
+        self.invalid_bbls
+    }
+
+    /// The earliest date a counted complaint could have been received.
+    pub fn since(&self) -> NaiveDate {
+        self.since
+    }
+
+    pub fn complaints(&self, bbl: BBL) -> u32 {
+        self.by_bbl.get(&bbl).copied().unwrap_or(0)
+    }
+}
+
+/// Measures of a portfolio's housing conditions. Each is `None` if the data
+/// it needs wasn't loaded.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PortfolioConditions {
+    /// The number of open class C violations on the portfolio's tax lots.
+    pub open_class_c_violations: Option<u32>,
+    /// Open class C violations per residential unit, which requires PLUTO.
+    pub open_class_c_violations_per_unit: Option<f64>,
+    /// The number of complaints about the portfolio's tax lots since
+    /// `complaints_since`.
+    pub recent_complaints: Option<u32>,
+    /// Recent complaints per building.
+    pub recent_complaints_per_building: Option<f64>,
+    /// The date complaints were counted from (a year ago by default), as
+    /// YYYY-MM-DD.
+    pub complaints_since: Option<String>,
+}
+
+impl PortfolioConditions {
+    /// Describes the conditions in a sentence or two, for people to read.
+    pub fn describe(&self) -> Vec<String> {
+        let mut sentences = vec![];
+        if let Some(violations) = self.open_class_c_violations {
+            sentences.push(match self.open_class_c_violations_per_unit {
+                Some(per_unit) => format!(
+                    "It has {} open class C violations ({:.2} per unit).",
+                    violations, per_unit
+                ),
+                None => format!("It has {} open class C violations.", violations),
+            });
+        }
+        if let (Some(complaints), Some(since)) = (self.recent_complaints, &self.complaints_since) {
+            sentences.push(match self.recent_complaints_per_building {
+                Some(per_building) => format!(
+                    "It has had {} complaints since {} ({:.2} per building).",
+                    complaints, since, per_building
+                ),
+                None => format!("It has had {} complaints since {}.", complaints, since),
+            });
+        }
+        sentences
+    }
+}
+
+/// Divides `count` by `total`, if both are known and `total` isn't zero.
+pub(crate) fn ratio(count: Option<u32>, total: Option<usize>) -> Option<f64> {
+    match (count, total) {
+        (Some(count), Some(total)) if total > 0 => Some(count as f64 / total as f64),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_violation_counts_only_include_open_class_c() {
+    let violations = ViolationCounts::from_csv(csv::Reader::from_reader(
+        "ViolationID,BoroID,Block,Lot,Class,ViolationStatus\n\
+         1,3,1234,10,C,Open\n\
+         2,3,1234,10,C,Open\n\
+         3,3,1234,10,C,Close\n\
+         4,3,1234,10,B,Open\n\
+         5,3,1234,11,C,Open\n\
+         6,9,1234,12,C,Open\n"
+            .as_bytes(),
+    ))
+    .unwrap();
+    assert_eq!(violations.open_class_c("3012340010".parse().unwrap()), 2);
+    assert_eq!(violations.open_class_c("3012340011".parse().unwrap()), 1);
+    assert_eq!(violations.open_class_c("3012340012".parse().unwrap()), 0);
+    assert_eq!(violations.invalid_bbls(), 1);
+}
+
+#[test]
+fn test_complaint_counts_only_include_recent_complaints() {
+    let since = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+    let complaints = ComplaintCounts::from_csv(
+        csv::Reader::from_reader(
+            "ComplaintID,BoroughID,Block,Lot,ReceivedDate\n\
+             1,3,1234,10,12/31/2019\n\
+             2,3,1234,10,01/01/2020\n\
+             3,3,1234,10,06/15/2020\n\
+             4,9,1234,10,06/15/2020\n"
+                .as_bytes(),
+        ),
+        since,
+    )
+    .unwrap();
+    assert_eq!(complaints.complaints("3012340010".parse().unwrap()), 2);
+    assert_eq!(complaints.invalid_bbls(), 1);
+}
+
+#[test]
+fn test_ratio_works() {
+    assert_eq!(ratio(Some(3), Some(2)), Some(1.5));
+    assert_eq!(ratio(Some(3), Some(0)), None);
+    assert_eq!(ratio(None, Some(2)), None);
+    assert_eq!(ratio(Some(3), None), None);
+}
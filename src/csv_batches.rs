@@ -0,0 +1,63 @@
+use csv::StringRecord;
+use rayon::prelude::*;
+
+use super::error::HpdError;
+
+/// The number of records parsed in parallel at a time. Reading in batches
+/// means we never need to hold an entire CSV file in memory.
+const BATCH_SIZE: usize = 10_000;
+
+/// Reads every record in the CSV, parsing batches of them in parallel with
+/// `parse` and then passing the results to `consume` in their original
+/// order, so that the results don't depend on the number of threads.
+pub fn parse_records<R, T, P, C>(
+    mut rdr: csv::Reader<R>,
+    parse: P,
+    mut consume: C,
+) -> Result<(), HpdError>
+where
+    R: std::io::Read,
+    T: Send,
+    P: Fn(&StringRecord, &StringRecord) -> Result<T, HpdError> + Sync,
+    C: FnMut(T) -> Result<(), HpdError>,
+{
+    let headers = rdr.headers()?.clone();
+    let mut records = vec![StringRecord::new(); BATCH_SIZE];
+
+    loop {
+        let mut len = 0;
+        while len < BATCH_SIZE && rdr.read_record(&mut records[len])? {
+            len += 1;
+        }
+        let parsed: Vec<Result<T, HpdError>> = records[..len]
+            .par_iter()
+            .map(|record| parse(record, &headers))
+            .collect();
+        for item in parsed {
+            consume(item?)?;
+        }
+        if len < BATCH_SIZE {
+            return Ok(());
+        }
+    }
+}
+
+#[test]
+fn test_parse_records_preserves_order_across_batches() {
+    let count = BATCH_SIZE * 2 + 5;
+    let mut data = "n\n".to_owned();
+    for i in 0..count {
+        data.push_str(&format!("{}\n", i));
+    }
+    let mut result = vec![];
+    parse_records(
+        csv::Reader::from_reader(data.as_bytes()),
+        |record, _| Ok(record[0].parse::<usize>().unwrap()),
+        |n| {
+            result.push(n);
+            Ok(())
+        },
+    )
+    .unwrap();
+    assert_eq!(result, (0..count).collect::<Vec<_>>());
+}
@@ -0,0 +1,327 @@
+use chrono::Duration;
+use petgraph::graph::NodeIndex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use super::acris::AcrisDeeds;
+use super::bbl::BBL;
+use super::conditions::{ComplaintCounts, ViolationCounts};
+use super::error::HpdError;
+use super::evictions::EvictionCounts;
+use super::hpd_graph::HpdGraph;
+use super::hpd_registrations::{CountBy, HpdRegistrationMap};
+use super::pluto::PlutoMap;
+use super::portfolio::{Portfolio, PortfolioMap};
+use super::portfolio_id::PortfolioIdSnapshot;
+use super::stabilization::StabilizedUnitCounts;
+
+/// The filename of NYC HPD Registrations, as downloaded from NYC Open Data.
+pub const REGISTRATIONS_FILENAME: &str = "Multiple_Dwelling_Registrations.csv";
+
+/// The filename of NYC HPD Registration Contacts, as downloaded from NYC
+/// Open Data.
+pub const CONTACTS_FILENAME: &str = "Registration_Contacts.csv";
+
+/// The default for `DatasetBuilder::max_expiration_age`, in days.
+pub const DEFAULT_MAX_EXPIRATION_AGE: i64 = 90;
+
+/// Opens a CSV file, reporting its path if it can't be opened.
+pub fn open_csv<P: AsRef<Path>>(path: P) -> Result<csv::Reader<File>, HpdError> {
+    let path = path.as_ref();
+    match File::open(path) {
+        Ok(file) => Ok(csv::Reader::from_reader(file)),
+        Err(e) => Err(HpdError::Io(path.to_path_buf(), e)),
+    }
+}
+
+/// The options a `Dataset` was loaded with, as recorded in its output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct DatasetOptions {
+    /// Registrations that expired more than this many days ago were ignored.
+    pub max_expiration_age_days: i64,
+    /// Whether corporation names were added to the graph.
+    pub include_corps: bool,
+    /// Whether expired registrations were kept regardless of
+    /// `max_expiration_age_days`.
+    pub keep_expired: bool,
+    /// Whether the grantees of ACRIS deeds were linked into the graph.
+    pub link_deeds: bool,
+    /// How buildings were counted.
+    pub count_by: CountBy,
+    /// Whether PLUTO's records were loaded.
+    pub pluto: bool,
+    /// Whether counts of HPD violations were loaded.
+    pub violations: bool,
+    /// The date complaints were counted from, as YYYY-MM-DD, if they were
+    /// loaded.
+    pub complaints_since: Option<String>,
+    /// The date evictions were counted from, as YYYY-MM-DD, if they were
+    /// loaded.
+    pub evictions_since: Option<String>,
+    /// Whether counts of rent-stabilized units were loaded.
+    pub stabilized_units: bool,
+    /// Whether portfolio IDs were carried forward from a previous snapshot.
+    pub previous_ids: bool,
+}
+
+/// Configures how a `Dataset` is loaded.
+pub struct DatasetBuilder {
+    max_expiration_age: Duration,
+    include_corps: bool,
+    keep_expired: bool,
+    count_by: CountBy,
+    previous_ids: Option<PortfolioIdSnapshot>,
+    pluto: Option<PlutoMap>,
+    violations: Option<ViolationCounts>,
+    complaints: Option<ComplaintCounts>,
+    evictions: Option<EvictionCounts>,
+    stabilized_units: Option<StabilizedUnitCounts>,
+    deeds: Option<AcrisDeeds>,
+}
+
+impl Default for DatasetBuilder {
+    fn default() -> Self {
+        DatasetBuilder::new()
+    }
+}
+
+impl DatasetBuilder {
+    pub fn new() -> Self {
+        DatasetBuilder {
+            max_expiration_age: Duration::days(DEFAULT_MAX_EXPIRATION_AGE),
+            include_corps: false,
+            keep_expired: false,
+            count_by: CountBy::default(),
+            previous_ids: None,
+            pluto: None,
+            violations: None,
+            complaints: None,
+            evictions: None,
+            stabilized_units: None,
+            deeds: None,
+        }
+    }
+
+    /// Ignore registrations that expired more than this long ago.
+    pub fn max_expiration_age(mut self, max_expiration_age: Duration) -> Self {
+        self.max_expiration_age = max_expiration_age;
+        self
+    }
+
+    /// Add corporation names to the graph, rather than only the names of
+    /// individuals.
+    pub fn include_corps(mut self, include_corps: bool) -> Self {
+        self.include_corps = include_corps;
+        self
+    }
+
+    /// Keep every registration, regardless of `max_expiration_age`. This is
+    /// needed to look at portfolios as of past dates (see `history`).
+    pub fn keep_expired(mut self, keep_expired: bool) -> Self {
+        self.keep_expired = keep_expired;
+        self
+    }
+
+    /// Record that buildings are counted as described by `count_by`.
+    pub fn count_by(mut self, count_by: CountBy) -> Self {
+        self.count_by = count_by;
+        self
+    }
+
+    /// Carry portfolio IDs forward from a previous snapshot of the data.
+    pub fn previous_ids(mut self, previous_ids: PortfolioIdSnapshot) -> Self {
+        self.previous_ids = Some(previous_ids);
+        self
+    }
+
+    /// Join PLUTO's records to the registrations, to count residential
+    /// units and describe buildings.
+    pub fn pluto(mut self, pluto: PlutoMap) -> Self {
+        self.pluto = Some(pluto);
+        self
+    }
+
+    /// Join counts of HPD violations to the registrations, to measure
+    /// portfolios' housing conditions.
+    pub fn violations(mut self, violations: ViolationCounts) -> Self {
+        self.violations = Some(violations);
+        self
+    }
+
+    /// Join counts of HPD complaints to the registrations, to measure
+    /// portfolios' housing conditions.
+    pub fn complaints(mut self, complaints: ComplaintCounts) -> Self {
+        self.complaints = Some(complaints);
+        self
+    }
+
+    /// Join counts of marshal evictions to the registrations, to see which
+    /// portfolios evict the most.
+    pub fn evictions(mut self, evictions: EvictionCounts) -> Self {
+        self.evictions = Some(evictions);
+        self
+    }
+
+    /// Join counts of rent-stabilized units to the registrations, to see
+    /// how many stabilized units portfolios control and lose.
+    pub fn stabilized_units(mut self, stabilized_units: StabilizedUnitCounts) -> Self {
+        self.stabilized_units = Some(stabilized_units);
+        self
+    }
+
+    /// Link the names and addresses of the grantees of ACRIS deeds, so
+    /// that portfolios are also connected by who bought their buildings.
+    pub fn deeds(mut self, deeds: AcrisDeeds) -> Self {
+        self.deeds = Some(deeds);
+        self
+    }
+
+    /// Loads the dataset from CSV data in the format of NYC HPD Registrations
+    /// and NYC HPD Registration Contacts, respectively.
+    pub fn read<R: std::io::Read, C: std::io::Read>(
+        self,
+        registrations: R,
+        contacts: C,
+    ) -> Result<Dataset, HpdError> {
+        self.read_csv(
+            csv::Reader::from_reader(registrations),
+            csv::Reader::from_reader(contacts),
+        )
+    }
+
+    /// Loads the dataset from a directory containing `REGISTRATIONS_FILENAME`
+    /// and `CONTACTS_FILENAME`.
+    pub fn read_dir<P: AsRef<Path>>(self, dir: P) -> Result<Dataset, HpdError> {
+        let dir = dir.as_ref();
+        self.read_csv(
+            open_csv(dir.join(REGISTRATIONS_FILENAME))?,
+            open_csv(dir.join(CONTACTS_FILENAME))?,
+        )
+    }
+
+    fn read_csv<R: std::io::Read, C: std::io::Read>(
+        self,
+        registrations: csv::Reader<R>,
+        contacts: csv::Reader<C>,
+    ) -> Result<Dataset, HpdError> {
+        let options = DatasetOptions {
+            max_expiration_age_days: self.max_expiration_age.num_days(),
+            include_corps: self.include_corps,
+            keep_expired: self.keep_expired,
+            link_deeds: self.deeds.is_some(),
+            count_by: self.count_by,
+            pluto: self.pluto.is_some(),
+            violations: self.violations.is_some(),
+            complaints_since: self
+                .complaints
+                .as_ref()
+                .map(|complaints| complaints.since().to_string()),
+            evictions_since: self
+                .evictions
+                .as_ref()
+                .map(|evictions| evictions.since().to_string()),
+            stabilized_units: self.stabilized_units.is_some(),
+            previous_ids: self.previous_ids.is_some(),
+        };
+        let mut regs = if self.keep_expired {
+            HpdRegistrationMap::from_csv_all(registrations)?
+        } else {
+            HpdRegistrationMap::from_csv(registrations, self.max_expiration_age)?
+        };
+        if let Some(pluto) = self.pluto {
+            regs.join_pluto(pluto);
+        }
+        if let Some(violations) = self.violations {
+            regs.join_violations(violations);
+        }
+        if let Some(complaints) = self.complaints {
+            regs.join_complaints(complaints);
+        }
+        if let Some(evictions) = self.evictions {
+            regs.join_evictions(evictions);
+        }
+        if let Some(stabilized_units) = self.stabilized_units {
+            regs.join_stabilized_units(stabilized_units);
+        }
+        let hpd = HpdGraph::from_csv_with_deeds(
+            contacts,
+            &regs,
+            self.include_corps,
+            self.deeds.as_ref(),
+        )?;
+
+        Ok(Dataset {
+            regs,
+            hpd,
+            options,
+            previous_ids: self.previous_ids,
+            portfolios: OnceLock::new(),
+            nodes_by_bbl: OnceLock::new(),
+        })
+    }
+}
+
+/// A snapshot of HPD's registration data and the graph built from it.
+pub struct Dataset {
+    pub regs: HpdRegistrationMap,
+    pub hpd: HpdGraph,
+    options: DatasetOptions,
+    previous_ids: Option<PortfolioIdSnapshot>,
+    /// The portfolios, inferred the first time they're needed.
+    portfolios: OnceLock<PortfolioMap>,
+    /// The node of each building, found the first time it's needed.
+    nodes_by_bbl: OnceLock<HashMap<BBL, NodeIndex<u32>>>,
+}
+
+impl Dataset {
+    pub fn options(&self) -> &DatasetOptions {
+        &self.options
+    }
+
+    /// Infers all the portfolios in the graph, the first time it's called.
+    pub fn portfolios(&self) -> &PortfolioMap {
+        self.portfolios
+            .get_or_init(|| self.portfolios_with_ids(self.previous_ids.as_ref()))
+    }
+
+    /// Like `portfolios`, but carries IDs forward from the given snapshot
+    /// instead of the one the dataset was built with (if any).
+    pub fn portfolios_with_ids(&self, previous_ids: Option<&PortfolioIdSnapshot>) -> PortfolioMap {
+        PortfolioMap::from_graph(&self.hpd, &self.regs, previous_ids)
+    }
+
+    /// Finds the name that is either exactly `search`, or the only name
+    /// that contains it.
+    pub fn find_name(&self, search: &str) -> Result<NodeIndex<u32>, HpdError> {
+        self.hpd.find_name(search)
+    }
+
+    /// Returns the portfolio containing the given building.
+    pub fn portfolio_with_bbl(&self, bbl: BBL) -> Result<Arc<Portfolio>, HpdError> {
+        let not_found = || HpdError::NotFound {
+            what: "portfolio",
+            search: bbl.to_string(),
+        };
+        let node = *self
+            .nodes_by_bbl
+            .get_or_init(|| self.hpd.nodes_by_bbl(&self.regs))
+            .get(&bbl)
+            .ok_or_else(not_found)?;
+        self.portfolios().for_node(node).ok_or_else(not_found)
+    }
+
+    /// Returns the portfolio containing the name found by `find_name`.
+    pub fn portfolio_with_name(&self, search: &str) -> Result<Arc<Portfolio>, HpdError> {
+        let node = self.find_name(search)?;
+        self.portfolios()
+            .for_node(node)
+            .ok_or_else(|| HpdError::NotFound {
+                what: "portfolio",
+                search: search.to_owned(),
+            })
+    }
+}
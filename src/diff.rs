@@ -0,0 +1,373 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use super::hpd_registrations::{CountBy, HpdRegistrationMap};
+use super::portfolio::{Portfolio, PortfolioMap};
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Serialize)]
+pub struct PortfolioDiff {
+    id: Arc<String>,
+    name: Arc<String>,
+    status: DiffStatus,
+    old_building_count: usize,
+    new_building_count: usize,
+    buildings_added: Vec<String>,
+    buildings_removed: Vec<String>,
+    names_gained: Vec<String>,
+    names_lost: Vec<String>,
+    addresses_gained: Vec<String>,
+    addresses_lost: Vec<String>,
+}
+
+/// A portfolio in the new snapshot that contains names or addresses from
+/// more than one portfolio in the old snapshot.
+#[derive(Serialize)]
+pub struct MergeEvent {
+    into: Arc<String>,
+    name: Arc<String>,
+    from: Vec<Arc<String>>,
+}
+
+/// A portfolio in the old snapshot whose names or addresses are spread
+/// across more than one portfolio in the new snapshot.
+#[derive(Serialize)]
+pub struct SplitEvent {
+    from: Arc<String>,
+    name: Arc<String>,
+    into: Vec<Arc<String>>,
+}
+
+#[derive(Serialize)]
+pub struct SnapshotDiff {
+    portfolios: Vec<PortfolioDiff>,
+    merges: Vec<MergeEvent>,
+    splits: Vec<SplitEvent>,
+}
+
+struct Snapshot<'a> {
+    portfolios: Vec<&'a Arc<Portfolio>>,
+    regs: &'a HpdRegistrationMap,
+    building_counts: Vec<usize>,
+}
+
+impl<'a> Snapshot<'a> {
+    fn new(map: &'a PortfolioMap, regs: &'a HpdRegistrationMap, count_by: CountBy) -> Self {
+        let portfolios: Vec<_> = map.iter().collect();
+        let building_counts = portfolios
+            .iter()
+            .map(|p| p.building_count(regs, count_by))
+            .collect();
+        Snapshot {
+            portfolios,
+            regs,
+            building_counts,
+        }
+    }
+
+    fn member_index(&self) -> HashMap<(bool, &'a str), usize> {
+        let mut index = HashMap::new();
+        for (i, portfolio) in self.portfolios.iter().copied().enumerate() {
+            for name in portfolio.names() {
+                index.insert((true, name), i);
+            }
+            for addr in portfolio.bizaddrs() {
+                index.insert((false, addr), i);
+            }
+        }
+        index
+    }
+
+    fn bbls(&self, i: usize) -> BTreeSet<String> {
+        self.portfolios[i]
+            .bbls(self.regs)
+            .iter()
+            .map(|bbl| bbl.to_string())
+            .collect()
+    }
+}
+
+fn difference<T: Ord + ToString>(a: &BTreeSet<T>, b: &BTreeSet<T>) -> Vec<String> {
+    a.difference(b).map(|value| value.to_string()).collect()
+}
+
+fn portfolio_diff(
+    old: &Snapshot,
+    new: &Snapshot,
+    pair: (Option<usize>, Option<usize>),
+) -> PortfolioDiff {
+    let (old_p, new_p) = (
+        pair.0.map(|i| old.portfolios[i]),
+        pair.1.map(|i| new.portfolios[i]),
+    );
+    let old_bbls = pair.0.map(|i| old.bbls(i)).unwrap_or_default();
+    let new_bbls = pair.1.map(|i| new.bbls(i)).unwrap_or_default();
+    let old_names = old_p.map(|p| p.names()).unwrap_or_default();
+    let new_names = new_p.map(|p| p.names()).unwrap_or_default();
+    let old_addrs = old_p.map(|p| p.bizaddrs()).unwrap_or_default();
+    let new_addrs = new_p.map(|p| p.bizaddrs()).unwrap_or_default();
+    let (portfolio, status) = match (old_p, new_p) {
+        (Some(_), Some(p)) => (p, DiffStatus::Changed),
+        (None, Some(p)) => (p, DiffStatus::Added),
+        (Some(p), None) => (p, DiffStatus::Removed),
+        (None, None) => unreachable!(),
+    };
+
+    PortfolioDiff {
+        id: portfolio.id(),
+        name: portfolio.name(),
+        status,
+        old_building_count: pair.0.map(|i| old.building_counts[i]).unwrap_or(0),
+        new_building_count: pair.1.map(|i| new.building_counts[i]).unwrap_or(0),
+        buildings_added: difference(&new_bbls, &old_bbls),
+        buildings_removed: difference(&old_bbls, &new_bbls),
+        names_gained: difference(&new_names, &old_names),
+        names_lost: difference(&old_names, &new_names),
+        addresses_gained: difference(&new_addrs, &old_addrs),
+        addresses_lost: difference(&old_addrs, &new_addrs),
+    }
+}
+
+impl PortfolioDiff {
+    fn is_unchanged(&self) -> bool {
+        self.status == DiffStatus::Changed
+            && self.buildings_added.is_empty()
+            && self.buildings_removed.is_empty()
+            && self.names_gained.is_empty()
+            && self.names_lost.is_empty()
+            && self.addresses_gained.is_empty()
+            && self.addresses_lost.is_empty()
+    }
+}
+
+impl SnapshotDiff {
+    /// Compares two snapshots of the data. The portfolios in `new` are
+    /// expected to have had their IDs carried forward from `old`, so that
+    /// portfolios with the same ID are considered the same portfolio.
+    ///
+    /// Only portfolios (and merges and splits involving portfolios) with at
+    /// least `min_buildings` buildings in either snapshot are included.
+    pub fn new(
+        old_map: &PortfolioMap,
+        old_regs: &HpdRegistrationMap,
+        new_map: &PortfolioMap,
+        new_regs: &HpdRegistrationMap,
+        count_by: CountBy,
+        min_buildings: usize,
+    ) -> Self {
+        let old = Snapshot::new(old_map, old_regs, count_by);
+        let new = Snapshot::new(new_map, new_regs, count_by);
+
+        let mut pairs = BTreeMap::<Arc<String>, (Option<usize>, Option<usize>)>::new();
+        for (i, portfolio) in old.portfolios.iter().enumerate() {
+            pairs.entry(portfolio.id()).or_default().0 = Some(i);
+        }
+        for (i, portfolio) in new.portfolios.iter().enumerate() {
+            pairs.entry(portfolio.id()).or_default().1 = Some(i);
+        }
+
+        let is_big_enough = |pair: &(Option<usize>, Option<usize>)| {
+            pair.0.map(|i| old.building_counts[i]).unwrap_or(0) >= min_buildings
+                || pair.1.map(|i| new.building_counts[i]).unwrap_or(0) >= min_buildings
+        };
+
+        let portfolios = pairs
+            .values()
+            .filter(|pair| is_big_enough(pair))
+            .map(|pair| portfolio_diff(&old, &new, *pair))
+            .filter(|diff| !diff.is_unchanged())
+            .collect();
+
+        let old_members = old.member_index();
+        let mut new_sources = BTreeMap::<usize, BTreeSet<usize>>::new();
+        let mut old_destinations = BTreeMap::<usize, BTreeSet<usize>>::new();
+        for (member, new_idx) in new.member_index() {
+            if let Some(old_idx) = old_members.get(&member) {
+                new_sources.entry(new_idx).or_default().insert(*old_idx);
+                old_destinations
+                    .entry(*old_idx)
+                    .or_default()
+                    .insert(new_idx);
+            }
+        }
+
+        let ids = |snapshot: &Snapshot, indices: &BTreeSet<usize>| {
+            let mut ids: Vec<_> = indices
+                .iter()
+                .map(|i| snapshot.portfolios[*i].id())
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        let mut merges: Vec<MergeEvent> = new_sources
+            .iter()
+            .filter(|(i, sources)| sources.len() > 1 && new.building_counts[**i] >= min_buildings)
+            .map(|(i, sources)| MergeEvent {
+                into: new.portfolios[*i].id(),
+                name: new.portfolios[*i].name(),
+                from: ids(&old, sources),
+            })
+            .collect();
+        merges.sort_by(|a, b| a.into.cmp(&b.into));
+
+        let mut splits: Vec<SplitEvent> = old_destinations
+            .iter()
+            .filter(|(i, dests)| dests.len() > 1 && old.building_counts[**i] >= min_buildings)
+            .map(|(i, dests)| SplitEvent {
+                from: old.portfolios[*i].id(),
+                name: old.portfolios[*i].name(),
+                into: ids(&new, dests),
+            })
+            .collect();
+        splits.sort_by(|a, b| a.from.cmp(&b.from));
+
+        SnapshotDiff {
+            portfolios,
+            merges,
+            splits,
+        }
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        let join = |ids: &Vec<Arc<String>>| {
+            ids.iter()
+                .map(|id| id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        for diff in &self.portfolios {
+            let symbol = match diff.status {
+                DiffStatus::Added => '+',
+                DiffStatus::Removed => '-',
+                DiffStatus::Changed => '~',
+            };
+            writeln!(
+                out,
+                "{} {} [{}]: {} -> {} buildings",
+                symbol, diff.name, diff.id, diff.old_building_count, diff.new_building_count
+            )
+            .unwrap();
+            let changes = [
+                ("+ building", &diff.buildings_added),
+                ("- building", &diff.buildings_removed),
+                ("+ name", &diff.names_gained),
+                ("- name", &diff.names_lost),
+                ("+ address", &diff.addresses_gained),
+                ("- address", &diff.addresses_lost),
+            ];
+            for (label, values) in changes.iter() {
+                for value in values.iter() {
+                    writeln!(out, "    {} {}", label, value).unwrap();
+                }
+            }
+        }
+
+        for merge in &self.merges {
+            writeln!(
+                out,
+                "Merged into {} [{}]: {}",
+                merge.name,
+                merge.into,
+                join(&merge.from)
+            )
+            .unwrap();
+        }
+
+        for split in &self.splits {
+            writeln!(
+                out,
+                "Split {} [{}] into: {}",
+                split.name,
+                split.from,
+                join(&split.into)
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hpd_graph::HpdGraph;
+    use crate::portfolio_id::PortfolioIdSnapshot;
+    use chrono::Duration;
+
+    const REGS: &str = "RegistrationID,BoroID,Block,Lot,BIN,RegistrationEndDate
+1,1,1,1,1000001,12/31/2099
+2,1,1,2,1000002,12/31/2099
+3,1,1,3,1000003,12/31/2099
+";
+
+    const HEADER: &str = "RegistrationContactID,RegistrationID,Type,CorporationName,FirstName,LastName,BusinessHouseNumber,BusinessStreetName,BusinessApartment,BusinessCity,BusinessState\n";
+
+    fn load(contacts: &str) -> (HpdRegistrationMap, HpdGraph) {
+        let regs = HpdRegistrationMap::from_csv(
+            csv::Reader::from_reader(REGS.as_bytes()),
+            Duration::days(90),
+        )
+        .unwrap();
+        let contacts = format!("{}{}", HEADER, contacts);
+        let hpd = HpdGraph::from_csv(csv::Reader::from_reader(contacts.as_bytes()), &regs, false)
+            .unwrap();
+        (regs, hpd)
+    }
+
+    #[test]
+    fn test_diff_reports_growth_and_splits() {
+        let (old_regs, old_hpd) = load(
+            "1,1,HeadOfficer,,BOOP,JONES,1,BOOP ST,,NEW YORK,NY
+2,2,HeadOfficer,,BLAP,SMITH,1,BOOP ST,,NEW YORK,NY
+",
+        );
+        let (new_regs, new_hpd) = load(
+            "1,1,HeadOfficer,,BOOP,JONES,1,BOOP ST,,NEW YORK,NY
+2,2,HeadOfficer,,BOOP,JONES,1,BOOP ST,,NEW YORK,NY
+3,3,HeadOfficer,,BOOP,JONES,1,BOOP ST,,NEW YORK,NY
+4,2,HeadOfficer,,BLAP,SMITH,2,BLAP ST,,NEW YORK,NY
+",
+        );
+        let old_map = PortfolioMap::from_graph(&old_hpd, &old_regs, None);
+        let snapshot = PortfolioIdSnapshot::from_portfolios(&old_map.id_snapshot(&old_regs));
+        let new_map = PortfolioMap::from_graph(&new_hpd, &new_regs, Some(&snapshot));
+        let diff = SnapshotDiff::new(
+            &old_map,
+            &old_regs,
+            &new_map,
+            &new_regs,
+            CountBy::default(),
+            0,
+        );
+
+        assert_eq!(diff.portfolios.len(), 2);
+        let grown = diff
+            .portfolios
+            .iter()
+            .find(|p| p.status == DiffStatus::Changed)
+            .unwrap();
+        assert_eq!(grown.name.as_str(), "BOOP JONES's portfolio");
+        assert_eq!(grown.buildings_added, vec!["1000010003".to_owned()]);
+        assert_eq!(grown.names_lost, vec!["BLAP SMITH".to_owned()]);
+        assert_eq!(diff.splits.len(), 1);
+        assert_eq!(diff.splits[0].into.len(), 2);
+        assert!(diff.merges.is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Everything that can go wrong while building or querying the graph.
+#[derive(Debug)]
+pub enum HpdError {
+    /// A file couldn't be read or written.
+    Io(PathBuf, std::io::Error),
+
+    /// A CSV file couldn't be read or written, e.g. because it's malformed
+    /// or is missing a column.
+    Csv(csv::Error),
+
+    /// A value in an input file couldn't be parsed.
+    Parse(String),
+
+    /// Nothing matched a search, e.g. for a name.
+    NotFound { what: &'static str, search: String },
+
+    /// More than one thing matched a search that should match only one.
+    AmbiguousMatch {
+        what: &'static str,
+        search: String,
+        candidates: Vec<String>,
+    },
+
+    /// A borough, block and lot didn't form a valid BBL.
+    InvalidBBL(String),
+
+    /// The HTTP server couldn't be started, e.g. because its port is in use.
+    Server(String),
+
+    /// A SQLite database couldn't be written.
+    Database(rusqlite::Error),
+}
+
+/// The maximum number of candidates listed for an ambiguous match.
+const MAX_CANDIDATES_SHOWN: usize = 10;
+
+impl HpdError {
+    /// The process exit code for this kind of error, so that scripts can
+    /// tell e.g. a failed search apart from corrupt input. These start at
+    /// 3 to stay clear of the code used for invalid command-line arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HpdError::NotFound { .. } => 3,
+            HpdError::AmbiguousMatch { .. } => 4,
+            HpdError::Io(..) => 5,
+            HpdError::Csv(_) => 6,
+            HpdError::Parse(_) => 7,
+            HpdError::InvalidBBL(_) => 8,
+            HpdError::Server(_) => 9,
+            HpdError::Database(_) => 10,
+        }
+    }
+}
+
+impl fmt::Display for HpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HpdError::Io(path, e) => write!(f, "Unable to access '{}': {}", path.display(), e),
+            HpdError::Csv(e) => write!(f, "Invalid CSV: {}", e),
+            HpdError::Parse(message) => write!(f, "Invalid input: {}", message),
+            HpdError::NotFound { what, search } => {
+                write!(f, "Unable to find a {} matching '{}'.", what, search)
+            }
+            HpdError::AmbiguousMatch {
+                what,
+                search,
+                candidates,
+            } => {
+                write!(
+                    f,
+                    "Found {} {}s matching '{}', please be more specific:",
+                    candidates.len(),
+                    what,
+                    search
+                )?;
+                for candidate in candidates.iter().take(MAX_CANDIDATES_SHOWN) {
+                    write!(f, "\n  {}", candidate)?;
+                }
+                if candidates.len() > MAX_CANDIDATES_SHOWN {
+                    write!(f, "\n  ...")?;
+                }
+                Ok(())
+            }
+            HpdError::InvalidBBL(bbl) => write!(f, "Invalid BBL: {}", bbl),
+            HpdError::Server(message) => write!(f, "Unable to start server: {}", message),
+            HpdError::Database(e) => write!(f, "Unable to write database: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HpdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HpdError::Io(_, e) => Some(e),
+            HpdError::Csv(e) => Some(e),
+            HpdError::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<csv::Error> for HpdError {
+    fn from(e: csv::Error) -> Self {
+        HpdError::Csv(e)
+    }
+}
+
+impl From<rusqlite::Error> for HpdError {
+    fn from(e: rusqlite::Error) -> Self {
+        HpdError::Database(e)
+    }
+}
+
+#[test]
+fn test_ambiguous_match_message_lists_candidates() {
+    let e = HpdError::AmbiguousMatch {
+        what: "name",
+        search: "ZAP".to_owned(),
+        candidates: vec!["ZIP ZAP".to_owned(), "ZORP ZAP".to_owned()],
+    };
+    assert_eq!(
+        e.to_string(),
+        "Found 2 names matching 'ZAP', please be more specific:\n  ZIP ZAP\n  ZORP ZAP"
+    );
+    assert_eq!(e.exit_code(), 4);
+}
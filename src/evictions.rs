@@ -0,0 +1,209 @@
+//! Evictions carried out by NYC marshals, counted by building.
+
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::bbl::BBL;
+use super::csv_batches::parse_records;
+use super::error::HpdError;
+use super::hpd_registrations::parse_date;
+
+#[derive(Deserialize)]
+struct RawEviction<'a> {
+    #[serde(alias = "BBL", default)]
+    bbl: Option<&'a str>,
+
+    #[serde(alias = "BIN", default)]
+    bin: Option<u32>,
+
+    #[serde(alias = "Executed Date")]
+    executed_date: &'a str,
+
+    #[serde(alias = "Residential/Commercial", default)]
+    kind: Option<&'a str>,
+}
+
+/// Identifies the building an eviction was carried out in.
+enum EvictionBuilding {
+    Bbl(BBL),
+    Bin(u32),
+    InvalidBbl,
+}
+
+/// The number of residential evictions carried out in each building since
+/// a given date.
+pub struct EvictionCounts {
+    since: NaiveDate,
+    by_bbl: HashMap<BBL, u32>,
+    /// Evictions with a BIN but no BBL.
+    by_bin: HashMap<u32, u32>,
+    /// The number of evictions skipped because their BBLs were invalid.
+    invalid_bbls: usize,
+}
+
+impl EvictionCounts {
+    /// Reads evictions in the format of NYC Open Data's Evictions, only
+    /// counting residential evictions executed on or after `since`.
+    /// Evictions are matched to buildings by BBL, or by BIN if they have
+    /// no BBL; those with neither, or with an invalid BBL, are skipped.
+    pub fn from_csv<T: std::io::Read>(
+        rdr: csv::Reader<T>,
+        since: NaiveDate,
+    ) -> Result<Self, HpdError> {
+        let mut by_bbl = HashMap::new();
+        let mut by_bin = HashMap::new();
+        let mut skipped = 0;
+        let mut invalid_bbls = 0;
+        parse_records(
+            rdr,
+            |record, headers| {
+                let r: RawEviction = record.deserialize(Some(headers))?;
+                let is_residential = r
+                    .kind
+                    .is_none_or(|kind| kind.eq_ignore_ascii_case("residential"));
+                let building = match (r.bbl.filter(|bbl| !bbl.is_empty()), r.bin) {
+                    (Some(bbl), _) => Some(
+                        bbl.parse()
+                            .map_or(EvictionBuilding::InvalidBbl, EvictionBuilding::Bbl),
+                    ),
+                    (None, Some(bin)) => Some(EvictionBuilding::Bin(bin)),
+                    (None, None) => None,
+                };
+                Ok((building, is_residential, parse_date(r.executed_date)?))
+            },
+            |(building, is_residential, executed_date)| {
+                if !is_residential || executed_date < since {
+                    return Ok(());
+                }
+                match building {
+                    Some(EvictionBuilding::Bbl(bbl)) => *by_bbl.entry(bbl).or_insert(0) += 1,
+                    Some(EvictionBuilding::Bin(bin)) => *by_bin.entry(bin).or_insert(0) += 1,
+                    Some(EvictionBuilding::InvalidBbl) => invalid_bbls += 1,
+                    None => skipped += 1,
+                }
+                Ok(())
+            },
+        )?;
+        eprintln!(
+            "Loaded evictions in {} buildings since {} (skipped {} without a BBL or BIN, \
+             and {} with invalid BBLs).",
+            by_bbl.len() + by_bin.len(),
+            since,
+            skipped,
+            invalid_bbls
+        );
+        Ok(EvictionCounts {
+            since,
+            by_bbl,
+            by_bin,
+            invalid_bbls,
+        })
+    }
+
+    /// The earliest date a counted eviction could have been executed.
+    pub fn since(&self) -> NaiveDate {
+        self.since
+    }
+
+    /// The number of evictions skipped because their BBLs were invalid.
+    pub fn invalid_bbls(&self) -> usize {
+        self.invalid_bbls
+    }
+
+    /// The number of evictions on the given tax lot.
+    pub fn on_lot(&self, bbl: BBL) -> u32 {
+        self.by_bbl.get(&bbl).copied().unwrap_or(0)
+    }
+
+    /// The number of evictions in the given building that couldn't be
+    /// matched to a tax lot.
+    pub fn in_building_without_lot(&self, bin: u32) -> u32 {
+        self.by_bin.get(&bin).copied().unwrap_or(0)
+    }
+}
+
+/// How many evictions a portfolio has carried out.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PortfolioEvictions {
+    /// The number of residential evictions in the portfolio's buildings
+    /// since `since`.
+    pub evictions: u32,
+    /// Evictions per residential unit, if PLUTO was loaded.
+    pub evictions_per_unit: Option<f64>,
+    /// Evictions per building.
+    pub evictions_per_building: Option<f64>,
+    /// The date evictions were counted from (a year ago by default), as
+    /// YYYY-MM-DD.
+    pub since: String,
+}
+
+impl PortfolioEvictions {
+    /// Describes the evictions in a sentence, for people to read.
+    pub fn describe(&self) -> String {
+        let rates: Vec<String> = [
+            (self.evictions_per_unit, "unit"),
+            (self.evictions_per_building, "building"),
+        ]
+        .iter()
+        .filter_map(|(rate, per)| rate.map(|rate| format!("{:.2} per {}", rate, per)))
+        .collect();
+        if rates.is_empty() {
+            format!(
+                "It has had {} evictions since {}.",
+                self.evictions, self.since
+            )
+        } else {
+            format!(
+                "It has had {} evictions since {} ({}).",
+                self.evictions,
+                self.since,
+                rates.join(", ")
+            )
+        }
+    }
+}
+
+#[test]
+fn test_from_csv_counts_recent_residential_evictions() {
+    let since = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+    let evictions = EvictionCounts::from_csv(
+        csv::Reader::from_reader(
+            "Executed Date,Residential/Commercial,BIN,BBL\n\
+             03/01/2020,Residential,3000001,3012340010\n\
+             03/02/2020,Residential,3000001,3012340010\n\
+             12/31/2019,Residential,3000001,3012340010\n\
+             03/03/2020,Commercial,3000001,3012340010\n\
+             03/04/2020,Residential,3000002,\n\
+             03/05/2020,Residential,,\n\
+             03/06/2020,Residential,3000001,301234\n"
+                .as_bytes(),
+        ),
+        since,
+    )
+    .unwrap();
+    assert_eq!(evictions.on_lot("3012340010".parse().unwrap()), 2);
+    assert_eq!(evictions.in_building_without_lot(3000001), 0);
+    assert_eq!(evictions.in_building_without_lot(3000002), 1);
+    assert_eq!(evictions.invalid_bbls(), 1);
+}
+
+#[test]
+fn test_describe_works() {
+    let mut evictions = PortfolioEvictions {
+        evictions: 3,
+        evictions_per_unit: None,
+        evictions_per_building: Some(1.5),
+        since: "2020-01-01".to_owned(),
+    };
+    assert_eq!(
+        evictions.describe(),
+        "It has had 3 evictions since 2020-01-01 (1.50 per building)."
+    );
+    evictions.evictions_per_building = None;
+    assert_eq!(
+        evictions.describe(),
+        "It has had 3 evictions since 2020-01-01."
+    );
+}
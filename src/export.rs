@@ -0,0 +1,289 @@
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::error::HpdError;
+use super::hpd_graph::{HpdPetGraph, Node};
+use super::hpd_registrations::HpdRegistrationMap;
+use super::interner::Interner;
+
+/// A file format understood by graph analysis tools like Gephi and
+/// Cytoscape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    GraphMl,
+    Gexf,
+    Cytoscape,
+}
+
+impl ExportFormat {
+    /// The names accepted by `from_str`.
+    pub const NAMES: &'static [&'static str] = &["graphml", "gexf", "cytoscape"];
+}
+
+impl FromStr for ExportFormat {
+    type Err = HpdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "graphml" => Ok(ExportFormat::GraphMl),
+            "gexf" => Ok(ExportFormat::Gexf),
+            "cytoscape" => Ok(ExportFormat::Cytoscape),
+            _ => Err(HpdError::Parse(format!(
+                "unknown export format '{}', expected one of {}",
+                s,
+                ExportFormat::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+struct ExportNode<'a> {
+    id: usize,
+    label: &'a str,
+    kind: &'static str,
+    portfolio: Arc<String>,
+}
+
+struct ExportEdge {
+    id: usize,
+    source: usize,
+    target: usize,
+    weight: usize,
+    is_bridge: bool,
+    bbls: Vec<String>,
+}
+
+impl ExportEdge {
+    fn bbls_str(&self) -> String {
+        self.bbls.join(";")
+    }
+}
+
+/// The nodes and edges of part of the graph, along with the attributes
+/// that get exported for each of them. Nodes and edges are identified by
+/// their indices in the graph, like the JSON export does.
+pub struct GraphExport<'a> {
+    nodes: Vec<ExportNode<'a>>,
+    edges: Vec<ExportEdge>,
+}
+
+impl<'a> GraphExport<'a> {
+    /// Collects the given nodes, each paired with the ID of its portfolio,
+    /// and every edge between them.
+    pub(crate) fn new(
+        nodes: impl Iterator<Item = (NodeIndex<u32>, Arc<String>)>,
+        graph: &'a HpdPetGraph,
+        strings: &'a Interner,
+        local_bridges: &HashSet<EdgeIndex<u32>>,
+        regs: &HpdRegistrationMap,
+    ) -> Self {
+        let mut export = GraphExport {
+            nodes: vec![],
+            edges: vec![],
+        };
+        let mut edges = BTreeMap::new();
+
+        for (node, portfolio) in nodes {
+            let (label, kind) = match graph[node] {
+                Node::Name(name) => (strings.resolve(name), "name"),
+                Node::BizAddr(addr) => (strings.resolve(addr), "address"),
+            };
+            export.nodes.push(ExportNode {
+                id: node.index(),
+                label,
+                kind,
+                portfolio,
+            });
+            for edge in graph.edges(node) {
+                edges.entry(edge.id()).or_insert_with(|| {
+                    let reg_infos = &edge.weight().regs;
+                    let bbls: BTreeSet<String> = reg_infos
+                        .iter()
+                        .filter_map(|reg_info| regs.get_by_id(reg_info.id))
+                        .flatten()
+                        .map(|reg| reg.bbl.to_string())
+                        .collect();
+                    ExportEdge {
+                        id: edge.id().index(),
+                        source: edge.source().index(),
+                        target: edge.target().index(),
+                        weight: reg_infos.len(),
+                        is_bridge: local_bridges.contains(&edge.id()),
+                        bbls: bbls.into_iter().collect(),
+                    }
+                });
+            }
+        }
+
+        export.edges = edges.into_values().collect();
+        export
+    }
+
+    pub fn to_format(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::GraphMl => self.graphml(),
+            ExportFormat::Gexf => self.gexf(),
+            ExportFormat::Cytoscape => self.cytoscape(),
+        }
+    }
+
+    pub fn graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (id, domain, kind) in &[
+            ("label", "node", "string"),
+            ("type", "node", "string"),
+            ("portfolio", "node", "string"),
+            ("weight", "edge", "int"),
+            ("is_bridge", "edge", "boolean"),
+            ("bbls", "edge", "string"),
+        ] {
+            writeln!(
+                out,
+                "  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>",
+                id, domain, kind
+            )
+            .unwrap();
+        }
+        out.push_str("  <graph id=\"G\" edgedefault=\"undirected\">\n");
+        for node in &self.nodes {
+            writeln!(
+                out,
+                "    <node id=\"n{}\"><data key=\"label\">{}</data><data key=\"type\">{}</data><data key=\"portfolio\">{}</data></node>",
+                node.id,
+                escape_xml(node.label),
+                node.kind,
+                escape_xml(&node.portfolio)
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\"><data key=\"weight\">{}</data><data key=\"is_bridge\">{}</data><data key=\"bbls\">{}</data></edge>",
+                edge.id,
+                edge.source,
+                edge.target,
+                edge.weight,
+                edge.is_bridge,
+                edge.bbls_str()
+            )
+            .unwrap();
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    pub fn gexf(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n");
+        out.push_str("  <graph defaultedgetype=\"undirected\" mode=\"static\">\n");
+        out.push_str("    <attributes class=\"node\">\n");
+        out.push_str("      <attribute id=\"type\" title=\"type\" type=\"string\"/>\n");
+        out.push_str("      <attribute id=\"portfolio\" title=\"portfolio\" type=\"string\"/>\n");
+        out.push_str("    </attributes>\n");
+        out.push_str("    <attributes class=\"edge\">\n");
+        out.push_str("      <attribute id=\"is_bridge\" title=\"is_bridge\" type=\"boolean\"/>\n");
+        out.push_str("      <attribute id=\"bbls\" title=\"bbls\" type=\"string\"/>\n");
+        out.push_str("    </attributes>\n");
+        out.push_str("    <nodes>\n");
+        for node in &self.nodes {
+            writeln!(
+                out,
+                "      <node id=\"{}\" label=\"{}\"><attvalues><attvalue for=\"type\" value=\"{}\"/><attvalue for=\"portfolio\" value=\"{}\"/></attvalues></node>",
+                node.id,
+                escape_xml(node.label),
+                node.kind,
+                escape_xml(&node.portfolio)
+            )
+            .unwrap();
+        }
+        out.push_str("    </nodes>\n");
+        out.push_str("    <edges>\n");
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\"><attvalues><attvalue for=\"is_bridge\" value=\"{}\"/><attvalue for=\"bbls\" value=\"{}\"/></attvalues></edge>",
+                edge.id,
+                edge.source,
+                edge.target,
+                edge.weight,
+                edge.is_bridge,
+                edge.bbls_str()
+            )
+            .unwrap();
+        }
+        out.push_str("    </edges>\n");
+        out.push_str("  </graph>\n</gexf>\n");
+        out
+    }
+
+    /// Returns the graph in the format Cytoscape.js (and Cytoscape's
+    /// "Import Network from File") reads, with each element's attributes
+    /// in its `data` object.
+    pub fn cytoscape(&self) -> String {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                serde_json::json!({"data": {
+                    "id": format!("n{}", node.id),
+                    "label": node.label,
+                    "type": node.kind,
+                    "portfolio": node.portfolio.as_str(),
+                }})
+            })
+            .collect();
+        let edges: Vec<_> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                serde_json::json!({"data": {
+                    "id": format!("e{}", edge.id),
+                    "source": format!("n{}", edge.source),
+                    "target": format!("n{}", edge.target),
+                    "weight": edge.weight,
+                    "is_bridge": edge.is_bridge,
+                    "bbls": edge.bbls,
+                }})
+            })
+            .collect();
+        serde_json::json!({"elements": {"nodes": nodes, "edges": edges}}).to_string()
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[test]
+fn test_escape_xml_works() {
+    assert_eq!(
+        escape_xml("A & B <\"C\">"),
+        "A &amp; B &lt;&quot;C&quot;&gt;"
+    );
+    assert_eq!(escape_xml("BOOP JONES"), "BOOP JONES");
+}
+
+#[test]
+fn test_export_format_from_str_works() {
+    assert_eq!("gexf".parse::<ExportFormat>().unwrap(), ExportFormat::Gexf);
+    assert!("dot".parse::<ExportFormat>().is_err());
+}
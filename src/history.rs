@@ -0,0 +1,223 @@
+use chrono::{Duration, Months, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use super::error::HpdError;
+use super::hpd_graph::HpdGraph;
+use super::hpd_registrations::{CountBy, HpdRegistrationMap};
+use super::portfolio::PortfolioMap;
+use super::portfolio_id::PortfolioIdSnapshot;
+
+/// The state of a portfolio on a particular date, along with how it
+/// changed since the previous date in the timeline.
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    date: String,
+    portfolio_id: Option<Arc<String>>,
+    portfolio_name: Option<Arc<String>>,
+    building_count: usize,
+    name_count: usize,
+    address_count: usize,
+    buildings_added: Vec<String>,
+    buildings_removed: Vec<String>,
+    names_added: Vec<String>,
+    names_removed: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct HistoryCsvRow<'a> {
+    date: &'a str,
+    portfolio_id: Option<&'a str>,
+    portfolio_name: Option<&'a str>,
+    building_count: usize,
+    name_count: usize,
+    address_count: usize,
+    buildings_added: String,
+    buildings_removed: String,
+    names_added: String,
+    names_removed: String,
+}
+
+/// Returns the dates from `start` to `end` (inclusive), `months` apart.
+pub fn history_dates(start: NaiveDate, end: NaiveDate, months: u32) -> Vec<NaiveDate> {
+    let mut dates = vec![];
+    let mut date = start;
+    while date <= end {
+        dates.push(date);
+        date = match date.checked_add_months(Months::new(months.max(1))) {
+            Some(date) => date,
+            None => break,
+        };
+    }
+    dates
+}
+
+fn difference<T: Ord + Clone>(a: &BTreeSet<T>, b: &BTreeSet<T>) -> Vec<T> {
+    a.difference(b).cloned().collect()
+}
+
+/// Computes the portfolio containing `name` as of each of the given dates.
+///
+/// `regs` should contain every registration regardless of expiration (see
+/// `HpdRegistrationMap::from_csv_all`), and `hpd` should have been built
+/// from them.
+pub fn portfolio_history(
+    hpd: &HpdGraph,
+    regs: &HpdRegistrationMap,
+    count_by: CountBy,
+    name: &str,
+    dates: &[NaiveDate],
+    max_expiration_age: Duration,
+) -> Vec<HistoryEntry> {
+    let mut entries = vec![];
+    let mut prev_bbls = BTreeSet::new();
+    let mut prev_names = BTreeSet::new();
+    let mut prev_ids: Option<PortfolioIdSnapshot> = None;
+
+    for date in dates {
+        let regs_at = regs.as_of(*date, max_expiration_age);
+        let hpd_at = hpd.retain_regs(|reg_info| !regs_at.is_expired_or_invalid(reg_info.id));
+        let portfolio = hpd_at.name_node(name).and_then(|node| {
+            PortfolioMap::from_graph(&hpd_at, &regs_at, prev_ids.as_ref()).for_node(node)
+        });
+        let (bbls, names, address_count) = match &portfolio {
+            Some(portfolio) => (
+                portfolio
+                    .bbls(&regs_at)
+                    .iter()
+                    .map(|bbl| bbl.to_string())
+                    .collect(),
+                portfolio
+                    .names()
+                    .into_iter()
+                    .map(|name| name.to_owned())
+                    .collect(),
+                portfolio.bizaddrs().len(),
+            ),
+            None => (BTreeSet::new(), BTreeSet::new(), 0),
+        };
+
+        // This ensures the portfolio keeps the same ID over time, as long
+        // as it doesn't change too drastically from one date to the next.
+        prev_ids = portfolio.as_ref().map(|portfolio| {
+            PortfolioIdSnapshot::from_portfolios(&[(portfolio.id(), portfolio.bbls(&regs_at))])
+        });
+
+        entries.push(HistoryEntry {
+            date: date.format("%Y-%m-%d").to_string(),
+            portfolio_id: portfolio.as_ref().map(|p| p.id()),
+            portfolio_name: portfolio.as_ref().map(|p| p.name()),
+            building_count: portfolio
+                .as_ref()
+                .map(|p| p.building_count(&regs_at, count_by))
+                .unwrap_or(0),
+            name_count: names.len(),
+            address_count,
+            buildings_added: difference(&bbls, &prev_bbls),
+            buildings_removed: difference(&prev_bbls, &bbls),
+            names_added: difference(&names, &prev_names),
+            names_removed: difference(&prev_names, &names),
+        });
+
+        prev_bbls = bbls;
+        prev_names = names;
+    }
+
+    entries
+}
+
+pub fn history_json(entries: &[HistoryEntry]) -> String {
+    serde_json::to_string(entries).unwrap()
+}
+
+/// Writes the timeline as CSV. Since CSV cells can't contain lists, the
+/// BBLs and names that were added or removed are separated by semicolons.
+pub fn write_history_csv<T: std::io::Write>(
+    wtr: &mut csv::Writer<T>,
+    entries: &[HistoryEntry],
+) -> Result<(), HpdError> {
+    for entry in entries {
+        wtr.serialize(HistoryCsvRow {
+            date: &entry.date,
+            portfolio_id: entry.portfolio_id.as_ref().map(|id| id.as_str()),
+            portfolio_name: entry.portfolio_name.as_ref().map(|name| name.as_str()),
+            building_count: entry.building_count,
+            name_count: entry.name_count,
+            address_count: entry.address_count,
+            buildings_added: entry.buildings_added.join(";"),
+            buildings_removed: entry.buildings_removed.join(";"),
+            names_added: entry
+                .names_added
+                .iter()
+                .map(|n| n.as_str())
+                .collect::<Vec<_>>()
+                .join(";"),
+            names_removed: entry
+                .names_removed
+                .iter()
+                .map(|n| n.as_str())
+                .collect::<Vec<_>>()
+                .join(";"),
+        })?;
+    }
+    wtr.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_history_dates_works() {
+        assert_eq!(
+            history_dates(date("2019-01-31"), date("2020-01-31"), 6),
+            vec![date("2019-01-31"), date("2019-07-31"), date("2020-01-31")]
+        );
+    }
+
+    #[test]
+    fn test_portfolio_history_works() {
+        let regs = HpdRegistrationMap::from_csv_all(csv::Reader::from_reader(
+            "RegistrationID,BoroID,Block,Lot,BIN,LastRegistrationDate,RegistrationEndDate
+1,1,1,1,1000001,01/01/2018,01/01/2021
+2,1,1,2,1000002,01/01/2019,01/01/2023
+"
+            .as_bytes(),
+        ))
+        .unwrap();
+        let hpd = HpdGraph::from_csv(
+            csv::Reader::from_reader(
+                "RegistrationContactID,RegistrationID,Type,CorporationName,FirstName,LastName,BusinessHouseNumber,BusinessStreetName,BusinessApartment,BusinessCity,BusinessState
+1,1,HeadOfficer,,BOOP,JONES,1,BOOP ST,,NEW YORK,NY
+2,2,HeadOfficer,,BOOP,JONES,1,BOOP ST,,NEW YORK,NY
+3,2,IndividualOwner,,BLAP,SMITH,1,BOOP ST,,NEW YORK,NY
+"
+                .as_bytes(),
+            ),
+            &regs,
+            false,
+        )
+        .unwrap();
+        let dates = history_dates(date("2017-06-01"), date("2022-06-01"), 24);
+        let entries = portfolio_history(
+            &hpd,
+            &regs,
+            CountBy::default(),
+            "BOOP JONES",
+            &dates,
+            Duration::days(90),
+        );
+
+        let counts: Vec<usize> = entries.iter().map(|e| e.building_count).collect();
+        assert_eq!(counts, vec![0, 2, 1]);
+        assert_eq!(entries[1].names_added.len(), 2);
+        assert_eq!(entries[2].buildings_removed, vec!["1000010001".to_owned()]);
+        assert_eq!(entries[1].portfolio_id, entries[2].portfolio_id);
+    }
+}
@@ -1,34 +1,79 @@
+use petgraph::algo::{astar, connected_components, dijkstra};
 use petgraph::graph::{EdgeIndex, Graph, NodeIndex};
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::{EdgeRef, VisitMap};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::error::Error;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::Arc;
 
+use super::acris::{AcrisDeeds, DeedGrantee, DeedInfo};
+use super::bbl::BBL;
+use super::csv_batches::parse_records;
+use super::error::HpdError;
 use super::hpd_registrations::HpdRegistrationMap;
+use super::interner::{Interner, Symbol};
 use super::synonyms::Synonyms;
 
-#[derive(Debug, Serialize)]
+/// A node in the graph. Its text is stored in the graph's `Interner`.
+#[derive(Debug, Clone, Copy)]
 pub enum Node {
-    Name(Rc<String>),
-    BizAddr(Rc<String>),
+    Name(Symbol),
+    BizAddr(Symbol),
 }
 
 impl Node {
-    pub fn to_str(&self) -> &str {
+    pub fn symbol(&self) -> Symbol {
         match self {
-            Node::BizAddr(name) => name,
-            Node::Name(name) => name,
+            Node::BizAddr(symbol) => *symbol,
+            Node::Name(symbol) => *symbol,
         }
-        .as_ref()
-        .as_ref()
     }
 }
 
-type Edge = Vec<RegInfo>;
+/// What links a name to a business address.
+#[derive(Debug, Clone, Default)]
+pub struct Edge {
+    /// The HPD registration contacts that mention both.
+    pub regs: Vec<RegInfo>,
+    /// The ACRIS deeds whose grantees have the name and address.
+    pub deeds: Vec<Arc<DeedInfo>>,
+}
+
+impl Edge {
+    /// Registration contacts take precedence over deeds, so an edge with
+    /// both is a registration link.
+    pub fn kind(&self) -> LinkKind {
+        if self.regs.is_empty() {
+            LinkKind::Deed
+        } else {
+            LinkKind::Registration
+        }
+    }
+}
+
+/// The kind of record linking a name to a business address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Registration,
+    Deed,
+}
+
+impl LinkKind {
+    /// How the link is drawn between two nodes in a path, so that links
+    /// that only come from deeds stand out.
+    pub fn arrow(&self) -> &'static str {
+        match self {
+            LinkKind::Registration => " -> ",
+            LinkKind::Deed => " =deed=> ",
+        }
+    }
+}
 
 pub type HpdPetGraph = Graph<Node, Edge, petgraph::Undirected>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RegInfo {
     pub contact_id: u32,
     pub id: u32,
@@ -60,103 +105,478 @@ struct HpdRegistrationContact<'a> {
     reg_id: u32,
 }
 
+/// The name and address a registration contact links together, if any.
+struct ContactLink {
+    name: String,
+    addr: String,
+    reg_id: u32,
+    contact_id: u32,
+}
+
+impl ContactLink {
+    fn from_contact(
+        record: &HpdRegistrationContact,
+        regs: &HpdRegistrationMap,
+        synonyms: &Synonyms,
+        include_corps: bool,
+    ) -> Option<Self> {
+        match record._type {
+            "HeadOfficer" | "IndividualOwner" | "CorporateOwner" => {}
+            _ => return None,
+        }
+        if record.house_no.is_empty() || record.street_name.is_empty() {
+            return None;
+        }
+        let has_full_name = !record.first_name.is_empty() && !record.last_name.is_empty();
+        if !(has_full_name || (include_corps && !record.corp_name.is_empty())) {
+            return None;
+        }
+        if regs.is_expired_or_invalid(record.reg_id) {
+            return None;
+        }
+        let name_string = if has_full_name {
+            format!("{} {}", record.first_name, record.last_name)
+        } else {
+            record.corp_name.to_owned()
+        };
+        let name = match synonyms.get(&name_string) {
+            Some(canonical) => canonical.to_owned(),
+            None => name_string,
+        };
+        let mut addr = format!(
+            "{} {}{}{}, {} {}",
+            record.house_no,
+            record.street_name,
+            if !record.apt_no.is_empty() { " " } else { "" },
+            record.apt_no,
+            record.city,
+            record.state
+        );
+        addr.make_ascii_uppercase();
+        Some(ContactLink {
+            name,
+            addr,
+            reg_id: record.reg_id,
+            contact_id: record.reg_contact_id,
+        })
+    }
+}
+
+/// Words that mark an ACRIS party as a corporation rather than a person.
+const CORPORATE_WORDS: &[&str] = &[
+    "ASSOCIATES",
+    "ASSOCIATION",
+    "BANK",
+    "CO",
+    "COMPANY",
+    "CORP",
+    "CORPORATION",
+    "FUND",
+    "HOLDINGS",
+    "INC",
+    "LLC",
+    "LLP",
+    "LP",
+    "LTD",
+    "PARTNERS",
+    "PARTNERSHIP",
+    "REALTY",
+    "TRUST",
+];
+
+fn is_corporation(name: &str) -> bool {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| CORPORATE_WORDS.contains(&word))
+}
+
+/// Converts a person's name as ACRIS writes it, e.g. "JONES, BOOP A", to
+/// the way HPD registration contacts do, e.g. "BOOP JONES".
+fn person_name(name: &str) -> String {
+    match name.split_once(',') {
+        Some((last, first)) => {
+            let mut first: Vec<&str> = first.split_whitespace().collect();
+            if first.len() > 1 && first.last().is_some_and(|word| word.len() == 1) {
+                first.pop();
+            }
+            format!("{} {}", first.join(" "), last.trim())
+        }
+        None => name.to_owned(),
+    }
+}
+
+/// The name and address a deed's grantee links together, if any.
+struct DeedLink<'a> {
+    name: String,
+    addr: &'a str,
+    deed: &'a Arc<DeedInfo>,
+}
+
+impl<'a> DeedLink<'a> {
+    fn from_grantee(
+        grantee: &'a DeedGrantee,
+        synonyms: &Synonyms,
+        include_corps: bool,
+    ) -> Option<Self> {
+        let name = if is_corporation(&grantee.name) {
+            if !include_corps {
+                return None;
+            }
+            grantee.name.clone()
+        } else {
+            person_name(&grantee.name)
+        };
+        let name = match synonyms.get(&name) {
+            Some(canonical) => canonical.to_owned(),
+            None => name,
+        };
+        Some(DeedLink {
+            name,
+            addr: &grantee.addr,
+            deed: &grantee.deed,
+        })
+    }
+}
+
+/// Builds an `HpdGraph` one link at a time.
+struct GraphBuilder {
+    graph: HpdPetGraph,
+    strings: Interner,
+    name_nodes: HashMap<Symbol, NodeIndex<u32>>,
+    addr_nodes: HashMap<Symbol, NodeIndex<u32>>,
+    edges: HashMap<(NodeIndex<u32>, NodeIndex<u32>), EdgeIndex<u32>>,
+}
+
+impl GraphBuilder {
+    fn new() -> Self {
+        GraphBuilder {
+            graph: Graph::new_undirected(),
+            strings: Interner::new(),
+            name_nodes: HashMap::new(),
+            addr_nodes: HashMap::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    /// Returns the edge between the given name and address, adding them
+    /// if need be.
+    fn edge(&mut self, name: &str, addr: &str) -> &mut Edge {
+        let addr = self.strings.intern(addr);
+        let name = self.strings.intern(name);
+        let graph = &mut self.graph;
+        let addr_node = *self
+            .addr_nodes
+            .entry(addr)
+            .or_insert_with(|| graph.add_node(Node::BizAddr(addr)));
+        let name_node = *self
+            .name_nodes
+            .entry(name)
+            .or_insert_with(|| graph.add_node(Node::Name(name)));
+        let edge_idx = *self
+            .edges
+            .entry((name_node, addr_node))
+            .or_insert_with(|| graph.add_edge(name_node, addr_node, Edge::default()));
+        self.graph.edge_weight_mut(edge_idx).unwrap()
+    }
+}
+
+/// Removes the nodes of every connected component that has no
+/// registration contacts, e.g. owners who only appear in deeds, since they
+/// aren't portfolios of HPD-registered buildings.
+fn remove_unregistered_components(graph: &mut HpdPetGraph) {
+    let mut components = UnionFind::new(graph.node_count());
+    for edge in graph.edge_references() {
+        components.union(edge.source().index(), edge.target().index());
+    }
+    let registered: HashSet<usize> = graph
+        .edge_references()
+        .filter(|edge| !edge.weight().regs.is_empty())
+        .map(|edge| components.find(edge.source().index()))
+        .collect();
+    graph.retain_nodes(|_, node| registered.contains(&components.find(node.index())));
+}
+
 pub struct HpdGraph {
-    pub graph: Rc<HpdPetGraph>,
-    pub name_nodes: HashMap<Rc<String>, NodeIndex<u32>>,
-    pub addr_nodes: HashMap<Rc<String>, NodeIndex<u32>>,
+    pub graph: Arc<HpdPetGraph>,
+    pub strings: Arc<Interner>,
+    pub name_nodes: HashMap<Symbol, NodeIndex<u32>>,
+    pub addr_nodes: HashMap<Symbol, NodeIndex<u32>>,
 }
 
 impl HpdGraph {
     pub fn from_csv<T: std::io::Read>(
-        mut rdr: csv::Reader<T>,
+        rdr: csv::Reader<T>,
+        regs: &HpdRegistrationMap,
+        include_corps: bool,
+    ) -> Result<Self, HpdError> {
+        HpdGraph::from_csv_with_deeds(rdr, regs, include_corps, None)
+    }
+
+    /// Like `from_csv`, but also links the names and addresses of the
+    /// grantees of the given deeds. Owners who are only linked to other
+    /// owners by deeds are left out.
+    pub fn from_csv_with_deeds<T: std::io::Read>(
+        rdr: csv::Reader<T>,
         regs: &HpdRegistrationMap,
         include_corps: bool,
-    ) -> Result<Self, Box<dyn Error>> {
+        deeds: Option<&AcrisDeeds>,
+    ) -> Result<Self, HpdError> {
         let synonyms = Synonyms::new();
-        let mut src_graph: HpdPetGraph = Graph::new_undirected();
-        let mut name_nodes = HashMap::<Rc<String>, NodeIndex<u32>>::new();
-        let mut addr_nodes = HashMap::<Rc<String>, NodeIndex<u32>>::new();
-        let mut edges = HashMap::<(NodeIndex<u32>, NodeIndex<u32>), EdgeIndex<u32>>::new();
-        let mut raw_record = csv::StringRecord::new();
-        let headers = rdr.headers()?.clone();
-
-        while rdr.read_record(&mut raw_record)? {
-            let record: HpdRegistrationContact = raw_record.deserialize(Some(&headers))?;
-            match record._type.as_ref() {
-                "HeadOfficer" | "IndividualOwner" | "CorporateOwner" => {
-                    if record.house_no == "" || record.street_name == "" {
-                        continue;
-                    }
-                    let has_full_name = record.first_name != "" && record.last_name != "";
-                    if !(has_full_name || (include_corps && record.corp_name != "")) {
-                        continue;
-                    }
-                    if regs.is_expired_or_invalid(record.reg_id) {
-                        continue;
+        let mut builder = GraphBuilder::new();
+
+        let parse = |raw_record: &csv::StringRecord, headers: &csv::StringRecord| {
+            let record: HpdRegistrationContact = raw_record.deserialize(Some(headers))?;
+            Ok(ContactLink::from_contact(
+                &record,
+                regs,
+                &synonyms,
+                include_corps,
+            ))
+        };
+
+        parse_records(rdr, parse, |link| {
+            if let Some(link) = link {
+                builder.edge(&link.name, &link.addr).regs.push(RegInfo {
+                    id: link.reg_id,
+                    contact_id: link.contact_id,
+                });
+            }
+            Ok(())
+        })?;
+
+        if let Some(deeds) = deeds {
+            for grantee in &deeds.grantees {
+                if let Some(link) = DeedLink::from_grantee(grantee, &synonyms, include_corps) {
+                    let edge = builder.edge(&link.name, link.addr);
+                    if !edge.deeds.contains(link.deed) {
+                        edge.deeds.push(Arc::clone(link.deed));
                     }
-                    let name_string = if has_full_name {
-                        format!("{} {}", record.first_name, record.last_name)
-                    } else {
-                        record.corp_name.to_owned()
-                    };
-                    let name = synonyms
-                        .get(&name_string)
-                        .unwrap_or_else(|| Rc::new(name_string));
-                    let mut addr_string = format!(
-                        "{} {}{}{}, {} {}",
-                        record.house_no,
-                        record.street_name,
-                        if record.apt_no.len() > 0 { " " } else { "" },
-                        record.apt_no,
-                        record.city,
-                        record.state
-                    );
-                    addr_string.make_ascii_uppercase();
-                    let addr = Rc::new(addr_string);
-                    let addr_node = *addr_nodes
-                        .entry(Rc::clone(&addr))
-                        .or_insert_with(|| src_graph.add_node(Node::BizAddr(Rc::clone(&addr))));
-                    let name_node = *name_nodes
-                        .entry(Rc::clone(&name))
-                        .or_insert_with(|| src_graph.add_node(Node::Name(Rc::clone(&name))));
-                    let edge_idx = edges
-                        .entry((name_node, addr_node))
-                        .or_insert_with(|| src_graph.add_edge(name_node, addr_node, vec![]));
-                    let edge = src_graph.edge_weight_mut(*edge_idx).unwrap();
-                    edge.push(RegInfo {
-                        id: record.reg_id,
-                        contact_id: record.reg_contact_id,
-                    });
                 }
-                _ => {}
             }
+            remove_unregistered_components(&mut builder.graph);
         }
 
-        let graph = Rc::new(src_graph);
+        builder.strings.shrink_to_fit();
+        Ok(HpdGraph::from_pet_graph(
+            builder.graph,
+            Arc::new(builder.strings),
+        ))
+    }
 
-        Ok(HpdGraph {
-            graph,
+    fn from_pet_graph(graph: HpdPetGraph, strings: Arc<Interner>) -> Self {
+        let mut name_nodes = HashMap::new();
+        let mut addr_nodes = HashMap::new();
+        for node in graph.node_indices() {
+            match graph[node] {
+                Node::Name(name) => name_nodes.insert(name, node),
+                Node::BizAddr(addr) => addr_nodes.insert(addr, node),
+            };
+        }
+
+        HpdGraph {
+            graph: Arc::new(graph),
+            strings,
             name_nodes,
             addr_nodes,
-        })
+        }
     }
 
-    pub fn path_to_string(&self, path: Vec<NodeIndex<u32>>) -> String {
-        path.iter()
-            .map(|node| self.graph.node_weight(*node).unwrap().to_str())
-            .collect::<Vec<&str>>()
-            .join(" -> ")
+    /// Returns a copy of the graph containing only the registration
+    /// contacts for which `keep` returns true, dropping any nodes that are
+    /// left without registrations.
+    pub fn retain_regs<F: Fn(&RegInfo) -> bool>(&self, keep: F) -> Self {
+        let mut graph = self.graph.filter_map(
+            |_, node| Some(*node),
+            |_, edge| {
+                let edge = Edge {
+                    regs: edge.regs.iter().filter(|r| keep(r)).cloned().collect(),
+                    deeds: edge.deeds.clone(),
+                };
+                if edge.regs.is_empty() && edge.deeds.is_empty() {
+                    None
+                } else {
+                    Some(edge)
+                }
+            },
+        );
+        remove_unregistered_components(&mut graph);
+        HpdGraph::from_pet_graph(graph, Arc::clone(&self.strings))
+    }
+
+    /// The number of connected components in the graph, i.e. the number of
+    /// portfolios (including ones with no buildings).
+    pub fn connected_components(&self) -> usize {
+        connected_components(self.graph.deref())
     }
 
-    pub fn find_name(&self, search: &String) -> Option<NodeIndex<u32>> {
-        if let Some(node) = self.name_nodes.get(search) {
-            return Some(*node);
+    /// For each connected component, finds the longest of the shortest
+    /// paths from its first name to any other name, returning the ones
+    /// that are at least `min_length` long.
+    pub fn long_paths(&self, min_length: u32) -> Vec<(u32, Vec<NodeIndex<u32>>)> {
+        let mut visits = HashSet::new();
+        let mut paths = vec![];
+
+        for node in self.graph.node_indices() {
+            if visits.is_visited(&node) {
+                continue;
+            }
+            visits.visit(node);
+            if let Some(Node::Name(_)) = self.graph.node_weight(node) {
+                let mut max_cost = 0;
+                let mut max_cost_node = None;
+                let mut dijkstra_map: Vec<_> = dijkstra(self.graph.deref(), node, None, |_| 1)
+                    .into_iter()
+                    .collect();
+                dijkstra_map.sort();
+                for (other_node, cost) in dijkstra_map {
+                    visits.visit(other_node);
+                    if let Some(Node::Name(_)) = self.graph.node_weight(other_node) {
+                        if cost > max_cost {
+                            max_cost = cost;
+                            max_cost_node = Some(other_node);
+                        }
+                    }
+                }
+                if max_cost >= min_length {
+                    if let Some(other_node) = max_cost_node {
+                        let (_, path) =
+                            astar(self.graph.deref(), node, |n| n == other_node, |_| 1, |_| 1)
+                                .unwrap();
+                        paths.push((max_cost, path));
+                    }
+                }
+            }
         }
-        for (name, node) in self.name_nodes.iter() {
-            if name.find(search).is_some() {
-                return Some(*node);
+
+        paths
+    }
+
+    /// The name or business address of the given node.
+    pub fn node_str(&self, node: NodeIndex<u32>) -> &str {
+        self.strings.resolve(self.graph[node].symbol())
+    }
+
+    /// Returns the kind of link between two adjacent nodes.
+    pub fn link_kind(&self, a: NodeIndex<u32>, b: NodeIndex<u32>) -> Option<LinkKind> {
+        let edge = self.graph.find_edge(a, b)?;
+        Some(self.graph[edge].kind())
+    }
+
+    /// Describes a path, marking the links that only come from deeds.
+    pub fn path_to_string(&self, path: &[NodeIndex<u32>]) -> String {
+        let mut result = String::new();
+        for (i, node) in path.iter().enumerate() {
+            if i > 0 {
+                result.push_str(
+                    self.link_kind(path[i - 1], *node)
+                        .unwrap_or(LinkKind::Registration)
+                        .arrow(),
+                );
+            }
+            result.push_str(self.node_str(*node));
+        }
+        result
+    }
+
+    /// Returns the node for exactly the given name, if there is one.
+    pub fn name_node(&self, name: &str) -> Option<NodeIndex<u32>> {
+        self.strings
+            .get(name)
+            .and_then(|symbol| self.name_nodes.get(&symbol))
+            .copied()
+    }
+
+    fn search(
+        &self,
+        nodes: &HashMap<Symbol, NodeIndex<u32>>,
+        search: &str,
+    ) -> Vec<(&str, NodeIndex<u32>)> {
+        let mut matches: Vec<(&str, NodeIndex<u32>)> = nodes
+            .iter()
+            .map(|(value, node)| (self.strings.resolve(*value), *node))
+            .filter(|(value, _)| value.contains(search))
+            .collect();
+        matches.sort();
+        matches
+    }
+
+    fn find(
+        &self,
+        what: &'static str,
+        nodes: &HashMap<Symbol, NodeIndex<u32>>,
+        search: &str,
+    ) -> Result<NodeIndex<u32>, HpdError> {
+        let exact = self
+            .strings
+            .get(search)
+            .and_then(|symbol| nodes.get(&symbol));
+        if let Some(node) = exact {
+            return Ok(*node);
+        }
+        let matches = self.search(nodes, search);
+        match matches.len() {
+            0 => Err(HpdError::NotFound {
+                what,
+                search: search.to_owned(),
+            }),
+            1 => Ok(matches[0].1),
+            _ => Err(HpdError::AmbiguousMatch {
+                what,
+                search: search.to_owned(),
+                candidates: matches
+                    .into_iter()
+                    .map(|(value, _)| value.to_owned())
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Returns every name that contains `search`, sorted alphabetically.
+    pub fn search_names(&self, search: &str) -> Vec<(&str, NodeIndex<u32>)> {
+        self.search(&self.name_nodes, search)
+    }
+
+    /// Returns every business address that contains `search`, sorted
+    /// alphabetically.
+    pub fn search_addrs(&self, search: &str) -> Vec<(&str, NodeIndex<u32>)> {
+        self.search(&self.addr_nodes, search)
+    }
+
+    /// Finds the name that is either exactly `search`, or the only name
+    /// that contains it.
+    pub fn find_name(&self, search: &str) -> Result<NodeIndex<u32>, HpdError> {
+        self.find("name", &self.name_nodes, search)
+    }
+
+    /// Like `find_name`, but for business addresses.
+    pub fn find_addr(&self, search: &str) -> Result<NodeIndex<u32>, HpdError> {
+        self.find("address", &self.addr_nodes, search)
+    }
+
+    /// Returns one of the shortest paths between two nodes, if they're in
+    /// the same portfolio.
+    pub fn shortest_path(
+        &self,
+        from: NodeIndex<u32>,
+        to: NodeIndex<u32>,
+    ) -> Option<Vec<NodeIndex<u32>>> {
+        astar(self.graph.deref(), from, |n| n == to, |_| 1, |_| 0).map(|(_, path)| path)
+    }
+
+    /// Maps each BBL to a node whose registration contacts mention it, so
+    /// that the portfolio owning a building can be looked up.
+    pub fn nodes_by_bbl(&self, regs: &HpdRegistrationMap) -> HashMap<BBL, NodeIndex<u32>> {
+        let mut result = HashMap::new();
+        for edge in self.graph.edge_references() {
+            for reg in edge
+                .weight()
+                .regs
+                .iter()
+                .filter_map(|reg_info| regs.get_by_id(reg_info.id))
+                .flatten()
+            {
+                result.entry(reg.bbl).or_insert_with(|| edge.source());
             }
         }
-        None
+        result
     }
 }
@@ -1,9 +1,17 @@
 use chrono::{Duration, NaiveDate};
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::error::Error;
+use std::str::FromStr;
+use std::sync::Arc;
 
-use super::bbl::BBL;
+use super::bbl::{Boro, BBL};
+use super::conditions::{ComplaintCounts, ViolationCounts};
+use super::csv_batches::parse_records;
+use super::error::HpdError;
+use super::evictions::EvictionCounts;
+use super::pluto::{PlutoLot, PlutoMap};
+use super::stabilization::StabilizedUnitCounts;
 
 #[derive(Deserialize)]
 struct RawHpdRegistration<'a> {
@@ -22,51 +30,191 @@ struct RawHpdRegistration<'a> {
     #[serde(alias = "BIN")]
     bin: Option<u32>,
 
+    #[serde(alias = "CommunityBoard", default)]
+    community_board: Option<u8>,
+
+    #[serde(alias = "LastRegistrationDate", default)]
+    last_reg_date: Option<&'a str>,
+
     #[serde(alias = "RegistrationEndDate")]
     reg_end_date: &'a str,
 }
 
-#[derive(Debug)]
+/// Parses a date as written in NYC Open Data's CSVs, e.g. "09/01/2020".
+pub(crate) fn parse_date(value: &str) -> Result<NaiveDate, HpdError> {
+    NaiveDate::parse_from_str(value, "%m/%d/%Y")
+        .map_err(|e| HpdError::Parse(format!("date '{}' ({})", value, e)))
+}
+
+#[derive(Debug, Clone)]
 pub struct HpdRegistration {
     pub reg_id: u32,
     pub bbl: BBL,
     pub bin: Option<u32>,
+    pub community_board: Option<u8>,
+    pub last_reg_date: Option<NaiveDate>,
     pub reg_end_date: NaiveDate,
 }
 
+impl HpdRegistration {
+    fn from_record(
+        raw_record: &csv::StringRecord,
+        headers: &csv::StringRecord,
+    ) -> Result<Self, HpdError> {
+        let r: RawHpdRegistration = raw_record.deserialize(Some(headers))?;
+        let last_reg_date = match r.last_reg_date {
+            Some(date) if !date.is_empty() => Some(parse_date(date)?),
+            _ => None,
+        };
+        let bbl = BBL::from_numbers(r.boro, r.block, r.lot).map_err(|e| {
+            HpdError::InvalidBBL(format!("{}-{}-{} ({})", r.boro, r.block, r.lot, e))
+        })?;
+        Ok(HpdRegistration {
+            reg_id: r.reg_id,
+            reg_end_date: parse_date(r.reg_end_date)?,
+            last_reg_date,
+            bbl,
+            bin: r.bin,
+            community_board: r.community_board,
+        })
+    }
+
+    /// The building's community district, in the three-digit form used by
+    /// NYC Open Data, e.g. 302 for Brooklyn's second community district.
+    pub fn community_district(&self) -> Option<u16> {
+        self.community_board
+            .map(|board| self.bbl.boro() as u16 * 100 + board as u16)
+    }
+
+    /// Whether the registration was in effect on the given date, allowing
+    /// for it to have expired up to `max_expiration_age` beforehand.
+    ///
+    /// Note that the HPD data only contains the most recent registration
+    /// for each building, so when looking far into the past this can only
+    /// approximate the registrations that were actually in effect.
+    pub fn is_active_on(&self, date: NaiveDate, max_expiration_age: Duration) -> bool {
+        if let Some(last_reg_date) = self.last_reg_date {
+            if last_reg_date > date {
+                return false;
+            }
+        }
+        date - self.reg_end_date < max_expiration_age
+    }
+}
+
+/// How to decide whether two registrations are for the same building.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum CountBy {
+    /// Every registration is a separate building.
+    #[default]
+    Registrations,
+    /// Registrations with the same BIN are the same building. Registrations
+    /// without a BIN are counted separately.
+    Bins,
+    /// Registrations on the same tax lot are the same building.
+    Bbls,
+    /// Like `Bbls`, but a condo's lots are treated as the same tax lot. A
+    /// condo's units each have their own lot, as does the condo as a whole
+    /// for billing purposes, so this relies on PLUTO's condo numbers to tell
+    /// which lots belong to which condo; without them, it counts the same
+    /// as `Bbls`.
+    TaxLots,
+}
+
+impl CountBy {
+    /// The names accepted by `from_str`.
+    pub const NAMES: &'static [&'static str] = &["registrations", "bins", "bbls", "tax-lots"];
+
+    pub(crate) fn building_key(
+        &self,
+        reg: &HpdRegistration,
+        regs: &HpdRegistrationMap,
+    ) -> BuildingKey {
+        match self {
+            CountBy::Registrations => BuildingKey::Registration(reg.reg_id),
+            CountBy::Bins => match reg.bin {
+                Some(bin) => BuildingKey::Bin(bin),
+                None => BuildingKey::Registration(reg.reg_id),
+            },
+            CountBy::Bbls => BuildingKey::Bbl(reg.bbl),
+            CountBy::TaxLots => match regs.condo_number(reg.bbl) {
+                Some(condo_number) => BuildingKey::Condo(reg.bbl.boro(), condo_number),
+                None => BuildingKey::Bbl(reg.bbl),
+            },
+        }
+    }
+}
+
+impl FromStr for CountBy {
+    type Err = HpdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "registrations" => Ok(CountBy::Registrations),
+            "bins" => Ok(CountBy::Bins),
+            "bbls" => Ok(CountBy::Bbls),
+            "tax-lots" => Ok(CountBy::TaxLots),
+            _ => Err(HpdError::Parse(format!(
+                "unknown way to count buildings '{}', expected one of {}",
+                s,
+                CountBy::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Identifies a building for the purposes of counting them; see `CountBy`.
+#[derive(PartialEq, Eq, Hash)]
+pub(crate) enum BuildingKey {
+    Registration(u32),
+    Bin(u32),
+    Bbl(BBL),
+    Condo(Boro, u32),
+}
+
 pub struct HpdRegistrationMap {
     regs_by_id: HashMap<u32, Vec<HpdRegistration>>,
+    pluto: Option<Arc<PlutoMap>>,
+    violations: Option<Arc<ViolationCounts>>,
+    complaints: Option<Arc<ComplaintCounts>>,
+    evictions: Option<Arc<EvictionCounts>>,
+    stabilized_units: Option<Arc<StabilizedUnitCounts>>,
 }
 
 impl HpdRegistrationMap {
     pub fn from_csv<T: std::io::Read>(
-        mut rdr: csv::Reader<T>,
+        rdr: csv::Reader<T>,
         max_expiration_age: Duration,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, HpdError> {
+        let today = chrono::offset::Local::now().date_naive();
+        HpdRegistrationMap::from_csv_filtered(rdr, |reg| {
+            reg.is_active_on(today, max_expiration_age)
+        })
+    }
+
+    /// Like `from_csv`, but keeps every registration regardless of when
+    /// it expired. Use `as_of` to obtain the registrations in effect on a
+    /// particular date.
+    pub fn from_csv_all<T: std::io::Read>(rdr: csv::Reader<T>) -> Result<Self, HpdError> {
+        HpdRegistrationMap::from_csv_filtered(rdr, |_| true)
+    }
+
+    fn from_csv_filtered<T: std::io::Read, F: Fn(&HpdRegistration) -> bool>(
+        rdr: csv::Reader<T>,
+        keep: F,
+    ) -> Result<Self, HpdError> {
         let mut count = 0;
         let mut regs_by_id = HashMap::<u32, Vec<HpdRegistration>>::new();
-        let today = chrono::offset::Local::today().naive_local();
-        let mut raw_record = csv::StringRecord::new();
-        let headers = rdr.headers()?.clone();
-
-        while rdr.read_record(&mut raw_record)? {
-            let r: RawHpdRegistration = raw_record.deserialize(Some(&headers))?;
-            let reg_end_date =
-                NaiveDate::parse_from_str(&r.reg_end_date.as_ref(), "%m/%d/%Y").unwrap();
-            let bbl = BBL::from_numbers(r.boro, r.block, r.lot).unwrap();
-            let age = today - reg_end_date;
-            if age < max_expiration_age {
-                let reg = HpdRegistration {
-                    reg_id: r.reg_id,
-                    reg_end_date,
-                    bbl,
-                    bin: r.bin,
-                };
-                let regs = regs_by_id.entry(r.reg_id).or_insert_with(|| vec![]);
+
+        parse_records(rdr, HpdRegistration::from_record, |reg| {
+            if keep(&reg) {
+                let regs = regs_by_id.entry(reg.reg_id).or_default();
                 regs.push(reg);
             }
             count += 1;
-        }
+            Ok(())
+        })?;
 
         eprintln!(
             "Loaded {} registrations (skipped {}).",
@@ -74,14 +222,116 @@ impl HpdRegistrationMap {
             count - regs_by_id.len()
         );
 
-        Ok(HpdRegistrationMap { regs_by_id })
+        Ok(HpdRegistrationMap {
+            regs_by_id,
+            pluto: None,
+            violations: None,
+            complaints: None,
+            evictions: None,
+            stabilized_units: None,
+        })
     }
 
     pub fn is_expired_or_invalid(&self, id: u32) -> bool {
-        self.regs_by_id.get(&id).is_none()
+        !self.regs_by_id.contains_key(&id)
+    }
+
+    /// Iterates over every registration, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &HpdRegistration> {
+        self.regs_by_id.values().flatten()
     }
 
     pub fn get_by_id(&self, id: u32) -> Option<&Vec<HpdRegistration>> {
         self.regs_by_id.get(&id)
     }
+
+    /// Returns only the registrations that were in effect on the given date.
+    pub fn as_of(&self, date: NaiveDate, max_expiration_age: Duration) -> Self {
+        let mut regs_by_id = HashMap::new();
+        for (id, regs) in self.regs_by_id.iter() {
+            let active: Vec<HpdRegistration> = regs
+                .iter()
+                .filter(|reg| reg.is_active_on(date, max_expiration_age))
+                .cloned()
+                .collect();
+            if !active.is_empty() {
+                regs_by_id.insert(*id, active);
+            }
+        }
+        HpdRegistrationMap {
+            regs_by_id,
+            pluto: self.pluto.clone(),
+            violations: self.violations.clone(),
+            complaints: self.complaints.clone(),
+            evictions: self.evictions.clone(),
+            stabilized_units: self.stabilized_units.clone(),
+        }
+    }
+
+    /// Joins PLUTO's records to the registrations, by BBL.
+    pub fn join_pluto(&mut self, pluto: PlutoMap) {
+        self.pluto = Some(Arc::new(pluto));
+    }
+
+    /// Whether PLUTO's records have been joined with `join_pluto`.
+    pub fn has_pluto(&self) -> bool {
+        self.pluto.is_some()
+    }
+
+    /// Returns PLUTO's record of the given tax lot, if PLUTO has been
+    /// joined and has one.
+    pub fn pluto(&self, bbl: BBL) -> Option<&PlutoLot> {
+        self.pluto.as_ref()?.get(bbl)
+    }
+
+    /// The number of the condo the given lot belongs to, if it's a condo lot
+    /// and PLUTO says which condo.
+    pub fn condo_number(&self, bbl: BBL) -> Option<u32> {
+        if !bbl.is_condo_lot() {
+            return None;
+        }
+        self.pluto(bbl)?.condo_number
+    }
+
+    /// Joins counts of HPD violations to the registrations, by BBL.
+    pub fn join_violations(&mut self, violations: ViolationCounts) {
+        self.violations = Some(Arc::new(violations));
+    }
+
+    /// Returns the violation counts joined with `join_violations`, if any.
+    pub fn violations(&self) -> Option<&ViolationCounts> {
+        self.violations.as_deref()
+    }
+
+    /// Joins counts of HPD complaints to the registrations, by BBL.
+    pub fn join_complaints(&mut self, complaints: ComplaintCounts) {
+        self.complaints = Some(Arc::new(complaints));
+    }
+
+    /// Returns the complaint counts joined with `join_complaints`, if any.
+    pub fn complaints(&self) -> Option<&ComplaintCounts> {
+        self.complaints.as_deref()
+    }
+
+    /// Joins counts of marshal evictions to the registrations, by BBL or
+    /// BIN.
+    pub fn join_evictions(&mut self, evictions: EvictionCounts) {
+        self.evictions = Some(Arc::new(evictions));
+    }
+
+    /// Returns the eviction counts joined with `join_evictions`, if any.
+    pub fn evictions(&self) -> Option<&EvictionCounts> {
+        self.evictions.as_deref()
+    }
+
+    /// Joins counts of rent-stabilized units to the registrations, by BBL.
+    pub fn join_stabilized_units(&mut self, stabilized_units: StabilizedUnitCounts) {
+        self.stabilized_units = Some(Arc::new(stabilized_units));
+    }
+
+    /// Returns the stabilized unit counts joined with
+    /// `join_stabilized_units`, if any.
+    pub fn stabilized_units(&self) -> Option<&StabilizedUnitCounts> {
+        self.stabilized_units.as_deref()
+    }
 }
@@ -0,0 +1,126 @@
+use hashbrown::HashTable;
+use std::collections::hash_map::RandomState;
+use std::convert::TryFrom;
+use std::hash::BuildHasher;
+
+/// Identifies a string stored in an `Interner`. Symbols are assigned in the
+/// order strings are first interned, so comparing them doesn't compare the
+/// strings themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// Stores each distinct string exactly once, back to back in a single
+/// buffer, so that the graph can refer to names and addresses by `Symbol`
+/// rather than each holding its own allocation.
+pub struct Interner {
+    buffer: String,
+    // The end of each string in `buffer`; each one starts where the
+    // previous one ends.
+    ends: Vec<u32>,
+    // The symbols of every string, hashed by the strings they refer to.
+    table: HashTable<Symbol>,
+    hasher: RandomState,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Interner::new()
+    }
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner {
+            buffer: String::new(),
+            ends: vec![],
+            table: HashTable::new(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn span(ends: &[u32], symbol: Symbol) -> (usize, usize) {
+        let i = symbol.0 as usize;
+        let start = if i == 0 { 0 } else { ends[i - 1] };
+        (start as usize, ends[i] as usize)
+    }
+
+    /// Returns the symbol for `value`, adding it if it isn't already stored.
+    pub fn intern(&mut self, value: &str) -> Symbol {
+        let Interner {
+            buffer,
+            ends,
+            table,
+            hasher,
+        } = self;
+        let hash = hasher.hash_one(value);
+        let resolve = |symbol: &Symbol| {
+            let (start, end) = Interner::span(ends, *symbol);
+            &buffer[start..end]
+        };
+        if let Some(symbol) = table.find(hash, |symbol| resolve(symbol) == value) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(u32::try_from(ends.len()).expect("too many strings to intern"));
+        buffer.push_str(value);
+        ends.push(u32::try_from(buffer.len()).expect("too much text to intern"));
+        table.insert_unique(hash, symbol, |symbol| {
+            let (start, end) = Interner::span(ends, *symbol);
+            hasher.hash_one(&buffer[start..end])
+        });
+        symbol
+    }
+
+    /// Returns the symbol for `value`, if it's been interned.
+    pub fn get(&self, value: &str) -> Option<Symbol> {
+        self.table
+            .find(self.hasher.hash_one(value), |symbol| {
+                self.resolve(*symbol) == value
+            })
+            .copied()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        let (start, end) = Interner::span(&self.ends, symbol);
+        &self.buffer[start..end]
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Releases any spare capacity, which is worth doing once all the
+    /// strings have been interned.
+    pub fn shrink_to_fit(&mut self) {
+        let Interner {
+            buffer,
+            ends,
+            table,
+            hasher,
+        } = self;
+        buffer.shrink_to_fit();
+        ends.shrink_to_fit();
+        table.shrink_to_fit(|symbol| {
+            let (start, end) = Interner::span(ends, *symbol);
+            hasher.hash_one(&buffer[start..end])
+        });
+    }
+}
+
+#[test]
+fn test_interner_works() {
+    let mut strings = Interner::new();
+    let boop = strings.intern("BOOP JONES");
+    let blap = strings.intern("BLAP SMITH");
+    assert_eq!(strings.intern("BOOP JONES"), boop);
+    assert_ne!(boop, blap);
+    assert_eq!(strings.resolve(boop), "BOOP JONES");
+    assert_eq!(strings.resolve(blap), "BLAP SMITH");
+    assert_eq!(strings.get("BLAP SMITH"), Some(blap));
+    assert_eq!(strings.get("FLIM FLAM"), None);
+    assert_eq!(strings.len(), 2);
+}
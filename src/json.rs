@@ -1,72 +1,246 @@
+//! The JSON format of portfolios, as output by `Portfolio::json` and read
+//! by the website. Its JSON Schema is checked in at
+//! `schema/portfolio.schema.json`.
+
+use chrono::{DateTime, SecondsFormat, Utc};
 use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::visit::EdgeRef;
-use std::collections::HashSet;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{Metadata, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
 
+use super::bbl::BBL;
+use super::conditions::PortfolioConditions;
+use super::dataset::DatasetOptions;
 use super::hpd_graph::{HpdPetGraph, Node};
 use super::hpd_registrations::HpdRegistrationMap;
+use super::interner::Interner;
+use super::pluto::PlutoLot;
+use super::stabilization::PortfolioStabilizedUnits;
+
+/// The version of the format, which is increased whenever it changes in a
+/// way that could break its consumers. Output from before the format was
+/// versioned has no `schema_version`.
+pub const SCHEMA_VERSION: u32 = 2;
 
 // Note that petgraph supports Serde, but it only supports serializing
 // entire graphs, not connected components, which is what we want, so
 // I guess we'll have to roll our own here.
 
-#[derive(serde::Serialize)]
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonNodeKind {
+    Name,
+    Address,
+}
+
+/// A name or business address.
+#[derive(Serialize, JsonSchema)]
+pub struct JsonNodeValue<'a> {
+    pub kind: JsonNodeKind,
+    pub label: &'a str,
+}
+
+impl<'a> JsonNodeValue<'a> {
+    pub fn new(node: &Node, strings: &'a Interner) -> Self {
+        match node {
+            Node::Name(name) => JsonNodeValue {
+                kind: JsonNodeKind::Name,
+                label: strings.resolve(*name),
+            },
+            Node::BizAddr(addr) => JsonNodeValue {
+                kind: JsonNodeKind::Address,
+                label: strings.resolve(*addr),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
 pub struct JsonNode<'a> {
-    id: usize,
-    value: &'a Node,
+    /// Identifies the node within this portfolio.
+    pub id: usize,
+    #[serde(flatten)]
+    pub value: JsonNodeValue<'a>,
+}
+
+/// Links a name to a business address that appears alongside it in at
+/// least one registration contact.
+#[derive(Serialize, JsonSchema)]
+pub struct JsonEdge<'a> {
+    /// The `id` of one of the nodes.
+    pub from: usize,
+    /// The `id` of the other node.
+    pub to: usize,
+    /// The number of registration contacts linking the two nodes.
+    pub reg_contacts: usize,
+    /// Whether removing this edge would split the portfolio in two.
+    pub is_bridge: bool,
+    /// The BBLs of the buildings whose registrations link the two nodes.
+    pub bbls: Vec<BBL>,
+    /// The ACRIS deeds whose grantees link the two nodes, if ACRIS was
+    /// loaded. An edge with no `reg_contacts` is only linked by deeds.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schemars(default)]
+    pub deeds: Vec<JsonDeed<'a>>,
 }
 
-#[derive(serde::Serialize)]
-pub struct JsonEdge {
-    from: usize,
-    to: usize,
-    reg_contacts: usize,
-    is_bridge: bool,
-    bbl: String,
+/// The most recent deed of one or more tax lots, according to ACRIS.
+#[derive(Serialize, JsonSchema)]
+pub struct JsonDeed<'a> {
+    pub document_id: &'a str,
+    /// ACRIS's type of the document, e.g. "DEED".
+    pub doc_type: &'a str,
+    /// When the deed was executed, as YYYY-MM-DD.
+    pub document_date: String,
+    /// The tax lots the deed transferred.
+    pub bbls: &'a [BBL],
 }
 
-#[derive(serde::Serialize)]
+/// A registration of one of the portfolio's buildings. A registration can
+/// cover several buildings, and a building can have several registrations.
+#[derive(Serialize, JsonSchema)]
+pub struct JsonBuilding<'a> {
+    pub bbl: BBL,
+    /// The building's Building Identification Number, if it has one.
+    pub bin: Option<u32>,
+    pub registration_id: u32,
+    /// When the registration expires, as YYYY-MM-DD.
+    pub registration_end_date: String,
+    /// The names in the portfolio that appear in the registration's
+    /// contacts.
+    pub names: Vec<&'a str>,
+    /// The business addresses in the portfolio that appear in the
+    /// registration's contacts.
+    pub addresses: Vec<&'a str>,
+    /// What PLUTO says about the building's tax lot, if PLUTO was loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pluto: Option<&'a PlutoLot>,
+}
+
+/// A portfolio of buildings, inferred from the names and business addresses
+/// of their owners.
+#[derive(Serialize, JsonSchema)]
+#[schemars(rename = "Portfolio")]
 pub struct JsonGraph<'a> {
-    title: String,
-    nodes: Vec<JsonNode<'a>>,
-    edges: Vec<JsonEdge>,
+    #[schemars(schema_with = "schema_version_schema")]
+    pub schema_version: u32,
+    /// When the output was generated, in RFC 3339 format.
+    pub generated_at: String,
+    /// How the data the portfolio was inferred from was loaded.
+    pub options: DatasetOptions,
+    /// The portfolio's ID, which is stable between data releases.
+    pub id: String,
+    pub title: String,
+    /// Every building in the portfolio, sorted by BBL.
+    pub buildings: Vec<JsonBuilding<'a>>,
+    /// The total number of residential units on the portfolio's tax lots,
+    /// if PLUTO was loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub residential_units: Option<u32>,
+    /// Measures of the portfolio's housing conditions, if HPD violations or
+    /// complaints were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<PortfolioConditions>,
+    /// The portfolio's rent-stabilized units, if they were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stabilized_units: Option<PortfolioStabilizedUnits>,
+    pub nodes: Vec<JsonNode<'a>>,
+    pub edges: Vec<JsonEdge<'a>>,
+}
+
+fn schema_version_schema(_: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        metadata: Some(Box::new(Metadata {
+            description: Some("The version of this format.".to_owned()),
+            ..Default::default()
+        })),
+        const_value: Some(SCHEMA_VERSION.into()),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Returns the current time for `generated_at`, or the time given by the
+/// `SOURCE_DATE_EPOCH` environment variable, for reproducible output.
+pub(crate) fn generated_at() -> String {
+    let now = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .and_then(|epoch| DateTime::from_timestamp(epoch, 0))
+        .unwrap_or_else(Utc::now);
+    now.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub struct PortfolioJson<'a> {
+    pub id: String,
+    pub title: String,
+    pub buildings: Vec<JsonBuilding<'a>>,
+    pub residential_units: Option<u32>,
+    pub conditions: Option<PortfolioConditions>,
+    pub stabilized_units: Option<PortfolioStabilizedUnits>,
+    pub nodes: &'a BTreeSet<NodeIndex<u32>>,
+    pub local_bridges: HashSet<EdgeIndex<u32>>,
 }
 
 pub fn portfolio_json<'a>(
-    title: String,
-    nodes: &'a HashSet<NodeIndex<u32>>,
+    portfolio: PortfolioJson<'a>,
     petgraph: &'a HpdPetGraph,
-    local_bridges: HashSet<EdgeIndex<u32>>,
-    regs: &'a HpdRegistrationMap,
+    strings: &'a Interner,
+    regs: &HpdRegistrationMap,
+    options: &DatasetOptions,
 ) -> JsonGraph<'a> {
     let mut edges_written = HashSet::new();
     let mut graph = JsonGraph {
-        title,
+        schema_version: SCHEMA_VERSION,
+        generated_at: generated_at(),
+        options: options.clone(),
+        id: portfolio.id,
+        title: portfolio.title,
+        buildings: portfolio.buildings,
+        residential_units: portfolio.residential_units,
+        conditions: portfolio.conditions,
+        stabilized_units: portfolio.stabilized_units,
         nodes: vec![],
         edges: vec![],
     };
 
-    for node in nodes {
+    for node in portfolio.nodes {
         graph.nodes.push(JsonNode {
             id: node.index(),
-            value: petgraph.node_weight(*node).unwrap(),
+            value: JsonNodeValue::new(&petgraph[*node], strings),
         });
         for edge in petgraph.edges(*node) {
             let id = edge.id();
             if !edges_written.contains(&id) {
                 edges_written.insert(id);
-                let reg_infos = edge.weight();
-
-                // All this unwrapping/indexing carries a lot of assumptions with
-                // it, but it seems to hold, so we'll change it only if it ever
-                // panics.
-                let bbl = regs.get_by_id(reg_infos[0].id).unwrap()[0].bbl.to_string();
+                let reg_infos = &edge.weight().regs;
+                let bbls: BTreeSet<BBL> = reg_infos
+                    .iter()
+                    .filter_map(|reg_info| regs.get_by_id(reg_info.id))
+                    .flatten()
+                    .map(|reg| reg.bbl)
+                    .collect();
 
                 graph.edges.push(JsonEdge {
                     from: edge.source().index(),
                     to: edge.target().index(),
                     reg_contacts: reg_infos.len(),
-                    is_bridge: local_bridges.contains(&id),
-                    bbl,
+                    is_bridge: portfolio.local_bridges.contains(&id),
+                    bbls: bbls.into_iter().collect(),
+                    deeds: edge
+                        .weight()
+                        .deeds
+                        .iter()
+                        .map(|deed| JsonDeed {
+                            document_id: &deed.document_id,
+                            doc_type: &deed.doc_type,
+                            document_date: deed.document_date.format("%Y-%m-%d").to_string(),
+                            bbls: &deed.bbls,
+                        })
+                        .collect(),
                 });
             }
         }
@@ -74,3 +248,9 @@ pub fn portfolio_json<'a>(
 
     graph
 }
+
+/// Returns the JSON Schema of `Portfolio::json`'s output.
+pub fn portfolio_json_schema() -> String {
+    let schema = schemars::schema_for!(JsonGraph);
+    serde_json::to_string_pretty(&schema).unwrap()
+}
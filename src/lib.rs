@@ -0,0 +1,48 @@
+//! Infers portfolios of NYC buildings from NYC Housing Preservation &
+//! Development (HPD) registration data.
+//!
+//! Load a `Dataset` with a `DatasetBuilder`, then query its portfolios:
+//!
+//! ```
+//! use hpd::{CountBy, DatasetBuilder};
+//!
+//! let dataset = DatasetBuilder::new().read_dir("tests/fixtures")?;
+//! let portfolio = dataset.portfolio_with_name("BOOP JONES")?;
+//! assert_eq!(portfolio.building_count(&dataset.regs, CountBy::Bbls), 5);
+//! # Ok::<(), hpd::HpdError>(())
+//! ```
+
+pub mod acris;
+pub mod bbl;
+pub mod conditions;
+mod csv_batches;
+pub mod dataset;
+pub mod diff;
+pub mod error;
+pub mod evictions;
+pub mod export;
+pub mod history;
+pub mod hpd_graph;
+pub mod hpd_registrations;
+pub mod interner;
+pub mod json;
+mod local_bridge;
+pub mod pluto;
+pub mod portfolio;
+pub mod portfolio_id;
+mod ranking;
+pub mod report;
+pub mod server;
+pub mod sqlite;
+pub mod stabilization;
+mod synonyms;
+pub mod tables;
+pub mod website;
+
+pub use bbl::{Boro, BBL};
+pub use dataset::{Dataset, DatasetBuilder, DatasetOptions};
+pub use error::HpdError;
+pub use hpd_graph::HpdGraph;
+pub use hpd_registrations::{CountBy, HpdRegistrationMap};
+pub use portfolio::{Portfolio, PortfolioMap};
+pub use portfolio_id::PortfolioIdSnapshot;
@@ -23,11 +23,11 @@ impl LocalBridgeFinder {
                 entry_times.insert(n, time.0);
             }
             DfsEvent::TreeEdge(n1, n2) => {
-                let entry = tree_edges.entry(n1).or_insert_with(|| vec![]);
+                let entry = tree_edges.entry(n1).or_default();
                 entry.push(n2);
             }
             DfsEvent::BackEdge(n1, n2) => {
-                let entry = back_edges.entry(n1).or_insert_with(|| vec![]);
+                let entry = back_edges.entry(n1).or_default();
                 entry.push(n2);
             }
             _ => {}
@@ -58,7 +58,7 @@ impl LocalBridgeFinder {
             }
         }
 
-        times.iter().min().map(|u| *u)
+        times.iter().min().copied()
     }
 
     pub fn is_local_bridge(&self, from: NodeIndex<u32>, to: NodeIndex<u32>) -> Option<bool> {
@@ -79,6 +79,7 @@ impl LocalBridgeFinder {
             }
         }
 
+        result.sort();
         result
     }
 }
@@ -89,7 +90,7 @@ mod tests {
     use petgraph::graph::UnGraph;
 
     fn make_graph() -> UnGraph<u32, ()> {
-        UnGraph::<u32, ()>::from_edges(&[
+        UnGraph::<u32, ()>::from_edges([
             // Clique A
             (1, 2),
             (2, 3),
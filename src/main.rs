@@ -1,182 +1,491 @@
-mod bbl;
-mod hpd_graph;
-mod hpd_registrations;
-mod json;
-mod local_bridge;
-mod portfolio;
-mod ranking;
-mod synonyms;
-mod website;
-
-use chrono::Duration;
-use clap::{value_t, App, AppSettings, Arg, SubCommand};
-use petgraph::algo::{connected_components, dijkstra};
-use petgraph::visit::VisitMap;
-use std::collections::HashSet;
-use std::error::Error;
-use std::ops::Deref;
-use std::rc::Rc;
-
-use hpd_graph::{HpdGraph, Node};
-use hpd_registrations::HpdRegistrationMap;
-use portfolio::{Portfolio, PortfolioMap};
-
-const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+mod shell;
+
+use chrono::{Duration, NaiveDate};
+use clap::{value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
+use petgraph::graph::NodeIndex;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use hpd::acris::AcrisDeeds;
+use hpd::conditions::{ComplaintCounts, ViolationCounts};
+use hpd::dataset::open_csv;
+use hpd::diff::SnapshotDiff;
+use hpd::evictions::EvictionCounts;
+use hpd::export::ExportFormat;
+use hpd::pluto::PlutoMap;
+use hpd::report::{self, GraphSummary, PortfolioSummary, RankBy};
+use hpd::server::ApiServer;
+use hpd::stabilization::StabilizedUnitCounts;
+use hpd::tables::{GraphTables, TableFormat};
+use hpd::{history, json, sqlite, website};
+use hpd::{Boro, CountBy, Dataset, DatasetBuilder, HpdError, Portfolio, PortfolioIdSnapshot, BBL};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 struct ProgramArgs {
     max_expiration_age: i64,
     include_corps: bool,
+    previous_ids: Option<String>,
+    pluto: Option<String>,
+    violations: Option<String>,
+    complaints: Option<String>,
+    complaints_since: NaiveDate,
+    evictions: Option<String>,
+    evictions_since: NaiveDate,
+    rent_stabilization: Option<String>,
+    acris: Option<String>,
+    keep_expired: bool,
+    count_by: CountBy,
 }
 
-struct Program {
-    regs: HpdRegistrationMap,
-    hpd: HpdGraph,
+/// Identifies a portfolio by one of its names or one of its buildings.
+enum PortfolioQuery<'a> {
+    Name(&'a str),
+    Bbl(BBL),
 }
 
-impl Program {
-    fn new(args: ProgramArgs) -> Result<Self, Box<dyn Error>> {
-        let reg_rdr = csv::Reader::from_path("Multiple_Dwelling_Registrations.csv")?;
-        let regs = HpdRegistrationMap::from_csv(reg_rdr, Duration::days(args.max_expiration_age))?;
+impl<'a> PortfolioQuery<'a> {
+    /// Returns the portfolio given by the `--bbl` or `NAME` arguments, if
+    /// either was given.
+    fn from_matches(matches: &'a ArgMatches) -> Option<Self> {
+        if matches.is_present("bbl") {
+            let bbl = value_t!(matches, "bbl", BBL).unwrap_or_else(|e| e.exit());
+            Some(PortfolioQuery::Bbl(bbl))
+        } else {
+            matches.value_of("NAME").map(PortfolioQuery::Name)
+        }
+    }
+}
+
+/// Describes a portfolio's size, along with its most frequently mentioned
+/// business addresses and names.
+fn portfolio_info(summary: &PortfolioSummary) -> String {
+    let mut out = String::new();
+    writeln!(out, "This is {}.", summary.name).unwrap();
+    match summary.residential_units {
+        Some(units) => writeln!(
+            out,
+            "It has {} buildings with {} residential units.",
+            summary.buildings, units
+        ),
+        None => writeln!(out, "It has {} buildings.", summary.buildings),
+    }
+    .unwrap();
+    if !summary.boroughs.is_empty() {
+        let boroughs: Vec<String> = summary
+            .boroughs
+            .iter()
+            .map(|boro| format!("{} {}", boro.boro, boro.buildings))
+            .collect();
+        writeln!(out, "Buildings by borough: {}.", boroughs.join(", ")).unwrap();
+    }
+    if let Some(conditions) = &summary.conditions {
+        for sentence in conditions.describe() {
+            writeln!(out, "{}", sentence).unwrap();
+        }
+    }
+    if let Some(evictions) = &summary.evictions {
+        writeln!(out, "{}", evictions.describe()).unwrap();
+    }
+    if let Some(stabilized_units) = &summary.stabilized_units {
+        writeln!(out, "{}", stabilized_units.describe()).unwrap();
+    }
 
-        let rdr = csv::Reader::from_path("Registration_Contacts.csv")?;
-        let hpd = HpdGraph::from_csv(rdr, &regs, args.include_corps).unwrap();
+    writeln!(
+        out,
+        "\nThe most frequent business addresses mentioned in the portfolio are:\n"
+    )
+    .unwrap();
+    for mention in &summary.top_addresses {
+        writeln!(
+            out,
+            "{} (mentioned in {} HPD registration contacts)",
+            mention.value, mention.reg_contacts
+        )
+        .unwrap();
+    }
 
-        Ok(Program { regs, hpd })
+    writeln!(
+        out,
+        "\nThe most frequent names mentioned in the portfolio are:\n"
+    )
+    .unwrap();
+    for mention in &summary.top_names {
+        writeln!(
+            out,
+            "{} (mentioned in {} HPD registration contacts)",
+            mention.value, mention.reg_contacts
+        )
+        .unwrap();
     }
 
-    fn cmd_json(&self, name: &str) {
-        let portfolio = self.get_portfolio_with_name(&name.to_owned());
-        println!("{}", portfolio.json(&self.regs));
+    let bridges = summary.local_bridges;
+
+    if bridges > 0 {
+        writeln!(
+            out,
+            "\nThe portfolio has {} local bridge{}.\n",
+            bridges,
+            if bridges > 1 { "s" } else { "" }
+        )
+        .unwrap();
     }
 
-    fn cmd_info(&self, name: Option<&str>, top: usize) {
-        let cc = connected_components(&self.hpd.graph.deref());
+    out
+}
+
+struct Program {
+    data: Dataset,
+    count_by: CountBy,
+}
+
+impl Program {
+    fn new(args: ProgramArgs) -> Result<Self, HpdError> {
+        Program::from_dir(&args, Path::new("."))
+    }
+
+    fn from_dir(args: &ProgramArgs, dir: &Path) -> Result<Self, HpdError> {
+        let mut builder = DatasetBuilder::new()
+            .max_expiration_age(Duration::days(args.max_expiration_age))
+            .include_corps(args.include_corps)
+            .keep_expired(args.keep_expired)
+            .count_by(args.count_by);
+        if let Some(path) = &args.previous_ids {
+            builder = builder.previous_ids(PortfolioIdSnapshot::from_csv(open_csv(path)?)?);
+        }
+        if let Some(path) = &args.pluto {
+            builder = builder.pluto(PlutoMap::from_csv(open_csv(path)?)?);
+        }
+        if let Some(path) = &args.violations {
+            builder = builder.violations(ViolationCounts::from_csv(open_csv(path)?)?);
+        }
+        if let Some(path) = &args.complaints {
+            builder = builder.complaints(ComplaintCounts::from_csv(
+                open_csv(path)?,
+                args.complaints_since,
+            )?);
+        }
+        if let Some(path) = &args.evictions {
+            builder = builder.evictions(EvictionCounts::from_csv(
+                open_csv(path)?,
+                args.evictions_since,
+            )?);
+        }
+        if let Some(path) = &args.rent_stabilization {
+            builder = builder.stabilized_units(StabilizedUnitCounts::from_csv(open_csv(path)?)?);
+        }
+        if let Some(dir) = &args.acris {
+            builder = builder.deeds(AcrisDeeds::read_dir(dir)?);
+        }
+
+        Ok(Program {
+            data: builder.read_dir(dir)?,
+            count_by: args.count_by,
+        })
+    }
+
+    fn cmd_json(&self, query: &PortfolioQuery) -> Result<(), HpdError> {
+        let portfolio = self.get_portfolio(query)?;
         println!(
-            "Read {} unique names, {} unique addresses, and {} connected components.",
-            self.hpd.name_nodes.len(),
-            self.hpd.addr_nodes.len(),
-            cc
+            "{}",
+            portfolio.json(&self.data.regs, self.count_by, self.data.options())
         );
+        Ok(())
+    }
 
-        if let Some(name) = name {
-            let portfolio = self.get_portfolio_with_name(&name.to_owned());
-            println!("This is {}.", portfolio.name());
-            println!("It has {} buildings.", portfolio.building_count(&self.regs));
-
-            let bizaddrs = portfolio.rank_bizaddrs();
-            println!("\nThe most frequent business addresses mentioned in the portfolio are:\n");
-            for (bizaddr, total_regs) in bizaddrs.iter().take(top) {
-                println!(
-                    "{} (mentioned in {} HPD registration contacts)",
-                    bizaddr, total_regs
-                );
+    fn cmd_info(
+        &self,
+        query: Option<PortfolioQuery>,
+        top: usize,
+        format: &str,
+    ) -> Result<(), HpdError> {
+        let portfolio = match query {
+            Some(query) => {
+                let portfolio = self.get_portfolio(&query)?;
+                Some(PortfolioSummary::new(
+                    &portfolio,
+                    &self.data.regs,
+                    self.count_by,
+                    top,
+                ))
             }
+            None => None,
+        };
+        let summary = GraphSummary::new(&self.data.hpd, portfolio);
 
-            let names = portfolio.rank_names();
-            println!("\nThe most frequent names mentioned in the portfolio are:\n");
-            for (name, total_regs) in names.iter().take(top) {
+        match format {
+            "json" => println!("{}", summary.json()),
+            "csv" => summary.write_csv(&mut csv::Writer::from_writer(std::io::stdout()))?,
+            _ => {
                 println!(
-                    "{} (mentioned in {} HPD registration contacts)",
-                    name, total_regs
+                    "Read {} unique names, {} unique addresses, and {} connected components.",
+                    summary.names, summary.addresses, summary.connected_components
                 );
+                if let Some(portfolio) = &summary.portfolio {
+                    print!("{}", portfolio_info(portfolio));
+                }
             }
+        }
 
-            let bridges = portfolio.find_local_bridges().len();
+        Ok(())
+    }
 
-            if bridges > 0 {
-                println!(
-                    "\nThe portfolio has {} local bridge{}.\n",
-                    bridges,
-                    if bridges > 1 { "s" } else { "" }
-                );
-            }
-        }
+    fn find_name(&self, name: &str) -> Result<NodeIndex<u32>, HpdError> {
+        let node = self.data.find_name(name)?;
+        eprintln!("Found a matching name '{}'.", self.data.hpd.node_str(node));
+        Ok(node)
     }
 
-    fn make_portfolios(&self) -> PortfolioMap {
-        PortfolioMap::from_graph(Rc::clone(&self.hpd.graph))
+    fn get_portfolio_with_name(&self, name: &str) -> Result<Arc<Portfolio>, HpdError> {
+        let node = self.find_name(name)?;
+        self.data
+            .portfolios()
+            .for_node(node)
+            .ok_or_else(|| HpdError::NotFound {
+                what: "portfolio",
+                search: name.to_owned(),
+            })
     }
 
-    fn get_portfolio_with_name(&self, name: &String) -> Rc<Portfolio> {
-        if let Some(node) = self.hpd.find_name(&name) {
-            eprintln!(
-                "Found a matching name '{}'.",
-                self.hpd.graph.node_weight(node).unwrap().to_str()
-            );
-            self.make_portfolios().for_node(node).unwrap()
-        } else {
-            eprintln!("Unable to find a match for the name '{}'.", &name);
-            std::process::exit(1);
+    fn get_portfolio(&self, query: &PortfolioQuery) -> Result<Arc<Portfolio>, HpdError> {
+        match query {
+            PortfolioQuery::Name(name) => self.get_portfolio_with_name(name),
+            PortfolioQuery::Bbl(bbl) => self.data.portfolio_with_bbl(*bbl),
         }
     }
 
-    fn cmd_dot(&self, name: &String) {
-        let portfolio = self.get_portfolio_with_name(name);
+    fn cmd_dot(&self, query: &PortfolioQuery) -> Result<(), HpdError> {
+        let portfolio = self.get_portfolio(query)?;
         println!("{}", portfolio.dot_graph());
+        Ok(())
     }
 
-    fn cmd_ranking(&self, min_buildings: usize) {
-        let ranking = self
-            .make_portfolios()
-            .rank_by_building_count(&self.regs, min_buildings);
+    fn cmd_export(
+        &self,
+        query: Option<PortfolioQuery>,
+        format: ExportFormat,
+    ) -> Result<(), HpdError> {
+        let output = match query {
+            Some(query) => self.get_portfolio(&query)?.export(&self.data.regs, format),
+            None => self
+                .data
+                .portfolios()
+                .export(&self.data.hpd, &self.data.regs, format),
+        };
+        print!("{}", output);
+        Ok(())
+    }
 
-        let mut rank = 1;
-        for (portfolio, size) in ranking {
-            let name = portfolio.name();
-            println!("{}. {} - {} buildings", rank, name, size);
-            rank += 1;
+    fn cmd_export_tables(&self, dir: &Path, format: TableFormat) -> Result<(), HpdError> {
+        let portfolios = self.data.portfolios();
+        let tables = GraphTables::new(&self.data.hpd, portfolios, &self.data.regs);
+        for path in tables.write(dir, format)? {
+            println!("Wrote {}.", path.display());
         }
+        Ok(())
     }
 
-    fn cmd_website(&self, min_buildings: usize) -> Result<(), Box<dyn Error>> {
-        website::make_website(self.make_portfolios(), &self.regs, min_buildings)
+    fn cmd_export_sqlite(&self, path: &Path) -> Result<(), HpdError> {
+        let portfolios = self.data.portfolios();
+        sqlite::write_database(
+            path,
+            &self.data.hpd,
+            portfolios,
+            &self.data.regs,
+            self.count_by,
+        )?;
+        println!("Wrote {}.", path.display());
+        Ok(())
     }
 
-    fn cmd_longpaths(&self, min_length: u32) {
-        let mut visits = HashSet::new();
-
-        println!("\nPaths with minimum length {}:\n", min_length);
+    fn cmd_ranking(
+        &self,
+        rank_by: RankBy,
+        min_buildings: usize,
+        boro: Option<Boro>,
+        top: usize,
+        format: &str,
+    ) -> Result<(), HpdError> {
+        let ranking = report::ranking(
+            self.data.portfolios(),
+            &self.data.regs,
+            self.count_by,
+            rank_by,
+            min_buildings,
+            boro,
+            top,
+        );
 
-        for node in self.hpd.graph.node_indices() {
-            if visits.is_visited(&node) {
-                continue;
+        match format {
+            "json" => println!("{}", report::ranking_json(&ranking)),
+            "csv" => {
+                let mut wtr = csv::Writer::from_writer(std::io::stdout());
+                report::write_ranking_csv(&mut wtr, &ranking)?;
             }
-            visits.visit(node);
-            if let Some(Node::Name(_)) = self.hpd.graph.node_weight(node) {
-                let mut max_cost = 0;
-                let mut max_cost_node = None;
-                let dijkstra_map = dijkstra(&self.hpd.graph.deref(), node, None, |_| 1);
-                for (other_node, cost) in dijkstra_map {
-                    visits.visit(other_node);
-                    if let Some(Node::Name(_)) = self.hpd.graph.node_weight(other_node) {
-                        if cost > max_cost {
-                            max_cost = cost;
-                            max_cost_node = Some(other_node);
-                        }
+            _ => {
+                for entry in ranking {
+                    let mut line = format!(
+                        "{}. {} - {} buildings",
+                        entry.rank, entry.name, entry.buildings
+                    );
+                    if let Some(units) = entry.residential_units {
+                        write!(line, ", {} units", units).unwrap();
                     }
-                }
-                if max_cost >= min_length {
-                    if let Some(other_node) = max_cost_node {
-                        let (_, path) = petgraph::algo::astar(
-                            &self.hpd.graph.deref(),
-                            node,
-                            |n| n == other_node,
-                            |_| 1,
-                            |_| 1,
+                    let conditions = entry.conditions.as_ref();
+                    if let Some(violations) = conditions.and_then(|c| c.open_class_c_violations) {
+                        write!(line, ", {} open class C violations", violations).unwrap();
+                    }
+                    if let Some(complaints) = conditions.and_then(|c| c.recent_complaints) {
+                        write!(line, ", {} recent complaints", complaints).unwrap();
+                    }
+                    if let Some(evictions) = &entry.evictions {
+                        write!(line, ", {} evictions", evictions.evictions).unwrap();
+                    }
+                    if let Some(stabilized_units) = &entry.stabilized_units {
+                        write!(
+                            line,
+                            ", {} stabilized units",
+                            stabilized_units.stabilized_units
                         )
                         .unwrap();
-                        println!(
-                            "length {} path: {}\n",
-                            max_cost,
-                            &self.hpd.path_to_string(path)
-                        );
+                        if let Some(lost) = stabilized_units.stabilized_units_lost {
+                            write!(line, " ({} lost)", lost).unwrap();
+                        }
                     }
+                    println!("{}", line);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn cmd_ids(&self) -> Result<(), HpdError> {
+        let portfolios = self.data.portfolios().id_snapshot(&self.data.regs);
+        let mut wtr = csv::Writer::from_writer(std::io::stdout());
+        PortfolioIdSnapshot::write_csv(&mut wtr, &portfolios)
+    }
+
+    fn cmd_diff(&self, new: &Program, format: &str, min_buildings: usize) {
+        let old_portfolios = self.data.portfolios();
+        let old_ids =
+            PortfolioIdSnapshot::from_portfolios(&old_portfolios.id_snapshot(&self.data.regs));
+        let new_portfolios = new.data.portfolios_with_ids(Some(&old_ids));
+        let diff = SnapshotDiff::new(
+            old_portfolios,
+            &self.data.regs,
+            &new_portfolios,
+            &new.data.regs,
+            self.count_by,
+            min_buildings,
+        );
+
+        match format {
+            "json" => println!("{}", diff.json()),
+            _ => print!("{}", diff.text()),
+        }
+    }
+
+    fn cmd_history(
+        &self,
+        name: &str,
+        dates: &[NaiveDate],
+        max_expiration_age: i64,
+        format: &str,
+    ) -> Result<(), HpdError> {
+        let node = self.find_name(name)?;
+        let name = self.data.hpd.node_str(node);
+        let entries = history::portfolio_history(
+            &self.data.hpd,
+            &self.data.regs,
+            self.count_by,
+            name,
+            dates,
+            Duration::days(max_expiration_age),
+        );
+
+        match format {
+            "json" => println!("{}", history::history_json(&entries)),
+            _ => {
+                let mut wtr = csv::Writer::from_writer(std::io::stdout());
+                history::write_history_csv(&mut wtr, &entries)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cmd_website(&self, min_buildings: usize, boro: Option<Boro>) -> Result<(), HpdError> {
+        website::make_website(&self.data, self.count_by, min_buildings, boro)
+    }
+
+    fn cmd_shell(self) -> Result<(), HpdError> {
+        shell::run(self.data, self.count_by)
+    }
+
+    fn cmd_serve(self, addr: &str) -> Result<(), HpdError> {
+        let api = ApiServer::new(self.data);
+        let server = tiny_http::Server::http(addr).map_err(|e| HpdError::Server(e.to_string()))?;
+        eprintln!("Listening on http://{}.", server.server_addr());
+        api.serve(&server);
+        Ok(())
+    }
+
+    fn cmd_buildings(&self, query: &PortfolioQuery, format: &str) -> Result<(), HpdError> {
+        let portfolio = self.get_portfolio(query)?;
+        let buildings = portfolio.buildings(&self.data.regs);
+
+        match format {
+            "json" => println!("{}", report::buildings_json(&buildings)),
+            _ => {
+                let mut wtr = csv::Writer::from_writer(std::io::stdout());
+                report::write_buildings_csv(&mut wtr, &buildings)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cmd_longpaths(&self, min_length: u32, format: &str) -> Result<(), HpdError> {
+        let paths = report::long_paths(&self.data.hpd, min_length);
+
+        match format {
+            "json" => println!("{}", report::long_paths_json(&paths)),
+            "csv" => {
+                let mut wtr = csv::Writer::from_writer(std::io::stdout());
+                report::write_long_paths_csv(&mut wtr, &paths)?;
+            }
+            _ => {
+                println!("\nPaths with minimum length {}:\n", min_length);
+                for path in paths {
+                    println!("length {} path: {}\n", path.length, path.describe());
                 }
             }
         }
+        Ok(())
     }
 }
 
+/// An alternative to a `NAME` argument that selects a portfolio by one of
+/// its buildings.
+fn bbl_arg() -> Arg<'static, 'static> {
+    Arg::with_name("bbl")
+        .long("bbl")
+        .value_name("BBL")
+        .conflicts_with("NAME")
+        .validator(|bbl| bbl.parse::<BBL>().map(|_| ()).map_err(|e| e.to_string()))
+        .help("Use the portfolio containing this building, e.g. 3012340010 or \"Brooklyn 1234 10\"")
+        .takes_value(true)
+}
+
+/// Restricts a list of portfolios to those entirely within one borough.
+fn boro_arg() -> Arg<'static, 'static> {
+    Arg::with_name("boro")
+        .long("boro")
+        .value_name("BORO")
+        .validator(|boro| boro.parse::<Boro>().map(|_| ()).map_err(|e| e.to_string()))
+        .help("Only show portfolios whose buildings are all in this borough, e.g. Bronx or BX")
+        .takes_value(true)
+}
+
 fn main() {
     let matches = App::new("hpd-graph-fun")
         .setting(AppSettings::SubcommandRequiredElseHelp)
@@ -200,10 +509,92 @@ fn main() {
                 .long("include-corps")
                 .help("Include corporation names in portfolios"),
         )
+        .arg(
+            Arg::with_name("count-by")
+                .long("count-by")
+                .possible_values(CountBy::NAMES)
+                .default_value("registrations")
+                .help("How to decide which registrations are for the same building")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .value_name("N")
+                .default_value("0")
+                .takes_value(true)
+                .help("The number of threads to use, or 0 for one per CPU core"),
+        )
+        .arg(
+            Arg::with_name("previous-ids")
+                .long("previous-ids")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Carry portfolio IDs forward from a CSV previously written by 'ids'"),
+        )
+        .arg(
+            Arg::with_name("pluto")
+                .long("pluto")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Join unit counts and building attributes from a PLUTO CSV"),
+        )
+        .arg(
+            Arg::with_name("violations")
+                .long("violations")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Count open class C violations from an HPD Violations CSV"),
+        )
+        .arg(
+            Arg::with_name("complaints")
+                .long("complaints")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Count recent complaints from an HPD Complaints CSV"),
+        )
+        .arg(
+            Arg::with_name("complaints-since")
+                .long("complaints-since")
+                .value_name("YYYY-MM-DD")
+                .takes_value(true)
+                .requires("complaints")
+                .help("Only count complaints received on or after this date [default: a year ago]"),
+        )
+        .arg(
+            Arg::with_name("evictions")
+                .long("evictions")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Count residential evictions from a CSV of marshal evictions"),
+        )
+        .arg(
+            Arg::with_name("evictions-since")
+                .long("evictions-since")
+                .value_name("YYYY-MM-DD")
+                .takes_value(true)
+                .requires("evictions")
+                .help("Only count evictions executed on or after this date [default: a year ago]"),
+        )
+        .arg(
+            Arg::with_name("rent-stabilization")
+                .long("rent-stabilization")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Count rent-stabilized units from a CSV of yearly counts by BBL"),
+        )
+        .arg(
+            Arg::with_name("acris")
+                .long("acris")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Also link owners by the grantees of ACRIS deeds in DIR"),
+        )
         .subcommand(
             SubCommand::with_name("info")
                 .about("Shows general information about the graph")
                 .arg(Arg::with_name("NAME"))
+                .arg(bbl_arg())
                 .arg(
                     Arg::with_name("top")
                         .short("t")
@@ -212,6 +603,15 @@ fn main() {
                         .default_value("5")
                         .help("Show the top N names and business addresses in the portfolio")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["text", "json", "csv"])
+                        .default_value("text")
+                        .help("Output format")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -224,17 +624,92 @@ fn main() {
                         .default_value("10")
                         .help("Only show paths with this minimum length")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["text", "json", "csv"])
+                        .default_value("text")
+                        .help("Output format")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
             SubCommand::with_name("dot")
                 .about("Output a dot graph of a particular portfolio")
-                .arg(Arg::with_name("NAME").required(true)),
+                .arg(Arg::with_name("NAME").required_unless("bbl"))
+                .arg(bbl_arg()),
         )
         .subcommand(
             SubCommand::with_name("json")
                 .about("Output JSON of a particular portfolio")
-                .arg(Arg::with_name("NAME").required(true)),
+                .arg(Arg::with_name("NAME").required_unless("bbl"))
+                .arg(bbl_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("buildings")
+                .about("Output every building in a particular portfolio")
+                .arg(Arg::with_name("NAME").required_unless("bbl"))
+                .arg(bbl_arg())
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["csv", "json"])
+                        .default_value("csv")
+                        .help("Output format")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Export a particular portfolio, or the whole graph, for Gephi or Cytoscape")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("A name in the portfolio to export [default: the whole graph]"),
+                )
+                .arg(bbl_arg())
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(ExportFormat::NAMES)
+                        .default_value("graphml")
+                        .help("Output format")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export-tables")
+                .about("Export every node, edge and registration contact as tables")
+                .arg(
+                    Arg::with_name("DIR")
+                        .required(true)
+                        .help("The directory to write the tables to"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(TableFormat::NAMES)
+                        .default_value("csv")
+                        .help("Output format (Parquet requires building with '--features parquet')")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export-sqlite")
+                .about("Export the registrations, graph and portfolios as a SQLite database")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .help("The database to write, which is replaced if it exists"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("json-schema")
+                .about("Output the JSON Schema of the output of 'json'"),
         )
         .subcommand(
             SubCommand::with_name("ranking")
@@ -246,6 +721,135 @@ fn main() {
                         .default_value("0")
                         .help("Only show portfolios of a minimum size")
                         .takes_value(true),
+                )
+                .arg(boro_arg())
+                .arg(
+                    Arg::with_name("rank-by")
+                        .long("rank-by")
+                        .alias("sort-by")
+                        .possible_values(RankBy::NAMES)
+                        .default_value("buildings")
+                        .help(
+                            "What to rank portfolios by; ranking by units requires --pluto, \
+                             by evictions requires --evictions, and by stabilized units \
+                             requires --rent-stabilization",
+                        )
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .short("t")
+                        .long("top")
+                        .value_name("N")
+                        .default_value("5")
+                        .help(
+                            "Include the top N names and business addresses of each portfolio \
+                             in JSON and CSV output",
+                        )
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["text", "json", "csv"])
+                        .default_value("text")
+                        .help("Output format")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("Output a timeline of how a particular portfolio evolved")
+                .arg(Arg::with_name("NAME").required(true))
+                .arg(
+                    Arg::with_name("start")
+                        .long("start")
+                        .value_name("YYYY-MM-DD")
+                        .help("The first date in the timeline [default: ten years before --end]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("end")
+                        .long("end")
+                        .value_name("YYYY-MM-DD")
+                        .help("The last date in the timeline [default: today]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("every")
+                        .long("every")
+                        .value_name("MONTHS")
+                        .default_value("12")
+                        .help("The number of months between dates in the timeline")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["csv", "json"])
+                        .default_value("csv")
+                        .help("Output format")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ids")
+                .about("Output a CSV of every portfolio ID and the BBLs it contains"),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Show how portfolios changed between two data releases")
+                .arg(
+                    Arg::with_name("OLD")
+                        .required(true)
+                        .help("Directory containing the older CSV files"),
+                )
+                .arg(
+                    Arg::with_name("NEW")
+                        .required(true)
+                        .help("Directory containing the newer CSV files"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["text", "json"])
+                        .default_value("text")
+                        .help("Output format")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("min-buildings")
+                        .short("b")
+                        .long("min-buildings")
+                        .default_value("0")
+                        .help("Only show portfolios of a minimum size")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("shell")
+                .about("Explore the graph interactively, loading the data only once"),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serve a JSON API for querying portfolios over HTTP")
+                .arg(
+                    Arg::with_name("host")
+                        .long("host")
+                        .default_value("127.0.0.1")
+                        .help("The address to listen on")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .short("p")
+                        .long("port")
+                        .default_value("8000")
+                        .help("The port to listen on")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -258,38 +862,144 @@ fn main() {
                         .default_value("100")
                         .help("Only show portfolios of a minimum size")
                         .takes_value(true),
-                ),
+                )
+                .arg(boro_arg()),
         )
         .get_matches();
 
-    let args = ProgramArgs {
+    if let Err(e) = run(&matches) {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// Returns the date given by the named argument, or a year ago if it wasn't
+/// given.
+fn since_arg(matches: &ArgMatches, name: &str) -> NaiveDate {
+    match matches.value_of(name) {
+        Some(_) => value_t!(matches.value_of(name), NaiveDate).unwrap_or_else(|e| e.exit()),
+        None => chrono::offset::Local::now().date_naive() - chrono::Months::new(12),
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), HpdError> {
+    let threads = value_t!(matches.value_of("threads"), usize).unwrap_or_else(|e| e.exit());
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .expect("the thread pool should only be initialized once");
+
+    let mut args = ProgramArgs {
         max_expiration_age: value_t!(matches.value_of("max-expiration-age"), i64)
             .unwrap_or_else(|e| e.exit()),
         include_corps: matches.is_present("include-corps"),
+        previous_ids: matches.value_of("previous-ids").map(|path| path.to_owned()),
+        pluto: matches.value_of("pluto").map(|path| path.to_owned()),
+        violations: matches.value_of("violations").map(|path| path.to_owned()),
+        complaints: matches.value_of("complaints").map(|path| path.to_owned()),
+        complaints_since: since_arg(matches, "complaints-since"),
+        evictions: matches.value_of("evictions").map(|path| path.to_owned()),
+        evictions_since: since_arg(matches, "evictions-since"),
+        rent_stabilization: matches
+            .value_of("rent-stabilization")
+            .map(|path| path.to_owned()),
+        acris: matches.value_of("acris").map(|dir| dir.to_owned()),
+        keep_expired: false,
+        count_by: matches.value_of("count-by").unwrap().parse()?,
     };
     if let Some(matches) = matches.subcommand_matches("longpaths") {
         let min_length = value_t!(matches.value_of("min-length"), u32).unwrap_or_else(|e| e.exit());
-        Program::new(args).unwrap().cmd_longpaths(min_length);
+        let format = matches.value_of("format").unwrap();
+        Program::new(args)?.cmd_longpaths(min_length, format)?;
     } else if let Some(matches) = matches.subcommand_matches("info") {
-        let name = matches.value_of("NAME");
+        let query = PortfolioQuery::from_matches(matches);
         let top = value_t!(matches.value_of("top"), usize).unwrap_or_else(|e| e.exit());
-        Program::new(args).unwrap().cmd_info(name, top);
+        let format = matches.value_of("format").unwrap();
+        Program::new(args)?.cmd_info(query, top, format)?;
     } else if let Some(matches) = matches.subcommand_matches("dot") {
-        let name = matches.value_of("NAME").unwrap().to_owned();
-        Program::new(args).unwrap().cmd_dot(&name);
+        let query = PortfolioQuery::from_matches(matches).unwrap();
+        Program::new(args)?.cmd_dot(&query)?;
     } else if let Some(matches) = matches.subcommand_matches("json") {
-        let name = matches.value_of("NAME").unwrap().to_owned();
-        Program::new(args).unwrap().cmd_json(&name);
+        let query = PortfolioQuery::from_matches(matches).unwrap();
+        Program::new(args)?.cmd_json(&query)?;
+    } else if let Some(matches) = matches.subcommand_matches("buildings") {
+        let query = PortfolioQuery::from_matches(matches).unwrap();
+        let format = matches.value_of("format").unwrap();
+        Program::new(args)?.cmd_buildings(&query, format)?;
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        let query = PortfolioQuery::from_matches(matches);
+        let format = matches.value_of("format").unwrap().parse()?;
+        Program::new(args)?.cmd_export(query, format)?;
+    } else if let Some(matches) = matches.subcommand_matches("export-tables") {
+        let dir = Path::new(matches.value_of("DIR").unwrap());
+        let format = matches.value_of("format").unwrap().parse()?;
+        Program::new(args)?.cmd_export_tables(dir, format)?;
+    } else if let Some(matches) = matches.subcommand_matches("export-sqlite") {
+        let path = Path::new(matches.value_of("FILE").unwrap());
+        Program::new(args)?.cmd_export_sqlite(path)?;
+    } else if matches.subcommand_matches("json-schema").is_some() {
+        println!("{}", json::portfolio_json_schema());
     } else if let Some(matches) = matches.subcommand_matches("ranking") {
         let min_buildings =
             value_t!(matches.value_of("min-buildings"), usize).unwrap_or_else(|e| e.exit());
-        Program::new(args).unwrap().cmd_ranking(min_buildings);
+        let boro = matches.value_of("boro").map(str::parse).transpose()?;
+        let rank_by = matches.value_of("rank-by").unwrap().parse()?;
+        let missing = match rank_by {
+            RankBy::Units if args.pluto.is_none() => Some("Ranking by units requires --pluto"),
+            RankBy::Evictions if args.evictions.is_none() => {
+                Some("Ranking by evictions requires --evictions")
+            }
+            RankBy::StabilizedUnits | RankBy::StabilizedUnitsLost
+                if args.rent_stabilization.is_none() =>
+            {
+                Some("Ranking by stabilized units requires --rent-stabilization")
+            }
+            _ => None,
+        };
+        if let Some(description) = missing {
+            clap::Error::with_description(description, clap::ErrorKind::MissingRequiredArgument)
+                .exit();
+        }
+        let top = value_t!(matches.value_of("top"), usize).unwrap_or_else(|e| e.exit());
+        let format = matches.value_of("format").unwrap();
+        Program::new(args)?.cmd_ranking(rank_by, min_buildings, boro, top, format)?;
+    } else if let Some(matches) = matches.subcommand_matches("diff") {
+        let format = matches.value_of("format").unwrap();
+        let min_buildings =
+            value_t!(matches.value_of("min-buildings"), usize).unwrap_or_else(|e| e.exit());
+        let old = Program::from_dir(&args, Path::new(matches.value_of("OLD").unwrap()))?;
+        let new = Program::from_dir(&args, Path::new(matches.value_of("NEW").unwrap()))?;
+        old.cmd_diff(&new, format, min_buildings);
+    } else if let Some(matches) = matches.subcommand_matches("history") {
+        let name = matches.value_of("NAME").unwrap();
+        let format = matches.value_of("format").unwrap();
+        let every = value_t!(matches.value_of("every"), u32).unwrap_or_else(|e| e.exit());
+        let end = match matches.value_of("end") {
+            Some(_) => value_t!(matches.value_of("end"), NaiveDate).unwrap_or_else(|e| e.exit()),
+            None => chrono::offset::Local::now().date_naive(),
+        };
+        let start = match matches.value_of("start") {
+            Some(_) => value_t!(matches.value_of("start"), NaiveDate).unwrap_or_else(|e| e.exit()),
+            None => end - chrono::Months::new(120),
+        };
+        let dates = history::history_dates(start, end, every);
+        let max_expiration_age = args.max_expiration_age;
+        args.keep_expired = true;
+        Program::new(args)?.cmd_history(name, &dates, max_expiration_age, format)?;
+    } else if matches.subcommand_matches("ids").is_some() {
+        Program::new(args)?.cmd_ids()?;
+    } else if matches.subcommand_matches("shell").is_some() {
+        Program::new(args)?.cmd_shell()?;
+    } else if let Some(matches) = matches.subcommand_matches("serve") {
+        let host = matches.value_of("host").unwrap();
+        let port = value_t!(matches.value_of("port"), u16).unwrap_or_else(|e| e.exit());
+        Program::new(args)?.cmd_serve(&format!("{}:{}", host, port))?;
     } else if let Some(matches) = matches.subcommand_matches("website") {
         let min_buildings =
             value_t!(matches.value_of("min-buildings"), usize).unwrap_or_else(|e| e.exit());
-        Program::new(args)
-            .unwrap()
-            .cmd_website(min_buildings)
-            .unwrap();
+        let boro = matches.value_of("boro").map(str::parse).transpose()?;
+        Program::new(args)?.cmd_website(min_buildings, boro)?;
     }
+
+    Ok(())
 }
@@ -0,0 +1,160 @@
+//! Attributes of tax lots from the Department of City Planning's Primary
+//! Land Use Tax Lot Output (PLUTO).
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::bbl::BBL;
+use super::csv_batches::parse_records;
+use super::error::HpdError;
+
+#[derive(Deserialize)]
+struct RawPlutoLot<'a> {
+    #[serde(alias = "BBL")]
+    bbl: &'a str,
+
+    #[serde(alias = "UnitsRes", alias = "unitsres", default)]
+    residential_units: Option<u32>,
+
+    #[serde(alias = "YearBuilt", alias = "yearbuilt", default)]
+    year_built: Option<u16>,
+
+    #[serde(alias = "BldgClass", alias = "bldgclass", default)]
+    building_class: Option<&'a str>,
+
+    #[serde(alias = "CondoNo", alias = "condono", default)]
+    condo_number: Option<u32>,
+}
+
+/// What PLUTO says about a tax lot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct PlutoLot {
+    /// The number of residential units on the lot.
+    pub residential_units: u32,
+    /// The year the lot's largest building was built, if known.
+    pub year_built: Option<u16>,
+    /// The building class of the lot's largest building, e.g. "D1".
+    pub building_class: Option<String>,
+    /// The number of the condo the lot belongs to, which is unique within
+    /// its borough, if it's a condo lot.
+    pub condo_number: Option<u32>,
+}
+
+/// Parses a BBL as written in PLUTO, which some exports give as a decimal
+/// number, e.g. "1000010010.00000000".
+fn parse_bbl(value: &str) -> Result<BBL, HpdError> {
+    let digits = match value.split_once('.') {
+        Some((digits, zeros)) if zeros.bytes().all(|b| b == b'0') => digits,
+        _ => value,
+    };
+    digits
+        .parse()
+        .map_err(|_| HpdError::InvalidBBL(value.to_owned()))
+}
+
+/// Parses a lot, whose BBL is `None` if it's invalid.
+fn parse_lot(
+    raw_record: &csv::StringRecord,
+    headers: &csv::StringRecord,
+) -> Result<(Option<BBL>, PlutoLot), HpdError> {
+    let r: RawPlutoLot = raw_record.deserialize(Some(headers))?;
+    let lot = PlutoLot {
+        residential_units: r.residential_units.unwrap_or(0),
+        // PLUTO uses 0 for unknown years.
+        year_built: r.year_built.filter(|year| *year != 0),
+        building_class: r
+            .building_class
+            .filter(|class| !class.is_empty())
+            .map(|class| class.to_owned()),
+        // PLUTO uses 0 for lots that aren't condos.
+        condo_number: r.condo_number.filter(|number| *number != 0),
+    };
+    Ok((parse_bbl(r.bbl).ok(), lot))
+}
+
+/// The PLUTO records of every tax lot, by BBL.
+pub struct PlutoMap {
+    lots: HashMap<BBL, PlutoLot>,
+    /// The number of lots skipped because their BBLs were invalid.
+    invalid_bbls: usize,
+}
+
+impl PlutoMap {
+    /// Reads PLUTO's records, skipping lots whose BBLs are invalid.
+    pub fn from_csv<T: std::io::Read>(rdr: csv::Reader<T>) -> Result<Self, HpdError> {
+        let mut lots = HashMap::new();
+        let mut invalid_bbls = 0;
+        parse_records(rdr, parse_lot, |(bbl, lot)| {
+            match bbl {
+                Some(bbl) => {
+                    lots.insert(bbl, lot);
+                }
+                None => invalid_bbls += 1,
+            }
+            Ok(())
+        })?;
+        eprintln!(
+            "Loaded {} tax lots from PLUTO (skipped {} invalid BBLs).",
+            lots.len(),
+            invalid_bbls
+        );
+        Ok(PlutoMap { lots, invalid_bbls })
+    }
+
+    /// The number of lots skipped because their BBLs were invalid.
+    pub fn invalid_bbls(&self) -> usize {
+        self.invalid_bbls
+    }
+
+    pub fn get(&self, bbl: BBL) -> Option<&PlutoLot> {
+        self.lots.get(&bbl)
+    }
+}
+
+#[test]
+fn test_from_csv_works() {
+    let pluto = PlutoMap::from_csv(csv::Reader::from_reader(
+        "BBL,UnitsRes,YearBuilt,BldgClass,CondoNo\n\
+         1000010010,12,1920,D1,0\n\
+         3012340010.00000000,,0,,\n\
+         1000017501,40,2005,R4,12\n"
+            .as_bytes(),
+    ))
+    .unwrap();
+    assert_eq!(
+        pluto.get("1000010010".parse().unwrap()),
+        Some(&PlutoLot {
+            residential_units: 12,
+            year_built: Some(1920),
+            building_class: Some("D1".to_owned()),
+            condo_number: None,
+        })
+    );
+    assert_eq!(
+        pluto.get("3012340010".parse().unwrap()),
+        Some(&PlutoLot {
+            residential_units: 0,
+            year_built: None,
+            building_class: None,
+            condo_number: None,
+        })
+    );
+    assert_eq!(
+        pluto
+            .get("1000017501".parse().unwrap())
+            .and_then(|lot| lot.condo_number),
+        Some(12)
+    );
+}
+
+#[test]
+fn test_from_csv_skips_invalid_bbls() {
+    let pluto = PlutoMap::from_csv(csv::Reader::from_reader(
+        "bbl,unitsres\n1000010010.5,1\n1000010011,2\n".as_bytes(),
+    ))
+    .unwrap();
+    assert_eq!(pluto.invalid_bbls(), 1);
+    assert_eq!(pluto.get("1000010010".parse().unwrap()), None);
+    assert!(pluto.get("1000010011".parse().unwrap()).is_some());
+}
@@ -1,31 +1,55 @@
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::visit::{Dfs, EdgeRef, VisitMap};
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
-use std::ops::Deref;
-use std::rc::Rc;
-
-use super::hpd_graph::{HpdPetGraph, Node, RegInfo};
-use super::hpd_registrations::HpdRegistrationMap;
-use super::json::portfolio_json;
-use super::ranking::rank_tuples;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::{Deref, RangeInclusive};
+use std::sync::{Arc, OnceLock};
+
+use super::bbl::{Boro, BBL};
+use super::conditions::{ratio, PortfolioConditions};
+use super::dataset::DatasetOptions;
+use super::evictions::PortfolioEvictions;
+use super::export::{ExportFormat, GraphExport};
+use super::hpd_graph::{Edge, HpdGraph, HpdPetGraph, LinkKind, Node, RegInfo};
+use super::hpd_registrations::{BuildingKey, CountBy, HpdRegistration, HpdRegistrationMap};
+use super::interner::Interner;
+use super::json::{portfolio_json, JsonBuilding, PortfolioJson};
+use super::portfolio_id::{derive_id, PortfolioIdSnapshot, DEFAULT_MIN_OVERLAP};
+use super::ranking::{rank_tuples, rank_tuples_by};
+use super::stabilization::PortfolioStabilizedUnits;
 
 pub struct Portfolio {
-    graph: Rc<HpdPetGraph>,
-    nodes: HashSet<NodeIndex<u32>>,
-    cached_name: RefCell<Option<Rc<String>>>,
+    graph: Arc<HpdPetGraph>,
+    strings: Arc<Interner>,
+    nodes: BTreeSet<NodeIndex<u32>>,
+    id: Arc<String>,
+    cached_name: OnceLock<Arc<String>>,
 }
 
 impl Portfolio {
-    fn new(nodes: HashSet<NodeIndex<u32>>, graph: Rc<HpdPetGraph>) -> Self {
+    fn new(nodes: BTreeSet<NodeIndex<u32>>, hpd: &HpdGraph) -> Self {
+        let id = derive_id(
+            nodes.iter().map(|idx| hpd.graph.node_weight(*idx).unwrap()),
+            &hpd.strings,
+        );
         Portfolio {
-            graph,
+            graph: Arc::clone(&hpd.graph),
+            strings: Arc::clone(&hpd.strings),
             nodes,
-            cached_name: RefCell::new(None),
+            id: Arc::new(id),
+            cached_name: OnceLock::new(),
         }
     }
 
+    /// A stable identifier for the portfolio. Unlike its name, this won't
+    /// change between snapshots of the data unless the portfolio changes
+    /// substantially (see `PortfolioIdSnapshot::carry_forward`).
+    pub fn id(&self) -> Arc<String> {
+        Arc::clone(&self.id)
+    }
+
     fn iter_nodes_with_weights(&self) -> impl Iterator<Item = (&NodeIndex, &Node)> {
         self.nodes
             .iter()
@@ -35,18 +59,20 @@ impl Portfolio {
     fn get_hpd_reg_contact_count(&self, node: &NodeIndex<u32>) -> usize {
         let mut total = 0;
         for edge in self.graph.edges(*node) {
-            let reg_info = edge.weight();
-            total += reg_info.len();
+            total += edge.weight().regs.len();
         }
         total
     }
 
-    pub fn rank_bizaddrs(&self) -> Vec<(Rc<String>, usize)> {
+    pub fn rank_bizaddrs(&self) -> Vec<(&str, usize)> {
         let mut result = vec![];
 
         for (node, weight) in self.iter_nodes_with_weights() {
-            if let Node::BizAddr(name) = weight {
-                result.push((Rc::clone(name), self.get_hpd_reg_contact_count(node)));
+            if let Node::BizAddr(addr) = weight {
+                result.push((
+                    self.strings.resolve(*addr),
+                    self.get_hpd_reg_contact_count(node),
+                ));
             }
         }
 
@@ -54,12 +80,15 @@ impl Portfolio {
         result
     }
 
-    pub fn rank_names(&self) -> Vec<(Rc<String>, usize)> {
+    pub fn rank_names(&self) -> Vec<(&str, usize)> {
         let mut result = vec![];
 
         for (node, weight) in self.iter_nodes_with_weights() {
             if let Node::Name(name) = weight {
-                result.push((Rc::clone(name), self.get_hpd_reg_contact_count(node)));
+                result.push((
+                    self.strings.resolve(*name),
+                    self.get_hpd_reg_contact_count(node),
+                ));
             }
         }
 
@@ -67,21 +96,49 @@ impl Portfolio {
         result
     }
 
-    pub fn name(&self) -> Rc<String> {
-        if self.cached_name.borrow().is_none() {
-            let mut option = self.cached_name.borrow_mut();
-            let name = format!(
-                "{}'s portfolio",
-                self.get_best_name().unwrap_or("???".to_owned())
-            );
-            option.replace(Rc::new(name));
+    pub fn names(&self) -> BTreeSet<&str> {
+        let mut result = BTreeSet::new();
+
+        for (_, weight) in self.iter_nodes_with_weights() {
+            if let Node::Name(name) = weight {
+                result.insert(self.strings.resolve(*name));
+            }
         }
 
-        let option = self.cached_name.borrow();
-        Rc::clone(option.as_ref().unwrap())
+        result
     }
 
-    fn get_best_name(&self) -> Option<String> {
+    pub fn bizaddrs(&self) -> BTreeSet<&str> {
+        let mut result = BTreeSet::new();
+
+        for (_, weight) in self.iter_nodes_with_weights() {
+            if let Node::BizAddr(addr) = weight {
+                result.insert(self.strings.resolve(*addr));
+            }
+        }
+
+        result
+    }
+
+    pub fn name(&self) -> Arc<String> {
+        let name = self.cached_name.get_or_init(|| {
+            Arc::new(format!(
+                "{}'s portfolio",
+                self.get_best_name().unwrap_or("???")
+            ))
+        });
+        Arc::clone(name)
+    }
+
+    /// Orders portfolios by name, falling back to their IDs (which are
+    /// unique) if their names are the same.
+    pub fn cmp_by_name(&self, other: &Portfolio) -> Ordering {
+        self.name()
+            .cmp(&other.name())
+            .then_with(|| self.id.cmp(&other.id))
+    }
+
+    fn get_best_name(&self) -> Option<&str> {
         let mut best: Option<(NodeIndex<u32>, usize)> = None;
         for (node, weight) in self.iter_nodes_with_weights() {
             if let Node::Name(_) = weight {
@@ -98,65 +155,297 @@ impl Portfolio {
         }
         if let Some((node_idx, _)) = best {
             let node = self.graph.node_weight(node_idx).unwrap();
-            Some(node.to_str().to_owned())
+            Some(self.strings.resolve(node.symbol()))
         } else {
             None
         }
     }
 
-    pub fn building_count(&self, regs: &HpdRegistrationMap) -> usize {
-        let mut bins = HashSet::<u32>::new();
+    fn iter_reg_infos(&self) -> impl Iterator<Item = &RegInfo> {
+        // Every edge connects a name to an address, so visiting the edges
+        // of all the names visits each edge exactly once.
+        self.iter_nodes_with_weights()
+            .filter(|(_, weight)| matches!(weight, Node::Name(_)))
+            .flat_map(move |(node, _)| self.graph.edges(*node))
+            .flat_map(|edge| edge.weight().regs.iter())
+    }
+
+    fn iter_regs<'a>(
+        &'a self,
+        regs: &'a HpdRegistrationMap,
+    ) -> impl Iterator<Item = &'a HpdRegistration> {
+        self.iter_reg_infos()
+            .filter_map(move |reg_info| regs.get_by_id(reg_info.id))
+            .flatten()
+    }
+
+    /// Counts the portfolio's buildings, deciding which registrations are
+    /// for the same building as described by `count_by`.
+    pub fn building_count(&self, regs: &HpdRegistrationMap, count_by: CountBy) -> usize {
+        let mut buildings = HashSet::new();
+        for reg in self.iter_regs(regs) {
+            buildings.insert(count_by.building_key(reg, regs));
+        }
+        buildings.len()
+    }
+
+    /// Counts the portfolio's buildings by the given key, leaving out any
+    /// for which it returns `None`.
+    fn building_count_by<K: Ord, F: Fn(&HpdRegistration) -> Option<K>>(
+        &self,
+        regs: &HpdRegistrationMap,
+        count_by: CountBy,
+        key: F,
+    ) -> BTreeMap<K, usize> {
+        let mut buildings = BTreeMap::<K, HashSet<BuildingKey>>::new();
+        for reg in self.iter_regs(regs) {
+            if let Some(key) = key(reg) {
+                buildings
+                    .entry(key)
+                    .or_default()
+                    .insert(count_by.building_key(reg, regs));
+            }
+        }
+        buildings
+            .into_iter()
+            .map(|(key, buildings)| (key, buildings.len()))
+            .collect()
+    }
+
+    pub fn building_count_by_boro(
+        &self,
+        regs: &HpdRegistrationMap,
+        count_by: CountBy,
+    ) -> BTreeMap<Boro, usize> {
+        self.building_count_by(regs, count_by, |reg| Some(reg.bbl.boro()))
+    }
+
+    /// Counts the portfolio's buildings in each community district (see
+    /// `HpdRegistration::community_district`).
+    pub fn building_count_by_community_district(
+        &self,
+        regs: &HpdRegistrationMap,
+        count_by: CountBy,
+    ) -> BTreeMap<u16, usize> {
+        self.building_count_by(regs, count_by, |reg| reg.community_district())
+    }
+
+    /// Returns the lowest and highest block the portfolio has buildings on
+    /// in each borough.
+    pub fn block_ranges(&self, regs: &HpdRegistrationMap) -> BTreeMap<Boro, RangeInclusive<u32>> {
+        let mut ranges = BTreeMap::<Boro, RangeInclusive<u32>>::new();
+        for bbl in self.bbls(regs) {
+            let block = bbl.block();
+            ranges
+                .entry(bbl.boro())
+                .and_modify(|range| *range = *range.start().min(&block)..=*range.end().max(&block))
+                .or_insert(block..=block);
+        }
+        ranges
+    }
+
+    /// Whether all of the portfolio's buildings are in the given borough.
+    pub fn is_only_in(&self, boro: Boro, regs: &HpdRegistrationMap) -> bool {
+        self.iter_regs(regs).all(|reg| reg.bbl.boro() == boro)
+    }
+
+    pub fn bbls(&self, regs: &HpdRegistrationMap) -> BTreeSet<BBL> {
+        self.iter_regs(regs).map(|reg| reg.bbl).collect()
+    }
+
+    /// The total number of residential units on the portfolio's tax lots,
+    /// according to PLUTO, or `None` if PLUTO hasn't been joined to the
+    /// registrations.
+    pub fn residential_units(&self, regs: &HpdRegistrationMap) -> Option<u32> {
+        if !regs.has_pluto() {
+            return None;
+        }
+        let units = self
+            .bbls(regs)
+            .into_iter()
+            .filter_map(|bbl| regs.pluto(bbl))
+            .map(|lot| lot.residential_units)
+            .sum();
+        Some(units)
+    }
+
+    /// Measures the portfolio's housing conditions, if HPD violations or
+    /// complaints have been joined to the registrations.
+    pub fn conditions(
+        &self,
+        regs: &HpdRegistrationMap,
+        count_by: CountBy,
+    ) -> Option<PortfolioConditions> {
+        if regs.violations().is_none() && regs.complaints().is_none() {
+            return None;
+        }
+        let bbls = self.bbls(regs);
+        let violations = regs
+            .violations()
+            .map(|violations| bbls.iter().map(|bbl| violations.open_class_c(*bbl)).sum());
+        let complaints = regs
+            .complaints()
+            .map(|complaints| bbls.iter().map(|bbl| complaints.complaints(*bbl)).sum());
+        let units = self.residential_units(regs).map(|units| units as usize);
+        Some(PortfolioConditions {
+            open_class_c_violations: violations,
+            open_class_c_violations_per_unit: ratio(violations, units),
+            recent_complaints: complaints,
+            recent_complaints_per_building: ratio(
+                complaints,
+                Some(self.building_count(regs, count_by)),
+            ),
+            complaints_since: regs
+                .complaints()
+                .map(|complaints| complaints.since().format("%Y-%m-%d").to_string()),
+        })
+    }
+
+    /// Counts the evictions in the portfolio's buildings, if marshal
+    /// evictions have been joined to the registrations.
+    pub fn evictions(
+        &self,
+        regs: &HpdRegistrationMap,
+        count_by: CountBy,
+    ) -> Option<PortfolioEvictions> {
+        let counts = regs.evictions()?;
+        let bins: BTreeSet<u32> = self.iter_regs(regs).filter_map(|reg| reg.bin).collect();
+        let evictions = self
+            .bbls(regs)
+            .into_iter()
+            .map(|bbl| counts.on_lot(bbl))
+            .chain(
+                bins.into_iter()
+                    .map(|bin| counts.in_building_without_lot(bin)),
+            )
+            .sum();
+        let units = self.residential_units(regs).map(|units| units as usize);
+        Some(PortfolioEvictions {
+            evictions,
+            evictions_per_unit: ratio(Some(evictions), units),
+            evictions_per_building: ratio(
+                Some(evictions),
+                Some(self.building_count(regs, count_by)),
+            ),
+            since: counts.since().format("%Y-%m-%d").to_string(),
+        })
+    }
+
+    /// Totals the rent-stabilized units on the portfolio's tax lots, if
+    /// stabilized unit counts have been joined to the registrations.
+    pub fn stabilized_units(&self, regs: &HpdRegistrationMap) -> Option<PortfolioStabilizedUnits> {
+        let counts = regs.stabilized_units()?;
+        Some(PortfolioStabilizedUnits::new(counts, self.bbls(regs)))
+    }
+
+    /// Lists every building in the portfolio, along with the names and
+    /// business addresses that link it to the portfolio.
+    pub fn buildings<'a>(&'a self, regs: &'a HpdRegistrationMap) -> Vec<JsonBuilding<'a>> {
+        let mut links = BTreeMap::<u32, (BTreeSet<&str>, BTreeSet<&str>)>::new();
         for (node, weight) in self.iter_nodes_with_weights() {
-            if let Node::Name(_) = weight {
+            if let Node::Name(name) = weight {
                 for edge in self.graph.edges(*node) {
-                    for reg_info in edge.weight() {
-                        for reg in regs.get_by_id(reg_info.id).unwrap() {
-                            bins.insert(reg.reg_id);
-                        }
+                    let addr = self.graph.node_weight(edge.target()).unwrap();
+                    for reg_info in &edge.weight().regs {
+                        let (names, addrs) = links.entry(reg_info.id).or_default();
+                        names.insert(self.strings.resolve(*name));
+                        addrs.insert(self.strings.resolve(addr.symbol()));
                     }
                 }
             }
         }
-        bins.len()
+
+        let mut buildings: Vec<JsonBuilding> = links
+            .into_iter()
+            .filter_map(|(id, links)| Some((regs.get_by_id(id)?, links)))
+            .flat_map(|(regs_with_id, (names, addrs))| {
+                regs_with_id.iter().map(move |reg| JsonBuilding {
+                    bbl: reg.bbl,
+                    bin: reg.bin,
+                    registration_id: reg.reg_id,
+                    registration_end_date: reg.reg_end_date.format("%Y-%m-%d").to_string(),
+                    names: names.iter().copied().collect(),
+                    addresses: addrs.iter().copied().collect(),
+                    pluto: regs.pluto(reg.bbl),
+                })
+            })
+            .collect();
+        buildings.sort_by_key(|building| (building.bbl, building.bin, building.registration_id));
+        buildings
     }
 
-    pub fn json(&self, regs: &HpdRegistrationMap) -> String {
+    /// Returns the portfolio in the format described by
+    /// `json::portfolio_json_schema`.
+    pub fn json(
+        &self,
+        regs: &HpdRegistrationMap,
+        count_by: CountBy,
+        options: &DatasetOptions,
+    ) -> String {
         let graph = portfolio_json(
-            self.name().to_string(),
-            &self.nodes,
+            PortfolioJson {
+                id: self.id().to_string(),
+                title: self.name().to_string(),
+                buildings: self.buildings(regs),
+                residential_units: self.residential_units(regs),
+                conditions: self.conditions(regs, count_by),
+                stabilized_units: self.stabilized_units(regs),
+                nodes: &self.nodes,
+                local_bridges: self.find_local_bridges_hashset(),
+            },
             &self.graph,
-            self.find_local_bridges_hashset(),
+            &self.strings,
             regs,
+            options,
         );
         serde_json::to_string(&graph).unwrap()
     }
 
+    /// Returns the portfolio in a format for graph analysis tools such as
+    /// Gephi or Cytoscape.
+    pub fn export(&self, regs: &HpdRegistrationMap, format: ExportFormat) -> String {
+        GraphExport::new(
+            self.nodes.iter().map(|node| (*node, self.id())),
+            &self.graph,
+            &self.strings,
+            &self.find_local_bridges_hashset(),
+            regs,
+        )
+        .to_format(format)
+    }
+
     pub fn dot_graph(&self) -> String {
         let g = self.graph.deref();
-        let gf = petgraph::visit::NodeFiltered::from_fn(&g, |g| self.nodes.is_visited(&g));
+        let gf = petgraph::visit::NodeFiltered::from_fn(&g, |g| self.nodes.contains(&g));
         let bridges = self.find_local_bridges_hashset();
-        let get_edge_str = |_, edge: petgraph::graph::EdgeReference<Vec<RegInfo>>| {
+        let get_edge_str = |_, edge: petgraph::graph::EdgeReference<Edge>| {
             let is_bridge = bridges.contains(&edge.id());
             let color = if is_bridge { "red" } else { "black" };
-            format!("label=\" {}\" color={}", edge.weight().len(), color)
+            match edge.weight().kind() {
+                LinkKind::Registration => {
+                    format!("label=\" {}\" color={}", edge.weight().regs.len(), color)
+                }
+                LinkKind::Deed => format!("label=\" deed\" color={} style=dashed", color),
+            }
+        };
+        let get_node_str = |_, (_, node): (NodeIndex<u32>, &Node)| match node {
+            Node::BizAddr(addr) => {
+                format!(
+                    "label=\"{}\", color=lightblue2, style=filled, shape=box",
+                    self.strings.resolve(*addr).to_lowercase()
+                )
+            }
+            Node::Name(name) => format!(
+                "label=\"{}\", color=whitesmoke, style=filled",
+                self.strings.resolve(*name).to_lowercase()
+            ),
         };
 
         let d = Dot::with_attr_getters(
             &gf,
             &[Config::EdgeNoLabel, Config::NodeNoLabel],
             &get_edge_str,
-            &|_, (_, node)| match node {
-                Node::BizAddr(addr) => {
-                    format!(
-                        "label=\"{}\", color=lightblue2, style=filled, shape=box",
-                        addr.to_lowercase()
-                    )
-                }
-                Node::Name(name) => format!(
-                    "label=\"{}\", color=whitesmoke, style=filled",
-                    name.to_lowercase()
-                ),
-            },
+            &get_node_str,
         );
 
         format!("// {}\n\n{:?}", self.name(), d)
@@ -188,12 +477,17 @@ impl Portfolio {
 }
 
 pub struct PortfolioMap {
-    portfolios: Vec<Rc<Portfolio>>,
+    portfolios: Vec<Arc<Portfolio>>,
     node_portfolios: HashMap<NodeIndex<u32>, usize>,
 }
 
 impl PortfolioMap {
-    pub fn from_graph(graph: Rc<HpdPetGraph>) -> Self {
+    pub fn from_graph(
+        hpd: &HpdGraph,
+        regs: &HpdRegistrationMap,
+        previous_ids: Option<&PortfolioIdSnapshot>,
+    ) -> Self {
+        let graph = &hpd.graph;
         let mut visited = HashSet::with_capacity(graph.node_count());
         let mut portfolios = vec![];
         let mut node_portfolios = HashMap::new();
@@ -204,7 +498,7 @@ impl PortfolioMap {
                 continue;
             }
             visited.visit(start);
-            let mut nodes = HashSet::new();
+            let mut nodes = BTreeSet::new();
             let mut dfs = Dfs::new(&graph.deref(), start);
 
             while let Some(node) = dfs.next(&graph.deref()) {
@@ -213,38 +507,97 @@ impl PortfolioMap {
                 node_portfolios.insert(node, portfolio_idx);
             }
 
-            portfolios.push(Rc::new(Portfolio::new(nodes, Rc::clone(&graph))));
+            portfolios.push(Portfolio::new(nodes, hpd));
 
             portfolio_idx += 1;
         }
 
+        if let Some(previous_ids) = previous_ids {
+            let snapshot: Vec<_> = portfolios
+                .par_iter()
+                .map(|portfolio| (portfolio.id(), portfolio.bbls(regs)))
+                .collect();
+            let ids = previous_ids.carry_forward(&snapshot, DEFAULT_MIN_OVERLAP);
+            for (portfolio, id) in portfolios.iter_mut().zip(ids) {
+                portfolio.id = id;
+            }
+        }
+
         PortfolioMap {
-            portfolios,
+            portfolios: portfolios.into_iter().map(Arc::new).collect(),
             node_portfolios,
         }
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Portfolio>> {
+        self.portfolios.iter()
+    }
+
+    /// Returns the ID and buildings of every portfolio, sorted by ID.
+    pub fn id_snapshot(&self, regs: &HpdRegistrationMap) -> Vec<(Arc<String>, BTreeSet<BBL>)> {
+        let mut result: Vec<_> = self
+            .portfolios
+            .par_iter()
+            .map(|portfolio| (portfolio.id(), portfolio.bbls(regs)))
+            .collect();
+        result.sort();
+        result
+    }
+
     pub fn rank_by_building_count(
         &self,
         regs: &HpdRegistrationMap,
+        count_by: CountBy,
         min_buildings: usize,
-    ) -> Vec<(Rc<Portfolio>, usize)> {
-        let mut ranking = vec![];
-
-        for portfolio in &self.portfolios {
-            let size = portfolio.building_count(&regs);
-            if size >= min_buildings {
-                ranking.push((Rc::clone(&portfolio), size));
-            }
-        }
+    ) -> Vec<(Arc<Portfolio>, usize)> {
+        let mut ranking: Vec<_> = self
+            .portfolios
+            .par_iter()
+            .filter_map(|portfolio| {
+                let size = portfolio.building_count(regs, count_by);
+                if size >= min_buildings {
+                    Some((Arc::clone(portfolio), size))
+                } else {
+                    None
+                }
+            })
+            .collect();
 
-        rank_tuples(&mut ranking);
+        rank_tuples_by(&mut ranking, |a, b| a.cmp_by_name(b));
         ranking
     }
 
-    pub fn for_node(&self, node: NodeIndex<u32>) -> Option<Rc<Portfolio>> {
+    /// Returns the local bridges of every portfolio.
+    pub(crate) fn local_bridges(&self) -> HashSet<EdgeIndex<u32>> {
+        self.portfolios
+            .par_iter()
+            .flat_map_iter(|portfolio| portfolio.find_local_bridges_hashset())
+            .collect()
+    }
+
+    /// Returns the whole graph in a format for graph analysis tools such as
+    /// Gephi or Cytoscape, with each node labeled by its portfolio's ID.
+    pub fn export(
+        &self,
+        hpd: &HpdGraph,
+        regs: &HpdRegistrationMap,
+        format: ExportFormat,
+    ) -> String {
+        GraphExport::new(
+            hpd.graph
+                .node_indices()
+                .map(|node| (node, self.portfolios[self.node_portfolios[&node]].id())),
+            &hpd.graph,
+            &hpd.strings,
+            &self.local_bridges(),
+            regs,
+        )
+        .to_format(format)
+    }
+
+    pub fn for_node(&self, node: NodeIndex<u32>) -> Option<Arc<Portfolio>> {
         if let Some(idx) = self.node_portfolios.get(&node) {
-            Some(Rc::clone(&self.portfolios[*idx]))
+            Some(Arc::clone(&self.portfolios[*idx]))
         } else {
             None
         }
@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+use super::bbl::BBL;
+use super::error::HpdError;
+use super::hpd_graph::Node;
+use super::interner::Interner;
+
+/// The minimum overlap (Jaccard similarity of building sets) a portfolio
+/// must have with a portfolio from a previous snapshot to inherit its ID.
+pub const DEFAULT_MIN_OVERLAP: f64 = 0.5;
+
+// https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+//
+// We roll our own rather than using std's hasher because the latter makes
+// no promises about its output staying the same across Rust releases.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Derives a portfolio ID from its canonical member set, i.e. the names and
+/// business addresses it contains. Since no node can belong to more than
+/// one portfolio, this is unique within a snapshot, and it doesn't depend
+/// on the order in which the graph was built.
+pub fn derive_id<'a, I: IntoIterator<Item = &'a Node>>(nodes: I, strings: &Interner) -> String {
+    let mut members: Vec<String> = nodes
+        .into_iter()
+        .map(|node| match node {
+            Node::Name(name) => format!("N:{}", strings.resolve(*name)),
+            Node::BizAddr(addr) => format!("A:{}", strings.resolve(*addr)),
+        })
+        .collect();
+    members.sort();
+    format!("{:016x}", fnv1a_64(members.join("\n").as_bytes()))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PortfolioIdRow<'a> {
+    portfolio_id: &'a str,
+    bbl: &'a str,
+}
+
+/// The portfolio IDs of a previous snapshot of the data, along with the
+/// buildings each one contained, as written by the `ids` subcommand.
+pub struct PortfolioIdSnapshot {
+    bbls_by_id: HashMap<Arc<String>, HashSet<String>>,
+}
+
+impl PortfolioIdSnapshot {
+    pub fn from_csv<T: std::io::Read>(mut rdr: csv::Reader<T>) -> Result<Self, HpdError> {
+        let mut bbls_by_id = HashMap::<Arc<String>, HashSet<String>>::new();
+        let mut raw_record = csv::StringRecord::new();
+        let headers = rdr.headers()?.clone();
+
+        while rdr.read_record(&mut raw_record)? {
+            let row: PortfolioIdRow = raw_record.deserialize(Some(&headers))?;
+            bbls_by_id
+                .entry(Arc::new(row.portfolio_id.to_owned()))
+                .or_default()
+                .insert(row.bbl.to_owned());
+        }
+
+        Ok(PortfolioIdSnapshot { bbls_by_id })
+    }
+
+    pub fn from_portfolios(portfolios: &[(Arc<String>, BTreeSet<BBL>)]) -> Self {
+        let bbls_by_id = portfolios
+            .iter()
+            .map(|(id, bbls)| {
+                let bbls = bbls.iter().map(|bbl| bbl.to_string()).collect();
+                (Arc::clone(id), bbls)
+            })
+            .collect();
+
+        PortfolioIdSnapshot { bbls_by_id }
+    }
+
+    /// Writes the given portfolio IDs and their buildings as CSV, in the
+    /// format expected by `from_csv`.
+    pub fn write_csv<T: std::io::Write>(
+        wtr: &mut csv::Writer<T>,
+        portfolios: &[(Arc<String>, BTreeSet<BBL>)],
+    ) -> Result<(), HpdError> {
+        for (id, bbls) in portfolios {
+            for bbl in bbls {
+                wtr.serialize(PortfolioIdRow {
+                    portfolio_id: id,
+                    bbl: &bbl.to_string(),
+                })?;
+            }
+        }
+        wtr.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+
+    /// Given the derived IDs and buildings of the portfolios in a new
+    /// snapshot, returns the ID each of them should be known by.
+    ///
+    /// A portfolio whose derived ID already existed in this snapshot keeps
+    /// it. Otherwise, portfolios are paired with previous ones in order of
+    /// decreasing building overlap, and inherit the previous ID if the
+    /// overlap is at least `min_overlap`. Each previous ID is carried
+    /// forward at most once, so when a portfolio splits, only the piece
+    /// most similar to the original keeps its ID.
+    pub fn carry_forward(
+        &self,
+        portfolios: &[(Arc<String>, BTreeSet<BBL>)],
+        min_overlap: f64,
+    ) -> Vec<Arc<String>> {
+        let mut result: Vec<Option<Arc<String>>> = vec![None; portfolios.len()];
+        let mut used = HashSet::<Arc<String>>::new();
+
+        for (i, (derived_id, _)) in portfolios.iter().enumerate() {
+            if let Some((id, _)) = self.bbls_by_id.get_key_value(derived_id) {
+                result[i] = Some(Arc::clone(id));
+                used.insert(Arc::clone(id));
+            }
+        }
+
+        let mut ids_by_bbl = HashMap::<&str, Vec<&Arc<String>>>::new();
+        for (id, bbls) in self.bbls_by_id.iter() {
+            if used.contains(id) {
+                continue;
+            }
+            for bbl in bbls {
+                ids_by_bbl.entry(bbl).or_default().push(id);
+            }
+        }
+
+        let mut candidates: Vec<(f64, &Arc<String>, usize)> = vec![];
+        for (i, (_, bbls)) in portfolios.iter().enumerate() {
+            if result[i].is_some() {
+                continue;
+            }
+            let mut overlaps = HashMap::<&Arc<String>, usize>::new();
+            for bbl in bbls {
+                if let Some(ids) = ids_by_bbl.get(bbl.to_string().as_str()) {
+                    for id in ids {
+                        *overlaps.entry(id).or_default() += 1;
+                    }
+                }
+            }
+            for (id, overlap) in overlaps {
+                let union = bbls.len() + self.bbls_by_id[id].len() - overlap;
+                candidates.push((overlap as f64 / union as f64, id, i));
+            }
+        }
+        candidates.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0)
+                .unwrap()
+                .then_with(|| a.1.cmp(b.1))
+                .then_with(|| a.2.cmp(&b.2))
+        });
+
+        for (overlap, id, i) in candidates {
+            if overlap < min_overlap || result[i].is_some() || used.contains(id) {
+                continue;
+            }
+            result[i] = Some(Arc::clone(id));
+            used.insert(Arc::clone(id));
+        }
+
+        result
+            .into_iter()
+            .zip(portfolios)
+            .map(|(id, (derived_id, _))| id.unwrap_or_else(|| Arc::clone(derived_id)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbls(lots: &[u16]) -> BTreeSet<BBL> {
+        lots.iter()
+            .map(|lot| BBL::from_numbers(1, 1, *lot).unwrap())
+            .collect()
+    }
+
+    fn snapshot(rows: &str) -> PortfolioIdSnapshot {
+        let csv = format!("portfolio_id,bbl\n{}", rows);
+        PortfolioIdSnapshot::from_csv(csv::Reader::from_reader(csv.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_derive_id_is_stable() {
+        let mut strings = Interner::new();
+        let nodes = vec![
+            Node::Name(strings.intern("BOOP JONES")),
+            Node::BizAddr(strings.intern("1 BOOP ST, NEW YORK NY")),
+        ];
+        assert_eq!(derive_id(&nodes, &strings), "ad6d5edaa872f40d".to_owned());
+        assert_eq!(
+            derive_id(nodes.iter().rev(), &strings),
+            derive_id(&nodes, &strings)
+        );
+    }
+
+    #[test]
+    fn test_carry_forward_keeps_ids_of_overlapping_portfolios() {
+        let snap = snapshot("old_a,1000010001\nold_a,1000010002\nold_b,1000010003\n");
+        let ids = snap.carry_forward(
+            &[
+                (Arc::new("new_a".to_owned()), bbls(&[1, 2, 4])),
+                (Arc::new("new_b".to_owned()), bbls(&[5])),
+            ],
+            DEFAULT_MIN_OVERLAP,
+        );
+        assert_eq!(
+            ids,
+            vec![Arc::new("old_a".to_owned()), Arc::new("new_b".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_carry_forward_gives_split_id_to_most_similar_piece() {
+        let snap = snapshot("old,1000010001\nold,1000010002\nold,1000010003\n");
+        let ids = snap.carry_forward(
+            &[
+                (Arc::new("small".to_owned()), bbls(&[1])),
+                (Arc::new("big".to_owned()), bbls(&[2, 3])),
+            ],
+            0.0,
+        );
+        assert_eq!(
+            ids,
+            vec![Arc::new("small".to_owned()), Arc::new("old".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_carry_forward_prefers_identical_membership() {
+        let snap = snapshot("same,1000010001\nother,1000010002\n");
+        let ids = snap.carry_forward(
+            &[
+                (Arc::new("fresh".to_owned()), bbls(&[1])),
+                (Arc::new("same".to_owned()), bbls(&[3])),
+            ],
+            DEFAULT_MIN_OVERLAP,
+        );
+        assert_eq!(
+            ids,
+            vec![Arc::new("fresh".to_owned()), Arc::new("same".to_owned())]
+        );
+    }
+}
@@ -1,4 +1,19 @@
-pub fn rank_tuples<T>(v: &mut Vec<(T, usize)>) {
-    v.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-    v.reverse();
+use std::cmp::Ordering;
+
+/// Sorts the given tuples by their counts in descending order. Ties are
+/// broken by ordering the first element of each tuple via `cmp`, so that
+/// the result doesn't depend on the order the tuples were in.
+pub fn rank_tuples_by<T, F: Fn(&T, &T) -> Ordering>(v: &mut [(T, usize)], cmp: F) {
+    v.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| cmp(&a.0, &b.0)));
+}
+
+pub fn rank_tuples<T: Ord>(v: &mut [(T, usize)]) {
+    rank_tuples_by(v, T::cmp);
+}
+
+#[test]
+fn test_rank_tuples_breaks_ties() {
+    let mut v = vec![("b", 1), ("c", 2), ("a", 1)];
+    rank_tuples(&mut v);
+    assert_eq!(v, vec![("c", 2), ("a", 1), ("b", 1)]);
 }
@@ -0,0 +1,608 @@
+//! Structured versions of what the `info`, `ranking`, `longpaths` and
+//! `buildings` commands print, for output as JSON or CSV.
+
+use petgraph::graph::NodeIndex;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::bbl::{Boro, BBL};
+use super::conditions::PortfolioConditions;
+use super::error::HpdError;
+use super::evictions::PortfolioEvictions;
+use super::hpd_graph::{HpdGraph, LinkKind};
+use super::hpd_registrations::{CountBy, HpdRegistrationMap};
+use super::json::{JsonBuilding, JsonNodeValue};
+use super::portfolio::{Portfolio, PortfolioMap};
+use super::ranking::rank_tuples_by;
+use super::stabilization::PortfolioStabilizedUnits;
+
+/// A name or business address, and the number of registration contacts
+/// that mention it.
+#[derive(Serialize)]
+pub struct Mention {
+    pub value: String,
+    pub reg_contacts: usize,
+}
+
+fn top_mentions(ranked: Vec<(&str, usize)>, top: usize) -> Vec<Mention> {
+    ranked
+        .into_iter()
+        .take(top)
+        .map(|(value, reg_contacts)| Mention {
+            value: value.to_owned(),
+            reg_contacts,
+        })
+        .collect()
+}
+
+/// Joins the mentioned values with semicolons, without their counts, for
+/// CSV output.
+fn join_mentions(mentions: &[Mention]) -> String {
+    mentions
+        .iter()
+        .map(|mention| mention.value.as_str())
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// How many of a portfolio's buildings are in a borough, and the range of
+/// blocks they're on.
+#[derive(Serialize)]
+pub struct BoroSummary {
+    pub boro: Boro,
+    pub buildings: usize,
+    pub min_block: u32,
+    pub max_block: u32,
+}
+
+#[derive(Serialize)]
+pub struct CommunityDistrictCount {
+    pub community_district: u16,
+    pub buildings: usize,
+}
+
+/// Returns the number of buildings the portfolio has in each borough, in
+/// the order of `Boro::ALL`.
+fn building_counts_by_boro(
+    portfolio: &Portfolio,
+    regs: &HpdRegistrationMap,
+    count_by: CountBy,
+) -> [usize; 5] {
+    let counts = portfolio.building_count_by_boro(regs, count_by);
+    Boro::ALL.map(|boro| counts.get(&boro).copied().unwrap_or(0))
+}
+
+#[derive(Serialize)]
+pub struct PortfolioSummary {
+    pub id: Arc<String>,
+    pub name: Arc<String>,
+    pub buildings: usize,
+    /// Only included if PLUTO was loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub residential_units: Option<u32>,
+    /// Only included if HPD violations or complaints were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<PortfolioConditions>,
+    /// Only included if evictions were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evictions: Option<PortfolioEvictions>,
+    /// Only included if rent-stabilized units were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stabilized_units: Option<PortfolioStabilizedUnits>,
+    pub boroughs: Vec<BoroSummary>,
+    pub community_districts: Vec<CommunityDistrictCount>,
+    pub top_addresses: Vec<Mention>,
+    pub top_names: Vec<Mention>,
+    pub local_bridges: usize,
+}
+
+impl PortfolioSummary {
+    /// Summarizes the portfolio, including its `top` most frequently
+    /// mentioned business addresses and names.
+    pub fn new(
+        portfolio: &Portfolio,
+        regs: &HpdRegistrationMap,
+        count_by: CountBy,
+        top: usize,
+    ) -> Self {
+        let block_ranges = portfolio.block_ranges(regs);
+        PortfolioSummary {
+            id: portfolio.id(),
+            name: portfolio.name(),
+            buildings: portfolio.building_count(regs, count_by),
+            residential_units: portfolio.residential_units(regs),
+            conditions: portfolio.conditions(regs, count_by),
+            evictions: portfolio.evictions(regs, count_by),
+            stabilized_units: portfolio.stabilized_units(regs),
+            boroughs: portfolio
+                .building_count_by_boro(regs, count_by)
+                .into_iter()
+                .map(|(boro, buildings)| BoroSummary {
+                    boro,
+                    buildings,
+                    min_block: *block_ranges[&boro].start(),
+                    max_block: *block_ranges[&boro].end(),
+                })
+                .collect(),
+            community_districts: portfolio
+                .building_count_by_community_district(regs, count_by)
+                .into_iter()
+                .map(|(community_district, buildings)| CommunityDistrictCount {
+                    community_district,
+                    buildings,
+                })
+                .collect(),
+            top_addresses: top_mentions(portfolio.rank_bizaddrs(), top),
+            top_names: top_mentions(portfolio.rank_names(), top),
+            local_bridges: portfolio.find_local_bridges().len(),
+        }
+    }
+
+    /// Returns the number of buildings in the given borough.
+    pub fn buildings_in(&self, boro: Boro) -> usize {
+        self.boroughs
+            .iter()
+            .find(|summary| summary.boro == boro)
+            .map_or(0, |summary| summary.buildings)
+    }
+}
+
+#[derive(Serialize)]
+pub struct GraphSummary {
+    pub names: usize,
+    pub addresses: usize,
+    pub connected_components: usize,
+    pub portfolio: Option<PortfolioSummary>,
+}
+
+impl GraphSummary {
+    pub fn new(hpd: &HpdGraph, portfolio: Option<PortfolioSummary>) -> Self {
+        GraphSummary {
+            names: hpd.name_nodes.len(),
+            addresses: hpd.addr_nodes.len(),
+            connected_components: hpd.connected_components(),
+            portfolio,
+        }
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Writes the summary as a single CSV row. Since CSV cells can't
+    /// contain lists, the top addresses and names are separated by
+    /// semicolons, without their counts, and the boroughs are given a
+    /// column each. Community districts and block ranges are left out.
+    pub fn write_csv<T: std::io::Write>(&self, wtr: &mut csv::Writer<T>) -> Result<(), HpdError> {
+        let portfolio = self.portfolio.as_ref();
+        let conditions = portfolio.and_then(|p| p.conditions.as_ref());
+        let evictions = portfolio.and_then(|p| p.evictions.as_ref());
+        let stabilized_units = portfolio.and_then(|p| p.stabilized_units.as_ref());
+        wtr.serialize(GraphSummaryCsvRow {
+            names: self.names,
+            addresses: self.addresses,
+            connected_components: self.connected_components,
+            portfolio_id: portfolio.map(|p| p.id.as_str()),
+            portfolio_name: portfolio.map(|p| p.name.as_str()),
+            buildings: portfolio.map(|p| p.buildings),
+            residential_units: portfolio.and_then(|p| p.residential_units),
+            open_class_c_violations: conditions.map(|c| c.open_class_c_violations),
+            open_class_c_violations_per_unit: conditions
+                .map(|c| c.open_class_c_violations_per_unit),
+            recent_complaints: conditions.map(|c| c.recent_complaints),
+            recent_complaints_per_building: conditions.map(|c| c.recent_complaints_per_building),
+            evictions: evictions.map(|e| e.evictions),
+            evictions_per_unit: evictions.map(|e| e.evictions_per_unit),
+            evictions_per_building: evictions.map(|e| e.evictions_per_building),
+            stabilized_units: stabilized_units.map(|s| s.stabilized_units),
+            stabilized_units_lost: stabilized_units.map(|s| s.stabilized_units_lost),
+            manhattan: portfolio.map(|p| p.buildings_in(Boro::Manhattan)),
+            bronx: portfolio.map(|p| p.buildings_in(Boro::Bronx)),
+            brooklyn: portfolio.map(|p| p.buildings_in(Boro::Brooklyn)),
+            queens: portfolio.map(|p| p.buildings_in(Boro::Queens)),
+            staten_island: portfolio.map(|p| p.buildings_in(Boro::StatenIsland)),
+            local_bridges: portfolio.map(|p| p.local_bridges),
+            top_addresses: portfolio.map(|p| join_mentions(&p.top_addresses)),
+            top_names: portfolio.map(|p| join_mentions(&p.top_names)),
+        })?;
+        wtr.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct GraphSummaryCsvRow<'a> {
+    names: usize,
+    addresses: usize,
+    connected_components: usize,
+    portfolio_id: Option<&'a str>,
+    portfolio_name: Option<&'a str>,
+    buildings: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    residential_units: Option<u32>,
+    // The outer options say whether violations, complaints, evictions or
+    // stabilized units were loaded, so that every row has the same columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_class_c_violations: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_class_c_violations_per_unit: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recent_complaints: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recent_complaints_per_building: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evictions: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evictions_per_unit: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evictions_per_building: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stabilized_units: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stabilized_units_lost: Option<Option<i64>>,
+    manhattan: Option<usize>,
+    bronx: Option<usize>,
+    brooklyn: Option<usize>,
+    queens: Option<usize>,
+    staten_island: Option<usize>,
+    local_bridges: Option<usize>,
+    top_addresses: Option<String>,
+    top_names: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RankingEntry {
+    pub rank: usize,
+    pub id: Arc<String>,
+    pub name: Arc<String>,
+    pub buildings: usize,
+    pub manhattan: usize,
+    pub bronx: usize,
+    pub brooklyn: usize,
+    pub queens: usize,
+    pub staten_island: usize,
+    /// Only included if PLUTO was loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub residential_units: Option<u32>,
+    /// Only included if HPD violations or complaints were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<PortfolioConditions>,
+    /// Only included if evictions were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evictions: Option<PortfolioEvictions>,
+    /// Only included if rent-stabilized units were loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stabilized_units: Option<PortfolioStabilizedUnits>,
+    pub top_addresses: Vec<Mention>,
+    pub top_names: Vec<Mention>,
+    pub local_bridges: usize,
+}
+
+/// What `ranking` ranks portfolios by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankBy {
+    #[default]
+    Buildings,
+    /// The total number of residential units, which requires PLUTO.
+    Units,
+    /// The number of evictions, which requires marshal evictions.
+    Evictions,
+    /// The number of rent-stabilized units, which requires stabilized unit
+    /// counts.
+    StabilizedUnits,
+    /// The number of rent-stabilized units lost since the year before.
+    StabilizedUnitsLost,
+}
+
+impl RankBy {
+    /// The names accepted by `from_str`.
+    pub const NAMES: &'static [&'static str] = &[
+        "buildings",
+        "units",
+        "evictions",
+        "stabilized-units",
+        "stabilized-units-lost",
+    ];
+}
+
+impl FromStr for RankBy {
+    type Err = HpdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buildings" => Ok(RankBy::Buildings),
+            "units" => Ok(RankBy::Units),
+            "evictions" => Ok(RankBy::Evictions),
+            "stabilized-units" => Ok(RankBy::StabilizedUnits),
+            "stabilized-units-lost" => Ok(RankBy::StabilizedUnitsLost),
+            _ => Err(HpdError::Parse(format!(
+                "unknown ranking '{}', expected one of {}",
+                s,
+                RankBy::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+/// Ranks the portfolios with at least `min_buildings` buildings by
+/// `rank_by`, optionally only including those entirely within one borough.
+/// Each entry includes the portfolio's `top` most frequently mentioned
+/// business addresses and names.
+pub fn ranking(
+    portfolios: &PortfolioMap,
+    regs: &HpdRegistrationMap,
+    count_by: CountBy,
+    rank_by: RankBy,
+    min_buildings: usize,
+    boro: Option<Boro>,
+    top: usize,
+) -> Vec<RankingEntry> {
+    let mut ranked: Vec<_> = portfolios
+        .rank_by_building_count(regs, count_by, min_buildings)
+        .into_iter()
+        .filter(|(portfolio, _)| boro.is_none_or(|boro| portfolio.is_only_in(boro, regs)))
+        .map(|(portfolio, buildings)| {
+            let units = portfolio.residential_units(regs);
+            let evictions = portfolio.evictions(regs, count_by);
+            let stabilized_units = portfolio.stabilized_units(regs);
+            let count = match rank_by {
+                RankBy::Buildings => buildings,
+                RankBy::Units => units.unwrap_or(0) as usize,
+                RankBy::Evictions => evictions.as_ref().map_or(0, |e| e.evictions as usize),
+                RankBy::StabilizedUnits => stabilized_units
+                    .as_ref()
+                    .map_or(0, |s| s.stabilized_units as usize),
+                // Portfolios that gained units rank as if they lost none.
+                RankBy::StabilizedUnitsLost => stabilized_units
+                    .as_ref()
+                    .and_then(|s| s.stabilized_units_lost)
+                    .map_or(0, |lost| lost.max(0) as usize),
+            };
+            (
+                (portfolio, buildings, units, evictions, stabilized_units),
+                count,
+            )
+        })
+        .collect();
+    if rank_by != RankBy::Buildings {
+        rank_tuples_by(&mut ranked, |a, b| a.0.cmp_by_name(&b.0));
+    }
+    (1..)
+        .zip(ranked)
+        .map(
+            |(
+                rank,
+                ((portfolio, buildings, residential_units, evictions, stabilized_units), _),
+            )| {
+                let [manhattan, bronx, brooklyn, queens, staten_island] =
+                    building_counts_by_boro(&portfolio, regs, count_by);
+                RankingEntry {
+                    rank,
+                    id: portfolio.id(),
+                    name: portfolio.name(),
+                    buildings,
+                    manhattan,
+                    bronx,
+                    brooklyn,
+                    queens,
+                    staten_island,
+                    residential_units,
+                    conditions: portfolio.conditions(regs, count_by),
+                    evictions,
+                    stabilized_units,
+                    top_addresses: top_mentions(portfolio.rank_bizaddrs(), top),
+                    top_names: top_mentions(portfolio.rank_names(), top),
+                    local_bridges: portfolio.find_local_bridges().len(),
+                }
+            },
+        )
+        .collect()
+}
+
+pub fn ranking_json(entries: &[RankingEntry]) -> String {
+    serde_json::to_string(entries).unwrap()
+}
+
+/// Writes the ranking as CSV, with a column for each measure of the
+/// portfolios' conditions, evictions and stabilized units. The top
+/// addresses and names are separated by semicolons, without their counts.
+pub fn write_ranking_csv<T: std::io::Write>(
+    wtr: &mut csv::Writer<T>,
+    entries: &[RankingEntry],
+) -> Result<(), HpdError> {
+    for entry in entries {
+        let conditions = entry.conditions.as_ref();
+        let evictions = entry.evictions.as_ref();
+        let stabilized_units = entry.stabilized_units.as_ref();
+        wtr.serialize(RankingCsvRow {
+            rank: entry.rank,
+            id: &entry.id,
+            name: &entry.name,
+            buildings: entry.buildings,
+            manhattan: entry.manhattan,
+            bronx: entry.bronx,
+            brooklyn: entry.brooklyn,
+            queens: entry.queens,
+            staten_island: entry.staten_island,
+            residential_units: entry.residential_units,
+            open_class_c_violations: conditions.map(|c| c.open_class_c_violations),
+            open_class_c_violations_per_unit: conditions
+                .map(|c| c.open_class_c_violations_per_unit),
+            recent_complaints: conditions.map(|c| c.recent_complaints),
+            recent_complaints_per_building: conditions.map(|c| c.recent_complaints_per_building),
+            evictions: evictions.map(|e| e.evictions),
+            evictions_per_unit: evictions.map(|e| e.evictions_per_unit),
+            evictions_per_building: evictions.map(|e| e.evictions_per_building),
+            stabilized_units: stabilized_units.map(|s| s.stabilized_units),
+            stabilized_units_lost: stabilized_units.map(|s| s.stabilized_units_lost),
+            local_bridges: entry.local_bridges,
+            top_addresses: join_mentions(&entry.top_addresses),
+            top_names: join_mentions(&entry.top_names),
+        })?;
+    }
+    wtr.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct RankingCsvRow<'a> {
+    rank: usize,
+    id: &'a str,
+    name: &'a str,
+    buildings: usize,
+    manhattan: usize,
+    bronx: usize,
+    brooklyn: usize,
+    queens: usize,
+    staten_island: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    residential_units: Option<u32>,
+    // The outer options say whether violations, complaints, evictions or
+    // stabilized units were loaded, so that every row has the same columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_class_c_violations: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    open_class_c_violations_per_unit: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recent_complaints: Option<Option<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recent_complaints_per_building: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evictions: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evictions_per_unit: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evictions_per_building: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stabilized_units: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stabilized_units_lost: Option<Option<i64>>,
+    local_bridges: usize,
+    top_addresses: String,
+    top_names: String,
+}
+
+#[derive(Serialize)]
+pub struct LongPath<'a> {
+    pub length: u32,
+    pub nodes: Vec<JsonNodeValue<'a>>,
+    /// Whether each link between consecutive nodes is a registration or a
+    /// deed.
+    pub links: Vec<LinkKind>,
+}
+
+impl<'a> LongPath<'a> {
+    /// Describes the given path through the graph.
+    pub fn new(hpd: &'a HpdGraph, path: &[NodeIndex<u32>]) -> Self {
+        LongPath {
+            length: (path.len() - 1) as u32,
+            nodes: path
+                .iter()
+                .map(|node| JsonNodeValue::new(&hpd.graph[*node], &hpd.strings))
+                .collect(),
+            links: path
+                .windows(2)
+                .map(|pair| {
+                    hpd.link_kind(pair[0], pair[1])
+                        .unwrap_or(LinkKind::Registration)
+                })
+                .collect(),
+        }
+    }
+
+    /// Describes the path like `HpdGraph::path_to_string`.
+    pub fn describe(&self) -> String {
+        let mut result = String::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                result.push_str(self.links[i - 1].arrow());
+            }
+            result.push_str(node.label);
+        }
+        result
+    }
+}
+
+/// Returns the paths found by `HpdGraph::long_paths`.
+pub fn long_paths(hpd: &HpdGraph, min_length: u32) -> Vec<LongPath<'_>> {
+    hpd.long_paths(min_length)
+        .into_iter()
+        .map(|(_, path)| LongPath::new(hpd, &path))
+        .collect()
+}
+
+pub fn long_paths_json(paths: &[LongPath]) -> String {
+    serde_json::to_string(paths).unwrap()
+}
+
+/// Writes the paths as CSV, with the nodes and links of each path separated
+/// by semicolons.
+pub fn write_long_paths_csv<T: std::io::Write>(
+    wtr: &mut csv::Writer<T>,
+    paths: &[LongPath],
+) -> Result<(), HpdError> {
+    for path in paths {
+        wtr.serialize(LongPathCsvRow {
+            length: path.length,
+            nodes: path
+                .nodes
+                .iter()
+                .map(|node| node.label)
+                .collect::<Vec<_>>()
+                .join(";"),
+            links: path
+                .links
+                .iter()
+                .map(|link| match link {
+                    LinkKind::Registration => "registration",
+                    LinkKind::Deed => "deed",
+                })
+                .collect::<Vec<_>>()
+                .join(";"),
+        })?;
+    }
+    wtr.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct LongPathCsvRow {
+    length: u32,
+    nodes: String,
+    links: String,
+}
+
+pub fn buildings_json(buildings: &[JsonBuilding]) -> String {
+    serde_json::to_string(buildings).unwrap()
+}
+
+/// Writes the buildings as CSV, with the names and addresses of each
+/// separated by semicolons.
+pub fn write_buildings_csv<T: std::io::Write>(
+    wtr: &mut csv::Writer<T>,
+    buildings: &[JsonBuilding],
+) -> Result<(), HpdError> {
+    for building in buildings {
+        wtr.serialize(BuildingCsvRow {
+            bbl: building.bbl,
+            bin: building.bin,
+            registration_id: building.registration_id,
+            registration_end_date: &building.registration_end_date,
+            names: building.names.join(";"),
+            addresses: building.addresses.join(";"),
+        })?;
+    }
+    wtr.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct BuildingCsvRow<'a> {
+    bbl: BBL,
+    bin: Option<u32>,
+    registration_id: u32,
+    registration_end_date: &'a str,
+    names: String,
+    addresses: String,
+}
@@ -0,0 +1,293 @@
+use petgraph::graph::NodeIndex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::dataset::Dataset;
+use super::error::HpdError;
+use super::hpd_graph::LinkKind;
+use super::hpd_registrations::CountBy;
+use super::json::JsonNodeValue;
+use super::portfolio::Portfolio;
+use super::report::{self, RankBy};
+
+/// The maximum number of results `/search` returns by default.
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// The number of top names and business addresses `/ranking` includes by
+/// default, as in `hpd ranking`.
+const DEFAULT_RANKING_TOP: usize = 5;
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidates: Option<&'a [String]>,
+}
+
+#[derive(Serialize)]
+struct SearchResult<'a> {
+    name: &'a str,
+    portfolio_id: Arc<String>,
+    portfolio_name: Arc<String>,
+}
+
+#[derive(Serialize)]
+struct PathResponse<'a> {
+    from: &'a str,
+    to: &'a str,
+    length: usize,
+    path: Vec<JsonNodeValue<'a>>,
+    /// The kind of each link in the path, so there's one fewer than there
+    /// are nodes.
+    links: Vec<LinkKind>,
+}
+
+/// A status code and JSON body.
+pub type ApiResponse = (u16, String);
+
+fn json_response<T: Serialize>(value: &T) -> ApiResponse {
+    (200, serde_json::to_string(value).unwrap())
+}
+
+fn error_response(status: u16, message: String, candidates: Option<&[String]>) -> ApiResponse {
+    let body = ErrorResponse {
+        error: message,
+        candidates,
+    };
+    (status, serde_json::to_string(&body).unwrap())
+}
+
+fn hpd_error_response(e: HpdError) -> ApiResponse {
+    match &e {
+        HpdError::NotFound { .. } => error_response(404, e.to_string(), None),
+        HpdError::AmbiguousMatch { candidates, .. } => {
+            error_response(400, e.to_string(), Some(candidates))
+        }
+        HpdError::InvalidBBL(_) | HpdError::Parse(_) => error_response(400, e.to_string(), None),
+        _ => error_response(500, e.to_string(), None),
+    }
+}
+
+/// Decodes a component of a URL query string, e.g. "BOOP+JONES".
+fn decode(value: &str) -> String {
+    percent_encoding::percent_decode_str(&value.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (decode(key), decode(value)),
+            None => (decode(pair), String::new()),
+        })
+        .collect()
+}
+
+struct Params(HashMap<String, String>);
+
+impl Params {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|value| value.as_str())
+    }
+
+    fn require(&self, key: &str) -> Result<&str, HpdError> {
+        self.get(key)
+            .ok_or_else(|| HpdError::Parse(format!("missing query parameter '{}'", key)))
+    }
+
+    fn number(&self, key: &str, default: usize) -> Result<usize, HpdError> {
+        match self.get(key) {
+            Some(value) => value.parse().map_err(|_| {
+                HpdError::Parse(format!("query parameter '{}' must be a number", key))
+            }),
+            None => Ok(default),
+        }
+    }
+
+    fn count_by(&self) -> Result<CountBy, HpdError> {
+        Ok(self
+            .get("count_by")
+            .map(str::parse)
+            .transpose()?
+            .unwrap_or_default())
+    }
+}
+
+/// Answers queries about a dataset that's loaded once, over HTTP.
+pub struct ApiServer {
+    dataset: Dataset,
+}
+
+impl ApiServer {
+    pub fn new(dataset: Dataset) -> Self {
+        ApiServer { dataset }
+    }
+
+    fn portfolio_for_node(&self, node: NodeIndex<u32>) -> Result<Arc<Portfolio>, HpdError> {
+        self.dataset
+            .portfolios()
+            .for_node(node)
+            .ok_or_else(|| HpdError::NotFound {
+                what: "portfolio",
+                search: self.dataset.hpd.node_str(node).to_owned(),
+            })
+    }
+
+    fn portfolio(&self, params: &Params) -> Result<ApiResponse, HpdError> {
+        let portfolio = match (params.get("name"), params.get("bbl")) {
+            (Some(name), _) => self.dataset.portfolio_with_name(&name.to_uppercase())?,
+            (None, Some(bbl)) => self.dataset.portfolio_with_bbl(bbl.parse()?)?,
+            (None, None) => {
+                return Err(HpdError::Parse(
+                    "expected query parameter 'name' or 'bbl'".to_owned(),
+                ))
+            }
+        };
+        Ok((
+            200,
+            portfolio.json(
+                &self.dataset.regs,
+                params.count_by()?,
+                self.dataset.options(),
+            ),
+        ))
+    }
+
+    fn search(&self, params: &Params) -> Result<ApiResponse, HpdError> {
+        let query = params.require("q")?.to_uppercase();
+        let limit = params.number("limit", DEFAULT_SEARCH_LIMIT)?;
+        let results = self
+            .dataset
+            .hpd
+            .search_names(&query)
+            .into_iter()
+            .take(limit)
+            .map(|(name, node)| {
+                let portfolio = self.portfolio_for_node(node)?;
+                Ok(SearchResult {
+                    name,
+                    portfolio_id: portfolio.id(),
+                    portfolio_name: portfolio.name(),
+                })
+            })
+            .collect::<Result<Vec<_>, HpdError>>()?;
+        Ok(json_response(&results))
+    }
+
+    fn path(&self, params: &Params) -> Result<ApiResponse, HpdError> {
+        let hpd = &self.dataset.hpd;
+        let from = hpd.find_name(&params.require("from")?.to_uppercase())?;
+        let to = hpd.find_name(&params.require("to")?.to_uppercase())?;
+        let path = hpd
+            .shortest_path(from, to)
+            .ok_or_else(|| HpdError::NotFound {
+                what: "path",
+                search: format!("{} -> {}", hpd.node_str(from), hpd.node_str(to)),
+            })?;
+        Ok(json_response(&PathResponse {
+            from: hpd.node_str(from),
+            to: hpd.node_str(to),
+            length: path.len() - 1,
+            path: path
+                .iter()
+                .map(|node| JsonNodeValue::new(&hpd.graph[*node], &hpd.strings))
+                .collect(),
+            links: path
+                .windows(2)
+                .filter_map(|pair| hpd.link_kind(pair[0], pair[1]))
+                .collect(),
+        }))
+    }
+
+    fn ranking(&self, params: &Params) -> Result<ApiResponse, HpdError> {
+        let min_buildings = params.number("min_buildings", 0)?;
+        let limit = params.number("limit", usize::MAX)?;
+        let top = params.number("top", DEFAULT_RANKING_TOP)?;
+        let boro = params.get("boro").map(str::parse).transpose()?;
+        let count_by = params.count_by()?;
+        let rank_by = params
+            .get("rank_by")
+            .map(str::parse)
+            .transpose()?
+            .unwrap_or_default();
+        if rank_by == RankBy::Units && !self.dataset.regs.has_pluto() {
+            return Err(HpdError::Parse(
+                "ranking by units requires PLUTO, which wasn't loaded".to_owned(),
+            ));
+        }
+        if rank_by == RankBy::Evictions && self.dataset.regs.evictions().is_none() {
+            return Err(HpdError::Parse(
+                "ranking by evictions requires evictions, which weren't loaded".to_owned(),
+            ));
+        }
+        if matches!(
+            rank_by,
+            RankBy::StabilizedUnits | RankBy::StabilizedUnitsLost
+        ) && self.dataset.regs.stabilized_units().is_none()
+        {
+            return Err(HpdError::Parse(
+                "ranking by stabilized units requires them, but they weren't loaded".to_owned(),
+            ));
+        }
+        let ranking: Vec<_> = report::ranking(
+            self.dataset.portfolios(),
+            &self.dataset.regs,
+            count_by,
+            rank_by,
+            min_buildings,
+            boro,
+            top,
+        )
+        .into_iter()
+        .take(limit)
+        .collect();
+        Ok(json_response(&ranking))
+    }
+
+    /// Responds to a GET request for the given URL, e.g.
+    /// "/portfolio?name=BOOP+JONES".
+    pub fn respond(&self, url: &str) -> ApiResponse {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let params = Params(parse_query(query));
+        let result = match path {
+            "/portfolio" => self.portfolio(&params),
+            "/search" => self.search(&params),
+            "/path" => self.path(&params),
+            "/ranking" => self.ranking(&params),
+            _ => return error_response(404, format!("Unknown endpoint '{}'.", path), None),
+        };
+        result.unwrap_or_else(hpd_error_response)
+    }
+
+    /// Responds to requests from the given server until it shuts down.
+    pub fn serve(&self, server: &tiny_http::Server) {
+        let content_type =
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+        for request in server.incoming_requests() {
+            let (status, body) = if *request.method() == tiny_http::Method::Get {
+                self.respond(request.url())
+            } else {
+                error_response(405, "Only GET requests are supported.".to_owned(), None)
+            };
+            let response = tiny_http::Response::from_string(body)
+                .with_status_code(status)
+                .with_header(content_type.clone());
+            if let Err(e) = request.respond(response) {
+                eprintln!("Unable to send response: {}", e);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_query_works() {
+    let params = parse_query("name=BOOP+JONES&bbl=3012340010&q=%C3%A9&empty");
+    assert_eq!(params["name"], "BOOP JONES");
+    assert_eq!(params["bbl"], "3012340010");
+    assert_eq!(params["q"], "é");
+    assert_eq!(params["empty"], "");
+}
@@ -0,0 +1,504 @@
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use hpd::report::PortfolioSummary;
+use hpd::{CountBy, Dataset, HpdError, Portfolio};
+
+use super::portfolio_info;
+
+const COMMANDS: &[&str] = &[
+    "bridges",
+    "exit",
+    "export",
+    "find",
+    "help",
+    "info",
+    "neighbors",
+    "path",
+    "quit",
+];
+
+const HELP: &str = "Commands:
+
+  find TEXT              List the names and addresses containing TEXT
+  info NAME              Describe the portfolio containing NAME, and select it
+  neighbors NAME         List the addresses of NAME (or the names at an address)
+  path FROM TO           Show a shortest path between two names or addresses
+  bridges [NAME]         List the local bridges of a portfolio
+  export FORMAT FILE     Save the selected portfolio to FILE, as dot, json,
+                         graphml, gexf or cytoscape
+  help                   Show this message
+  quit                   Exit the shell
+
+Names containing spaces must be quoted in 'path', e.g. path \"BOOP JONES\" \"FLIM FLAM\".
+Press Tab to complete names and addresses.
+";
+
+/// The maximum number of results each list in `find` shows.
+const MAX_FIND_RESULTS: usize = 20;
+
+/// The maximum number of completions offered at once.
+const MAX_COMPLETIONS: usize = 100;
+
+const TOP: usize = 5;
+
+/// Splits a line into words, treating text in double quotes as one word.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_quotes = false;
+    let mut has_word = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_word = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_word {
+                    words.push(std::mem::take(&mut word));
+                    has_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                has_word = true;
+            }
+        }
+    }
+    if has_word {
+        words.push(word);
+    }
+    words
+}
+
+fn usage(usage: &str) -> HpdError {
+    HpdError::Parse(format!("usage: {}", usage))
+}
+
+/// Executes shell commands against a dataset that's loaded once.
+pub struct Shell {
+    data: Dataset,
+    count_by: CountBy,
+    selected: Option<Arc<Portfolio>>,
+}
+
+impl Shell {
+    pub fn new(data: Dataset, count_by: CountBy) -> Self {
+        Shell {
+            data,
+            count_by,
+            selected: None,
+        }
+    }
+
+    /// Finds a name matching `search`, or failing that, an address,
+    /// ignoring case.
+    fn find_node(&self, search: &str) -> Result<NodeIndex<u32>, HpdError> {
+        let search = search.to_uppercase();
+        match self.data.hpd.find_name(&search) {
+            Err(HpdError::NotFound { .. }) => self.data.hpd.find_addr(&search),
+            result => result,
+        }
+    }
+
+    fn select(&mut self, search: &str) -> Result<Arc<Portfolio>, HpdError> {
+        if !search.is_empty() {
+            let node = self.find_node(search)?;
+            let portfolio =
+                self.data
+                    .portfolios()
+                    .for_node(node)
+                    .ok_or_else(|| HpdError::NotFound {
+                        what: "portfolio",
+                        search: search.to_owned(),
+                    })?;
+            self.selected = Some(portfolio);
+        }
+        self.selected.clone().ok_or_else(|| {
+            HpdError::Parse("no portfolio is selected; use 'info NAME' first".to_owned())
+        })
+    }
+
+    fn find(&self, search: &str) -> String {
+        let mut out = String::new();
+        let search = search.to_uppercase();
+        for (label, matches) in [
+            ("names", self.data.hpd.search_names(&search)),
+            ("addresses", self.data.hpd.search_addrs(&search)),
+        ] {
+            writeln!(out, "{} matching {}:", matches.len(), label).unwrap();
+            for (value, _) in matches.iter().take(MAX_FIND_RESULTS) {
+                writeln!(out, "  {}", value).unwrap();
+            }
+            if matches.len() > MAX_FIND_RESULTS {
+                writeln!(out, "  ...").unwrap();
+            }
+        }
+        out
+    }
+
+    fn neighbors(&self, search: &str) -> Result<String, HpdError> {
+        let hpd = &self.data.hpd;
+        let node = self.find_node(search)?;
+        let mut neighbors: Vec<(&str, usize, usize)> = hpd
+            .graph
+            .edges(node)
+            .map(|edge| {
+                let other = if edge.source() == node {
+                    edge.target()
+                } else {
+                    edge.source()
+                };
+                let weight = edge.weight();
+                (hpd.node_str(other), weight.regs.len(), weight.deeds.len())
+            })
+            .collect();
+        neighbors.sort();
+
+        let mut out = String::new();
+        writeln!(out, "{} is connected to:", hpd.node_str(node)).unwrap();
+        for (neighbor, reg_contacts, deeds) in neighbors {
+            if deeds > 0 {
+                writeln!(
+                    out,
+                    "  {} ({} HPD registration contacts, {} deeds)",
+                    neighbor, reg_contacts, deeds
+                )
+            } else {
+                writeln!(
+                    out,
+                    "  {} ({} HPD registration contacts)",
+                    neighbor, reg_contacts
+                )
+            }
+            .unwrap();
+        }
+        Ok(out)
+    }
+
+    fn path(&self, from: &str, to: &str) -> Result<String, HpdError> {
+        let hpd = &self.data.hpd;
+        let from = self.find_node(from)?;
+        let to = self.find_node(to)?;
+        let path = hpd
+            .shortest_path(from, to)
+            .ok_or_else(|| HpdError::NotFound {
+                what: "path",
+                search: format!("{} -> {}", hpd.node_str(from), hpd.node_str(to)),
+            })?;
+        Ok(format!(
+            "length {} path: {}\n",
+            path.len() - 1,
+            hpd.path_to_string(&path)
+        ))
+    }
+
+    fn bridges(&mut self, search: &str) -> Result<String, HpdError> {
+        let portfolio = self.select(search)?;
+        let hpd = &self.data.hpd;
+        let bridges = portfolio.find_local_bridges();
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{} has {} local bridges:",
+            portfolio.name(),
+            bridges.len()
+        )
+        .unwrap();
+        for (n1, n2) in bridges {
+            writeln!(out, "  {} -- {}", hpd.node_str(n1), hpd.node_str(n2)).unwrap();
+        }
+        Ok(out)
+    }
+
+    fn export(&mut self, format: &str, filename: &str) -> Result<String, HpdError> {
+        let portfolio = self.select("")?;
+        let content = match format {
+            "dot" => portfolio.dot_graph(),
+            "json" => portfolio.json(&self.data.regs, self.count_by, self.data.options()),
+            _ => portfolio.export(&self.data.regs, format.parse()?),
+        };
+        std::fs::write(filename, content).map_err(|e| HpdError::Io(PathBuf::from(filename), e))?;
+        Ok(format!("Wrote {} to {}.\n", portfolio.name(), filename))
+    }
+
+    /// Executes a line of input, returning what it outputs.
+    pub fn execute(&mut self, line: &str) -> Result<String, HpdError> {
+        let words = split_words(line);
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (command.as_str(), args),
+            None => return Ok(String::new()),
+        };
+        let rest = args.join(" ");
+
+        match (command, args.len()) {
+            ("help", _) => Ok(HELP.to_owned()),
+            ("find", n) if n > 0 => Ok(self.find(&rest)),
+            ("find", _) => Err(usage("find TEXT")),
+            ("info", n) if n > 0 => {
+                let portfolio = self.select(&rest)?;
+                Ok(portfolio_info(&PortfolioSummary::new(
+                    &portfolio,
+                    &self.data.regs,
+                    self.count_by,
+                    TOP,
+                )))
+            }
+            ("info", _) => Err(usage("info NAME")),
+            ("neighbors", n) if n > 0 => self.neighbors(&rest),
+            ("neighbors", _) => Err(usage("neighbors NAME")),
+            ("path", 2) => self.path(&args[0], &args[1]),
+            ("path", _) => Err(usage("path FROM TO")),
+            ("bridges", _) => self.bridges(&rest),
+            ("export", 2) => self.export(&args[0], &args[1]),
+            ("export", _) => Err(usage("export FORMAT FILE")),
+            _ => Err(HpdError::Parse(format!(
+                "unknown command '{}'; type 'help' for a list of commands",
+                command
+            ))),
+        }
+    }
+}
+
+/// Completes commands, and then names and addresses.
+struct ShellHelper {
+    values: Vec<String>,
+}
+
+impl ShellHelper {
+    fn new(data: &Dataset) -> Self {
+        let hpd = &data.hpd;
+        let mut values: Vec<String> = hpd
+            .name_nodes
+            .keys()
+            .chain(hpd.addr_nodes.keys())
+            .map(|symbol| hpd.strings.resolve(*symbol).to_owned())
+            .collect();
+        values.sort();
+        values.dedup();
+        ShellHelper { values }
+    }
+
+    fn complete_line(&self, line: &str) -> (usize, Vec<Pair>) {
+        let pair = |value: &str, suffix: &str| Pair {
+            display: value.to_owned(),
+            replacement: format!("{}{}", value, suffix),
+        };
+
+        let start = match line.find(char::is_whitespace) {
+            Some(start) => start + 1,
+            None => {
+                let commands = COMMANDS.iter().filter(|c| c.starts_with(line));
+                return (0, commands.map(|c| pair(c, " ")).collect());
+            }
+        };
+        let (start, suffix) = match line.rfind('"') {
+            Some(quote) if line.matches('"').count() % 2 == 1 => (quote + 1, "\""),
+            _ => (start, ""),
+        };
+        let prefix = line[start..].to_uppercase();
+        let first = self.values.partition_point(|value| *value < prefix);
+        let candidates = self.values[first..]
+            .iter()
+            .take_while(|value| value.starts_with(&prefix))
+            .take(MAX_COMPLETIONS)
+            .map(|value| pair(value, suffix))
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.complete_line(&line[..pos]))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".hpd_history"))
+}
+
+fn terminal_error(e: ReadlineError) -> HpdError {
+    HpdError::Io(PathBuf::from("<terminal>"), std::io::Error::other(e))
+}
+
+/// Runs the shell until the user quits.
+pub fn run(data: Dataset, count_by: CountBy) -> Result<(), HpdError> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new().map_err(terminal_error)?;
+    editor.set_helper(Some(ShellHelper::new(&data)));
+    let history_path = history_path();
+    if let Some(path) = &history_path {
+        // There won't be any history the first time the shell is run.
+        let _ = editor.load_history(path);
+    }
+
+    let mut shell = Shell::new(data, count_by);
+    println!("Type 'help' for a list of commands.");
+
+    loop {
+        let line = match editor.readline("hpd> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(terminal_error(e)),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line).map_err(terminal_error)?;
+        if line == "quit" || line == "exit" {
+            break;
+        }
+        match shell.execute(line) {
+            Ok(output) => print!("{}", output),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if let Some(path) = &history_path {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("Unable to save history to '{}': {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hpd::DatasetBuilder;
+
+    fn dataset() -> Dataset {
+        DatasetBuilder::new()
+            .read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_words_works() {
+        assert_eq!(
+            split_words(r#"path "BOOP JONES"  "1 BOOP STREET, NEW YORK NY""#),
+            vec!["path", "BOOP JONES", "1 BOOP STREET, NEW YORK NY"]
+        );
+        assert_eq!(
+            split_words(r#"info BOOP  JONES """#),
+            vec!["info", "BOOP", "JONES", ""]
+        );
+    }
+
+    #[test]
+    fn test_commands_work() {
+        let mut shell = Shell::new(dataset(), CountBy::default());
+        let found = shell.execute("find zap").unwrap();
+        assert!(found.starts_with("2 matching names:\n  ZIP ZAP\n  ZORP ZAP\n"));
+
+        let info = shell.execute("info BLAP SMITH").unwrap();
+        assert!(info.starts_with("This is BOOP JONES's portfolio.\nIt has 5 buildings.\n"));
+
+        let neighbors = shell.execute("neighbors FLIM FLAM").unwrap();
+        assert!(neighbors.starts_with("FLIM FLAM is connected to:\n  "));
+
+        let path = shell.execute(r#"path "BLAP SMITH" "FLIM FLAM""#).unwrap();
+        assert!(path.starts_with("length "));
+        assert!(path.trim_end().ends_with("FLIM FLAM"));
+
+        // The portfolio selected by 'info' is used when no name is given.
+        let bridges = shell.execute("bridges").unwrap();
+        assert!(bridges.starts_with("BOOP JONES's portfolio has "));
+
+        assert!(matches!(
+            shell.execute("path BOOP"),
+            Err(HpdError::Parse(_))
+        ));
+        assert!(matches!(shell.execute("frob"), Err(HpdError::Parse(_))));
+    }
+
+    #[test]
+    fn test_names_are_case_insensitive() {
+        let mut shell = Shell::new(dataset(), CountBy::default());
+        let info = shell.execute("info blap smith").unwrap();
+        assert!(info.starts_with("This is BOOP JONES's portfolio.\n"));
+
+        let neighbors = shell.execute("neighbors Flim Flam").unwrap();
+        assert!(neighbors.starts_with("FLIM FLAM is connected to:\n  "));
+
+        let path = shell.execute(r#"path "blap smith" "flim flam""#).unwrap();
+        assert!(path.trim_end().ends_with("FLIM FLAM"));
+    }
+
+    #[test]
+    fn test_export_requires_a_selected_portfolio() {
+        let mut shell = Shell::new(dataset(), CountBy::default());
+        assert!(matches!(
+            shell.execute("export dot out.dot"),
+            Err(HpdError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_works() {
+        let mut shell = Shell::new(dataset(), CountBy::default());
+        shell.execute("info ZIP ZAP").unwrap();
+        let path = std::env::temp_dir().join(format!("hpd-shell-{}.dot", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        shell
+            .execute(&format!("export dot \"{}\"", path_str))
+            .unwrap();
+        let dot = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(dot.starts_with("// ZIP ZAP's portfolio"));
+
+        assert!(matches!(
+            shell.execute("export svg out.svg"),
+            Err(HpdError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_completion_works() {
+        let helper = ShellHelper::new(&dataset());
+        let replacements = |line: &str| -> (usize, Vec<String>) {
+            let (start, pairs) = helper.complete_line(line);
+            (start, pairs.into_iter().map(|p| p.replacement).collect())
+        };
+        assert_eq!(replacements("ne"), (0, vec!["neighbors ".to_owned()]));
+        assert_eq!(
+            replacements("info z"),
+            (5, vec!["ZIP ZAP".to_owned(), "ZORP ZAP".to_owned()])
+        );
+        assert_eq!(
+            replacements(r#"path "BOOP JONES" "fl"#),
+            (19, vec!["FLIM FLAM\"".to_owned()])
+        );
+    }
+}
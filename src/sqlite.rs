@@ -0,0 +1,141 @@
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+use super::error::HpdError;
+use super::hpd_graph::HpdGraph;
+use super::hpd_registrations::{CountBy, HpdRegistrationMap};
+use super::portfolio::PortfolioMap;
+use super::tables::GraphTables;
+
+const SCHEMA: &str = "
+CREATE TABLE registrations (
+    id INTEGER NOT NULL,
+    bbl TEXT NOT NULL,
+    bin INTEGER,
+    last_registration_date TEXT,
+    registration_end_date TEXT NOT NULL
+);
+CREATE TABLE portfolios (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    building_count INTEGER NOT NULL
+);
+CREATE TABLE nodes (
+    id INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL,
+    label TEXT NOT NULL,
+    portfolio_id TEXT NOT NULL REFERENCES portfolios (id),
+    degree INTEGER NOT NULL
+);
+CREATE TABLE edges (
+    id INTEGER PRIMARY KEY,
+    from_node INTEGER NOT NULL REFERENCES nodes (id),
+    to_node INTEGER NOT NULL REFERENCES nodes (id),
+    reg_contact_count INTEGER NOT NULL,
+    is_bridge INTEGER NOT NULL
+);
+CREATE TABLE contacts (
+    id INTEGER NOT NULL,
+    registration_id INTEGER NOT NULL,
+    edge_id INTEGER NOT NULL REFERENCES edges (id)
+);
+";
+
+const INDEXES: &str = "
+CREATE INDEX registrations_id ON registrations (id);
+CREATE INDEX registrations_bbl ON registrations (bbl);
+CREATE INDEX registrations_bin ON registrations (bin);
+CREATE INDEX nodes_label ON nodes (label);
+CREATE INDEX nodes_portfolio_id ON nodes (portfolio_id);
+CREATE INDEX edges_from_node ON edges (from_node);
+CREATE INDEX edges_to_node ON edges (to_node);
+CREATE INDEX contacts_registration_id ON contacts (registration_id);
+CREATE INDEX contacts_edge_id ON contacts (edge_id);
+";
+
+/// Writes the registrations, graph and portfolios to a new SQLite database
+/// at `path`, replacing any file that's already there.
+pub fn write_database(
+    path: &Path,
+    hpd: &HpdGraph,
+    portfolios: &PortfolioMap,
+    regs: &HpdRegistrationMap,
+    count_by: CountBy,
+) -> Result<(), HpdError> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(HpdError::Io(path.to_path_buf(), e))
+        }
+        _ => {}
+    }
+    let mut conn = Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    let mut all_regs: Vec<_> = regs.iter().collect();
+    all_regs.sort_by_key(|reg| (reg.reg_id, reg.bbl));
+    {
+        let mut insert = tx.prepare("INSERT INTO registrations VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for reg in all_regs {
+            insert.execute(params![
+                reg.reg_id,
+                reg.bbl.to_string(),
+                reg.bin,
+                reg.last_reg_date.map(|date| date.to_string()),
+                reg.reg_end_date.to_string(),
+            ])?;
+        }
+    }
+
+    {
+        let mut insert = tx.prepare("INSERT INTO portfolios VALUES (?1, ?2, ?3)")?;
+        for (portfolio, building_count) in portfolios.rank_by_building_count(regs, count_by, 0) {
+            insert.execute(params![
+                portfolio.id().as_str(),
+                portfolio.name().as_str(),
+                building_count,
+            ])?;
+        }
+    }
+
+    let tables = GraphTables::new(hpd, portfolios, regs);
+    {
+        let mut insert = tx.prepare("INSERT INTO nodes VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for node in &tables.nodes {
+            insert.execute(params![
+                node.id,
+                node.kind,
+                node.label,
+                node.portfolio_id.as_str(),
+                node.degree,
+            ])?;
+        }
+
+        let mut insert = tx.prepare("INSERT INTO edges VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for edge in &tables.edges {
+            insert.execute(params![
+                edge.id,
+                edge.from,
+                edge.to,
+                edge.reg_contact_count,
+                edge.is_bridge,
+            ])?;
+        }
+
+        // A contact has a row in `edge_registrations` for each of its
+        // registration's buildings, which are all listed together.
+        let mut insert = tx.prepare("INSERT INTO contacts VALUES (?1, ?2, ?3)")?;
+        let mut previous = None;
+        for row in &tables.edge_registrations {
+            let contact = (row.edge, row.contact_id);
+            if previous != Some(contact) {
+                insert.execute(params![row.contact_id, row.registration_id, row.edge])?;
+                previous = Some(contact);
+            }
+        }
+    }
+
+    tx.execute_batch(INDEXES)?;
+    tx.commit()?;
+    Ok(())
+}
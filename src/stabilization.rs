@@ -0,0 +1,253 @@
+//! Rent-stabilized unit counts, as derived from Department of Finance
+//! property tax bills, by tax lot and year.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::bbl::BBL;
+use super::csv_batches::parse_records;
+use super::error::HpdError;
+
+/// Returns the year of a column of stabilized unit counts, which is named
+/// either like "2017uc" or like "uc2018".
+fn year_of_column(header: &str) -> Option<i32> {
+    let header = header.to_ascii_lowercase();
+    let year = header
+        .strip_suffix("uc")
+        .or_else(|| header.strip_prefix("uc"))?;
+    if year.len() != 4 {
+        return None;
+    }
+    year.parse().ok()
+}
+
+fn parse_units(value: &str) -> Result<u32, HpdError> {
+    if value.is_empty() {
+        return Ok(0);
+    }
+    value
+        .parse()
+        .map_err(|e| HpdError::Parse(format!("stabilized units '{}' ({})", value, e)))
+}
+
+/// The number of rent-stabilized units on each tax lot, in every year the
+/// data covers.
+pub struct StabilizedUnitCounts {
+    /// The years the data covers, in order.
+    years: Vec<i32>,
+    /// The units on each lot in each of `years`.
+    by_bbl: HashMap<BBL, Vec<u32>>,
+    /// The number of rows skipped because their BBLs were invalid.
+    invalid_bbls: usize,
+}
+
+impl StabilizedUnitCounts {
+    /// Reads counts from a CSV with a `ucbbl` (or `bbl`) column and a
+    /// column of counts for each year, named like `2017uc` or `uc2018`, as
+    /// in the rent stabilization data scraped from tax bills. Other columns
+    /// are ignored, blank counts are treated as zero, and rows with invalid
+    /// BBLs are skipped.
+    pub fn from_csv<T: std::io::Read>(mut rdr: csv::Reader<T>) -> Result<Self, HpdError> {
+        let headers = rdr.headers()?.clone();
+        let bbl_column = headers
+            .iter()
+            .position(|header| {
+                header.eq_ignore_ascii_case("ucbbl") || header.eq_ignore_ascii_case("bbl")
+            })
+            .ok_or_else(|| HpdError::Parse("stabilized units have no BBL column".to_owned()))?;
+        let mut year_columns: Vec<(i32, usize)> = headers
+            .iter()
+            .enumerate()
+            .filter_map(|(column, header)| year_of_column(header).map(|year| (year, column)))
+            .collect();
+        if year_columns.is_empty() {
+            return Err(HpdError::Parse(
+                "stabilized units have no columns of yearly counts".to_owned(),
+            ));
+        }
+        year_columns.sort();
+
+        let mut by_bbl = HashMap::new();
+        let mut invalid_bbls = 0;
+        parse_records(
+            rdr,
+            |record, _| {
+                let bbl: Option<BBL> = record.get(bbl_column).unwrap_or_default().parse().ok();
+                let units = year_columns
+                    .iter()
+                    .map(|(_, column)| parse_units(record.get(*column).unwrap_or_default()))
+                    .collect::<Result<Vec<u32>, HpdError>>()?;
+                Ok((bbl, units))
+            },
+            |(bbl, units)| {
+                match bbl {
+                    Some(bbl) => {
+                        by_bbl.insert(bbl, units);
+                    }
+                    None => invalid_bbls += 1,
+                }
+                Ok(())
+            },
+        )?;
+        let years: Vec<i32> = year_columns.into_iter().map(|(year, _)| year).collect();
+        eprintln!(
+            "Loaded rent-stabilized units on {} tax lots from {} to {} (skipped {} invalid BBLs).",
+            by_bbl.len(),
+            years[0],
+            years[years.len() - 1],
+            invalid_bbls
+        );
+        Ok(StabilizedUnitCounts {
+            years,
+            by_bbl,
+            invalid_bbls,
+        })
+    }
+
+    /// The years the data covers, in order.
+    pub fn years(&self) -> &[i32] {
+        &self.years
+    }
+
+    /// The number of rows skipped because their BBLs were invalid.
+    pub fn invalid_bbls(&self) -> usize {
+        self.invalid_bbls
+    }
+
+    /// The number of stabilized units on the given tax lot in each of
+    /// `years`, or `None` if the lot isn't in the data.
+    pub fn units(&self, bbl: BBL) -> Option<&[u32]> {
+        self.by_bbl.get(&bbl).map(Vec::as_slice)
+    }
+}
+
+/// The number of a portfolio's rent-stabilized units in one year.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct StabilizedUnitsInYear {
+    pub year: i32,
+    pub stabilized_units: u32,
+}
+
+/// How many rent-stabilized units a portfolio has, and how many it has
+/// lost.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PortfolioStabilizedUnits {
+    /// The number of stabilized units on the portfolio's tax lots in
+    /// `year`.
+    pub stabilized_units: u32,
+    /// The latest year the data covers.
+    pub year: i32,
+    /// How many fewer stabilized units the portfolio had in `year` than in
+    /// the year before, which is negative if it gained units, or `None` if
+    /// the data only covers one year.
+    pub stabilized_units_lost: Option<i64>,
+    /// The portfolio's stabilized units in every year the data covers, in
+    /// order.
+    pub history: Vec<StabilizedUnitsInYear>,
+}
+
+impl PortfolioStabilizedUnits {
+    /// Totals the given lots' stabilized units in each year.
+    pub fn new<I: IntoIterator<Item = BBL>>(counts: &StabilizedUnitCounts, bbls: I) -> Self {
+        let mut totals = vec![0; counts.years().len()];
+        for units in bbls.into_iter().filter_map(|bbl| counts.units(bbl)) {
+            for (total, units) in totals.iter_mut().zip(units) {
+                *total += units;
+            }
+        }
+        let history: Vec<StabilizedUnitsInYear> = counts
+            .years()
+            .iter()
+            .zip(totals)
+            .map(|(year, stabilized_units)| StabilizedUnitsInYear {
+                year: *year,
+                stabilized_units,
+            })
+            .collect();
+        let latest = &history[history.len() - 1];
+        let stabilized_units_lost = history
+            .len()
+            .checked_sub(2)
+            .map(|i| history[i].stabilized_units as i64 - latest.stabilized_units as i64);
+        PortfolioStabilizedUnits {
+            stabilized_units: latest.stabilized_units,
+            year: latest.year,
+            stabilized_units_lost,
+            history,
+        }
+    }
+
+    /// Describes the stabilized units in a sentence, for people to read.
+    pub fn describe(&self) -> String {
+        let had = format!(
+            "It had {} rent-stabilized units in {}",
+            self.stabilized_units, self.year
+        );
+        let previous_year = self
+            .history
+            .len()
+            .checked_sub(2)
+            .map(|i| self.history[i].year);
+        match (self.stabilized_units_lost, previous_year) {
+            (Some(lost), Some(previous_year)) if lost > 0 => {
+                format!("{}, {} fewer than in {}.", had, lost, previous_year)
+            }
+            (Some(lost), Some(previous_year)) if lost < 0 => {
+                format!("{}, {} more than in {}.", had, -lost, previous_year)
+            }
+            (Some(_), Some(previous_year)) => format!("{}, as in {}.", had, previous_year),
+            _ => format!("{}.", had),
+        }
+    }
+}
+
+#[test]
+fn test_from_csv_reads_every_year() {
+    let counts = StabilizedUnitCounts::from_csv(csv::Reader::from_reader(
+        "borough,ucbbl,2016uc,2016est,2017uc\n\
+         BK,3012340010,5,,4\n\
+         BK,3012340011,,,2\n\
+         BK,301234,1,,1\n"
+            .as_bytes(),
+    ))
+    .unwrap();
+    assert_eq!(counts.years(), &[2016, 2017]);
+    assert_eq!(
+        counts.units("3012340010".parse().unwrap()),
+        Some(&[5, 4][..])
+    );
+    assert_eq!(
+        counts.units("3012340011".parse().unwrap()),
+        Some(&[0, 2][..])
+    );
+    assert_eq!(counts.units("3012340012".parse().unwrap()), None);
+    assert_eq!(counts.invalid_bbls(), 1);
+}
+
+#[test]
+fn test_portfolio_stabilized_units_counts_losses() {
+    let counts = StabilizedUnitCounts::from_csv(csv::Reader::from_reader(
+        "ucbbl,uc2019,uc2018\n\
+         3012340010,4,5\n\
+         3012340011,2,\n\
+         3012340012,9,9\n"
+            .as_bytes(),
+    ))
+    .unwrap();
+    let bbls = ["3012340010", "3012340011", "3012340013"].map(|bbl| bbl.parse().unwrap());
+    let units = PortfolioStabilizedUnits::new(&counts, bbls);
+    assert_eq!(units.stabilized_units, 6);
+    assert_eq!(units.year, 2019);
+    assert_eq!(units.stabilized_units_lost, Some(-1));
+    assert_eq!(
+        units.describe(),
+        "It had 6 rent-stabilized units in 2019, 1 more than in 2018."
+    );
+
+    let units = PortfolioStabilizedUnits::new(&counts, bbls[..1].iter().copied());
+    assert_eq!(
+        units.describe(),
+        "It had 4 rent-stabilized units in 2019, 1 fewer than in 2018."
+    );
+}
@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use std::rc::Rc;
 
 fn get_synonyms() -> Vec<(&'static str, Vec<&'static str>)> {
     vec![(
@@ -20,27 +19,23 @@ fn get_synonyms() -> Vec<(&'static str, Vec<&'static str>)> {
 }
 
 pub struct Synonyms {
-    map: HashMap<String, Rc<String>>,
+    map: HashMap<&'static str, &'static str>,
 }
 
 impl Synonyms {
     pub fn new() -> Self {
-        let mut map: HashMap<String, Rc<String>> = HashMap::new();
+        let mut map: HashMap<&'static str, &'static str> = HashMap::new();
 
-        for (str_canonical, synonyms) in get_synonyms() {
-            let canonical = Rc::new(str_canonical.to_string());
+        for (canonical, synonyms) in get_synonyms() {
             for synonym in synonyms {
-                map.insert(synonym.to_string(), Rc::clone(&canonical));
+                map.insert(synonym, canonical);
             }
         }
 
         Synonyms { map }
     }
 
-    pub fn get(&self, value: &String) -> Option<Rc<String>> {
-        match self.map.get(value) {
-            Some(value) => Some(Rc::clone(&value)),
-            None => None,
-        }
+    pub fn get(&self, value: &str) -> Option<&'static str> {
+        self.map.get(value).copied()
     }
 }
@@ -0,0 +1,269 @@
+use petgraph::visit::EdgeRef;
+use serde::Serialize;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::error::HpdError;
+use super::hpd_graph::{HpdGraph, Node};
+use super::hpd_registrations::HpdRegistrationMap;
+use super::portfolio::PortfolioMap;
+
+pub const NODES_FILENAME: &str = "nodes";
+pub const EDGES_FILENAME: &str = "edges";
+pub const EDGE_REGISTRATIONS_FILENAME: &str = "edge_registrations";
+
+/// The file format of exported tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Parquet,
+}
+
+impl TableFormat {
+    /// The names accepted by `from_str`.
+    pub const NAMES: &'static [&'static str] = &["csv", "parquet"];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TableFormat::Csv => "csv",
+            TableFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for TableFormat {
+    type Err = HpdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(TableFormat::Csv),
+            "parquet" if cfg!(feature = "parquet") => Ok(TableFormat::Parquet),
+            "parquet" => Err(HpdError::Parse(
+                "Parquet support isn't enabled; rebuild with '--features parquet'".to_owned(),
+            )),
+            _ => Err(HpdError::Parse(format!(
+                "unknown table format '{}', expected one of {}",
+                s,
+                TableFormat::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct NodeRow<'a> {
+    pub id: u32,
+    pub kind: &'static str,
+    pub label: &'a str,
+    pub portfolio_id: Arc<String>,
+    pub degree: u32,
+}
+
+#[derive(Serialize)]
+pub struct EdgeRow {
+    pub id: u32,
+    pub from: u32,
+    pub to: u32,
+    pub reg_contact_count: u32,
+    pub is_bridge: bool,
+}
+
+/// One registration contact that links the two nodes of an edge.
+#[derive(Serialize)]
+pub struct EdgeRegistrationRow {
+    pub edge: u32,
+    pub registration_id: u32,
+    pub contact_id: u32,
+    pub bbl: String,
+    pub bin: Option<u32>,
+}
+
+/// The whole graph as flat tables, for loading into e.g. pandas or a data
+/// warehouse. Nodes and edges are identified by their indices in the graph.
+pub struct GraphTables<'a> {
+    pub nodes: Vec<NodeRow<'a>>,
+    pub edges: Vec<EdgeRow>,
+    pub edge_registrations: Vec<EdgeRegistrationRow>,
+}
+
+impl<'a> GraphTables<'a> {
+    pub fn new(hpd: &'a HpdGraph, portfolios: &PortfolioMap, regs: &HpdRegistrationMap) -> Self {
+        let graph = &hpd.graph;
+        let local_bridges = portfolios.local_bridges();
+
+        let nodes = graph
+            .node_indices()
+            .map(|node| {
+                let (kind, symbol) = match graph[node] {
+                    Node::Name(name) => ("name", name),
+                    Node::BizAddr(addr) => ("address", addr),
+                };
+                NodeRow {
+                    id: node.index() as u32,
+                    kind,
+                    label: hpd.strings.resolve(symbol),
+                    portfolio_id: portfolios
+                        .for_node(node)
+                        .expect("every node should be in a portfolio")
+                        .id(),
+                    degree: graph.neighbors(node).count() as u32,
+                }
+            })
+            .collect();
+
+        let mut edges = Vec::with_capacity(graph.edge_count());
+        let mut edge_registrations = vec![];
+        for edge in graph.edge_references() {
+            let id = edge.id().index() as u32;
+            edges.push(EdgeRow {
+                id,
+                from: edge.source().index() as u32,
+                to: edge.target().index() as u32,
+                reg_contact_count: edge.weight().regs.len() as u32,
+                is_bridge: local_bridges.contains(&edge.id()),
+            });
+            for reg_info in &edge.weight().regs {
+                for reg in regs.get_by_id(reg_info.id).into_iter().flatten() {
+                    edge_registrations.push(EdgeRegistrationRow {
+                        edge: id,
+                        registration_id: reg_info.id,
+                        contact_id: reg_info.contact_id,
+                        bbl: reg.bbl.to_string(),
+                        bin: reg.bin,
+                    });
+                }
+            }
+        }
+
+        GraphTables {
+            nodes,
+            edges,
+            edge_registrations,
+        }
+    }
+
+    /// Writes each table to its own file in `dir`, creating it if needed,
+    /// and returns the paths written.
+    pub fn write(&self, dir: &Path, format: TableFormat) -> Result<Vec<PathBuf>, HpdError> {
+        std::fs::create_dir_all(dir).map_err(|e| HpdError::Io(dir.to_path_buf(), e))?;
+        let path = |name: &str| dir.join(format!("{}.{}", name, format.extension()));
+        let paths = vec![
+            path(NODES_FILENAME),
+            path(EDGES_FILENAME),
+            path(EDGE_REGISTRATIONS_FILENAME),
+        ];
+        match format {
+            TableFormat::Csv => {
+                write_csv(&paths[0], &self.nodes)?;
+                write_csv(&paths[1], &self.edges)?;
+                write_csv(&paths[2], &self.edge_registrations)?;
+            }
+            #[cfg(feature = "parquet")]
+            TableFormat::Parquet => self.write_parquet(&paths)?,
+            #[cfg(not(feature = "parquet"))]
+            TableFormat::Parquet => unreachable!("TableFormat::from_str rejects Parquet"),
+        }
+        Ok(paths)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn write_parquet(&self, paths: &[PathBuf]) -> Result<(), HpdError> {
+        use arrow_array::{ArrayRef, BooleanArray, StringArray, UInt32Array};
+
+        fn u32s<T>(rows: &[T], f: impl Fn(&T) -> u32) -> ArrayRef {
+            Arc::new(rows.iter().map(f).collect::<UInt32Array>())
+        }
+        fn strs<T>(rows: &[T], f: impl Fn(&T) -> &str) -> ArrayRef {
+            Arc::new(rows.iter().map(|row| Some(f(row))).collect::<StringArray>())
+        }
+
+        let nodes = &self.nodes;
+        write_parquet(
+            &paths[0],
+            vec![
+                ("id", u32s(nodes, |row| row.id)),
+                ("kind", strs(nodes, |row| row.kind)),
+                ("label", strs(nodes, |row| row.label)),
+                ("portfolio_id", strs(nodes, |row| &row.portfolio_id)),
+                ("degree", u32s(nodes, |row| row.degree)),
+            ],
+        )?;
+
+        let edges = &self.edges;
+        write_parquet(
+            &paths[1],
+            vec![
+                ("id", u32s(edges, |row| row.id)),
+                ("from", u32s(edges, |row| row.from)),
+                ("to", u32s(edges, |row| row.to)),
+                (
+                    "reg_contact_count",
+                    u32s(edges, |row| row.reg_contact_count),
+                ),
+                (
+                    "is_bridge",
+                    Arc::new(
+                        edges
+                            .iter()
+                            .map(|row| Some(row.is_bridge))
+                            .collect::<BooleanArray>(),
+                    ),
+                ),
+            ],
+        )?;
+
+        let edge_regs = &self.edge_registrations;
+        write_parquet(
+            &paths[2],
+            vec![
+                ("edge", u32s(edge_regs, |row| row.edge)),
+                (
+                    "registration_id",
+                    u32s(edge_regs, |row| row.registration_id),
+                ),
+                ("contact_id", u32s(edge_regs, |row| row.contact_id)),
+                ("bbl", strs(edge_regs, |row| &row.bbl)),
+                (
+                    "bin",
+                    Arc::new(edge_regs.iter().map(|row| row.bin).collect::<UInt32Array>()),
+                ),
+            ],
+        )
+    }
+}
+
+fn write_csv<T: Serialize>(path: &Path, rows: &[T]) -> Result<(), HpdError> {
+    let file = File::create(path).map_err(|e| HpdError::Io(path.to_path_buf(), e))?;
+    let mut wtr = csv::Writer::from_writer(file);
+    for row in rows {
+        wtr.serialize(row)?;
+    }
+    wtr.flush().map_err(|e| HpdError::Io(path.to_path_buf(), e))
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(path: &Path, columns: Vec<(&str, arrow_array::ArrayRef)>) -> Result<(), HpdError> {
+    let io_error = |e: parquet::errors::ParquetError| {
+        HpdError::Io(path.to_path_buf(), std::io::Error::other(e))
+    };
+    let batch = arrow_array::RecordBatch::try_from_iter(columns)
+        .expect("every column should have the same length");
+    let file = File::create(path).map_err(|e| HpdError::Io(path.to_path_buf(), e))?;
+    let mut writer =
+        parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None).map_err(io_error)?;
+    writer.write(&batch).map_err(io_error)?;
+    writer.close().map_err(io_error)?;
+    Ok(())
+}
+
+#[test]
+fn test_table_format_from_str_works() {
+    assert_eq!("csv".parse::<TableFormat>().unwrap(), TableFormat::Csv);
+    assert_eq!(
+        "parquet".parse::<TableFormat>().is_ok(),
+        cfg!(feature = "parquet")
+    );
+    assert!("xlsx".parse::<TableFormat>().is_err());
+}
@@ -1,34 +1,52 @@
 use maud::{html, Markup, PreEscaped, DOCTYPE};
-use std::error::Error;
+use rayon::prelude::*;
+use serde::Serialize;
 use std::path::PathBuf;
-use std::rc::Rc;
+use std::sync::Arc;
 
-use super::hpd_registrations::HpdRegistrationMap;
-use super::portfolio::{Portfolio, PortfolioMap};
+use super::bbl::{Boro, BBL};
+use super::dataset::Dataset;
+use super::error::HpdError;
+use super::hpd_registrations::{CountBy, HpdRegistrationMap};
+use super::json::generated_at;
+use super::portfolio::Portfolio;
 
-static SITE_DIR: &'static str = "public";
-static INDEX_FILENAME: &'static str = "index.html";
+static SITE_DIR: &str = "public";
+static INDEX_FILENAME: &str = "index.html";
+static SEARCH_INDEX_FILENAME: &str = "search-index.json";
 
-fn slugify<T: AsRef<str>>(value: T) -> String {
-    let value_ref = value.as_ref();
-    value_ref
-        .chars()
-        .map(|c| match c {
-            'A'..='Z' => Some(c.to_ascii_lowercase()),
-            'a'..='z' => Some(c),
-            ' ' => Some('_'),
-            _ => None,
-        })
-        .flatten()
-        .collect::<String>()
+/// A page of the website, along with the names, business addresses and
+/// BBLs that visitors can search for to find it.
+#[derive(Serialize)]
+pub struct SearchIndexEntry<'a> {
+    pub href: String,
+    pub title: Arc<String>,
+    pub names: Vec<&'a str>,
+    pub addresses: Vec<&'a str>,
+    pub bbls: Vec<BBL>,
+}
+
+impl<'a> SearchIndexEntry<'a> {
+    pub fn new(portfolio: &'a Portfolio, regs: &HpdRegistrationMap) -> Self {
+        SearchIndexEntry {
+            // Portfolio names change whenever their most frequently
+            // mentioned name does, so we use IDs to keep links working
+            // between exports.
+            href: format!("{}.html", portfolio.id()),
+            title: portfolio.name(),
+            names: portfolio.names().into_iter().collect(),
+            addresses: portfolio.bizaddrs().into_iter().collect(),
+            bbls: portfolio.bbls(regs).into_iter().collect(),
+        }
+    }
 }
 
-fn write_website_file<T: AsRef<str>>(filename: T, content: &String) -> Result<(), Box<dyn Error>> {
+fn write_website_file<T: AsRef<str>>(filename: T, content: &String) -> Result<(), HpdError> {
     let filename: PathBuf = [SITE_DIR, filename.as_ref()].iter().collect();
 
-    match std::fs::write(filename, content) {
+    match std::fs::write(&filename, content) {
         Ok(result) => Ok(result),
-        Err(e) => Err(Box::new(e)),
+        Err(e) => Err(HpdError::Io(filename, e)),
     }
 }
 
@@ -37,12 +55,17 @@ fn header<T: AsRef<str>>(title: T) -> Markup {
         (DOCTYPE)
         meta charset="utf-8";
         link rel="stylesheet" href="styles.css";
-        title { (title) }
-        h1 { (title) }
+        title { (title.as_ref()) }
+        h1 { (title.as_ref()) }
     }
 }
 
-fn portfolio_html(portfolio: &Rc<Portfolio>, regs: &HpdRegistrationMap) -> String {
+fn portfolio_html(portfolio: &Arc<Portfolio>, data: &Dataset, count_by: CountBy) -> String {
+    let json = portfolio.json(&data.regs, count_by, data.options());
+    let buildings = portfolio.buildings(&data.regs);
+    let conditions = portfolio.conditions(&data.regs, count_by);
+    let evictions = portfolio.evictions(&data.regs, count_by);
+    let stabilized_units = portfolio.stabilized_units(&data.regs);
     let page = html! {
         (header(portfolio.name().as_ref()))
         div id="graph" {}
@@ -52,35 +75,93 @@ fn portfolio_html(portfolio: &Rc<Portfolio>, regs: &HpdRegistrationMap) -> Strin
         }
         p id="message" {}
         p id="back" { a href=(INDEX_FILENAME) { "« Back" } }
-        script type="application/json" id="portfolio" { (PreEscaped(portfolio.json(regs))) }
+        @if let Some(conditions) = &conditions {
+            p id="conditions" { (conditions.describe().join(" ")) }
+        }
+        @if let Some(evictions) = &evictions {
+            p id="evictions" { (evictions.describe()) }
+        }
+        @if let Some(stabilized_units) = &stabilized_units {
+            p id="stabilized-units" { (stabilized_units.describe()) }
+        }
+        details id="buildings" {
+            summary { "Buildings and their registrations (" (buildings.len()) ")" }
+            table {
+                tr {
+                    th { "BBL" }
+                    th { "BIN" }
+                    th { "Registration" }
+                    th { "Expires" }
+                    th { "Names" }
+                    th { "Business addresses" }
+                }
+                @for building in &buildings {
+                    tr {
+                        td { (building.bbl) }
+                        td { @if let Some(bin) = building.bin { (bin) } }
+                        td { (building.registration_id) }
+                        td { (building.registration_end_date) }
+                        td { (building.names.join("; ")) }
+                        td { (building.addresses.join("; ")) }
+                    }
+                }
+            }
+        }
+        script type="application/json" id="portfolio" { (PreEscaped(json)) }
         script src="main.bundle.js" { }
     };
 
     page.into_string()
 }
 
+/// Exports the portfolios with at least `min_buildings` buildings, or only
+/// those entirely within `boro` if it's given, along with an index page
+/// that lists them and a search index for finding them from it.
 pub fn make_website(
-    portfolio_map: PortfolioMap,
-    regs: &HpdRegistrationMap,
+    data: &Dataset,
+    count_by: CountBy,
     min_buildings: usize,
-) -> Result<(), Box<dyn Error>> {
-    let portfolios = portfolio_map.rank_by_building_count(&regs, min_buildings);
-    let mut list_items: Vec<(String, Rc<String>, usize)> = vec![];
-
-    for (portfolio, num_buildings) in &portfolios {
-        let html = portfolio_html(portfolio, regs);
-        let name = portfolio.name();
-        let filename = format!("{}.html", slugify(name.as_ref()));
-        write_website_file(&filename, &html)?;
-        list_items.push((filename, name, *num_buildings));
+    boro: Option<Boro>,
+) -> Result<(), HpdError> {
+    let mut portfolios =
+        data.portfolios()
+            .rank_by_building_count(&data.regs, count_by, min_buildings);
+    if let Some(boro) = boro {
+        portfolios.retain(|(portfolio, _)| portfolio.is_only_in(boro, &data.regs));
     }
+    // Generating each page involves finding the portfolio's local bridges,
+    // which is slow for large portfolios, so we do it in parallel.
+    let list_items = portfolios
+        .par_iter()
+        .map(|(portfolio, num_buildings)| {
+            let html = portfolio_html(portfolio, data, count_by);
+            let entry = SearchIndexEntry::new(portfolio, &data.regs);
+            write_website_file(&entry.href, &html)?;
+            Ok((entry, *num_buildings))
+        })
+        .collect::<Result<Vec<(SearchIndexEntry, usize)>, HpdError>>()?;
+    let search_index: Vec<&SearchIndexEntry> = list_items.iter().map(|(entry, _)| entry).collect();
+    write_website_file(
+        SEARCH_INDEX_FILENAME,
+        &serde_json::to_string(&search_index).unwrap(),
+    )?;
 
     let index_html = html! {
         (header("hpd-graph-fun"))
-        p { "These are all the portfolios with at least " (min_buildings) " buildings." }
-        ol {
-            @for (href, name, num_buildings) in &list_items {
-                li { a href=(href) { (name) } " (" (num_buildings) " buildings)" }
+        p {
+            "These are all the portfolios with at least " (min_buildings) " buildings"
+            @if let Some(boro) = boro { ", all of them in " (boro) }
+            "."
+        }
+        form id="index-search-form" {
+            input type="search" value="" placeholder="Name, business address or BBL" id="index-search-input";
+            button type="submit" { "Search" }
+        }
+        p id="message" {}
+        ol id="search-results" {}
+        ol id="portfolios" {
+            @for (entry, num_buildings) in &list_items {
+                li { a href=(entry.href) { (entry.title) } " (" (num_buildings) " buildings)" }
             }
         }
         p {
@@ -89,11 +170,12 @@ pub fn make_website(
             "."
         }
         p {
-            "This website was generated by hpd v" (env!("CARGO_PKG_VERSION")) " on " (chrono::Utc::now()) "."
+            "This website was generated by hpd v" (env!("CARGO_PKG_VERSION")) " on " (generated_at()) "."
         }
+        script src="main.bundle.js" { }
     };
 
-    write_website_file(&INDEX_FILENAME, &index_html.into_string())?;
+    write_website_file(INDEX_FILENAME, &index_html.into_string())?;
     println!(
         "Exported {} portfolios. You can view them at {}/{}.",
         portfolios.len(),
@@ -102,10 +184,3 @@ pub fn make_website(
     );
     Ok(())
 }
-
-#[test]
-fn test_slugify_works() {
-    assert_eq!(slugify("hello"), "hello".to_owned());
-    assert_eq!(slugify("HELLO"), "hello".to_owned());
-    assert_eq!(slugify("BOOP'S portfolio"), "boops_portfolio".to_owned());
-}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::{fixtures_dir, run_hpd};
+use hpd::acris::AcrisDeeds;
+use hpd::report::LongPath;
+use hpd::server::ApiServer;
+use hpd::{CountBy, Dataset, DatasetBuilder};
+use serde_json::{json, Value};
+
+fn dataset_with_deeds() -> Dataset {
+    let deeds = AcrisDeeds::read_dir(fixtures_dir().join("acris")).unwrap();
+    DatasetBuilder::new()
+        .deeds(deeds)
+        .read_dir(fixtures_dir())
+        .unwrap()
+}
+
+#[test]
+fn test_deeds_connect_portfolios() {
+    // BOOP JONES bought one of QUUX QUUX's buildings, giving QUUX QUUX's
+    // business address.
+    let ranking = String::from_utf8(run_hpd(&["--acris", "acris", "ranking"])).unwrap();
+    assert_eq!(
+        ranking,
+        "1. BOOP JONES's portfolio - 7 buildings
+2. HARBOR VIEW's portfolio - 2 buildings
+3. ZIP ZAP's portfolio - 2 buildings
+"
+    );
+}
+
+#[test]
+fn test_only_current_owners_are_linked() {
+    // ZIP ZAP sold the building, NOAH NOBODY's lot isn't registered with
+    // HPD, and HARBOR VIEW is only a party to a mortgage.
+    let dataset = dataset_with_deeds();
+    assert!(dataset.hpd.name_node("NOAH NOBODY").is_none());
+    let portfolio = dataset.portfolio_with_name("ZIP ZAP").unwrap();
+    assert_eq!(portfolio.building_count(&dataset.regs, CountBy::Bbls), 2);
+    let portfolio = dataset.portfolio_with_name("HARBOR VIEW").unwrap();
+    assert_eq!(portfolio.building_count(&dataset.regs, CountBy::Bbls), 2);
+}
+
+#[test]
+fn test_corporate_grantees_need_include_corps() {
+    let dataset = dataset_with_deeds();
+    assert!(dataset.hpd.name_node("QUUX ACQUISITION LLC").is_none());
+
+    let deeds = AcrisDeeds::read_dir(fixtures_dir().join("acris")).unwrap();
+    let dataset = DatasetBuilder::new()
+        .include_corps(true)
+        .deeds(deeds)
+        .read_dir(fixtures_dir())
+        .unwrap();
+    assert!(dataset.hpd.name_node("QUUX ACQUISITION LLC").is_some());
+}
+
+#[test]
+fn test_paths_show_deed_links() {
+    let api = ApiServer::new(dataset_with_deeds());
+    let (status, body) = api.respond("/path?from=BOOP+JONES&to=QUUX+QUUX");
+    assert_eq!(status, 200);
+    let json: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["links"], json!(["deed", "registration"]));
+
+    let dataset = dataset_with_deeds();
+    let hpd = &dataset.hpd;
+    let from = hpd.find_name("BOOP JONES").unwrap();
+    let to = hpd.find_name("QUUX QUUX").unwrap();
+    let path = hpd.shortest_path(from, to).unwrap();
+    assert_eq!(
+        hpd.path_to_string(&path),
+        "BOOP JONES =deed=> 7 QUUX LANE, QUEENS NY -> QUUX QUUX"
+    );
+
+    // `longpaths` describes its paths the same way.
+    let long_path = LongPath::new(hpd, &path);
+    assert_eq!(long_path.describe(), hpd.path_to_string(&path));
+    assert_eq!(
+        serde_json::to_value(&long_path).unwrap()["links"],
+        json!(["deed", "registration"])
+    );
+}
+
+#[test]
+fn test_portfolio_json_includes_deeds() {
+    let json: Value =
+        serde_json::from_slice(&run_hpd(&["--acris", "acris", "json", "QUUX QUUX"])).unwrap();
+    assert_eq!(json["options"]["link_deeds"], true);
+    let deed_edges: Vec<&Value> = json["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|edge| edge.get("deeds").is_some())
+        .collect();
+    assert_eq!(deed_edges.len(), 1);
+    assert_eq!(deed_edges[0]["reg_contacts"], 0);
+    assert_eq!(
+        deed_edges[0]["deeds"],
+        json!([{
+            "document_id": "2018060100001001",
+            "doc_type": "DEED",
+            "document_date": "2018-06-01",
+            "bbls": ["4003000040"],
+        }])
+    );
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use std::fs::File;
+
+use common::fixtures_dir;
+use hpd::dataset::{CONTACTS_FILENAME, REGISTRATIONS_FILENAME};
+use hpd::{
+    CountBy, Dataset, DatasetBuilder, HpdError, Portfolio, PortfolioIdSnapshot, PortfolioMap,
+};
+
+fn dataset() -> Dataset {
+    DatasetBuilder::new().read_dir(fixtures_dir()).unwrap()
+}
+
+#[test]
+fn test_read_works_like_read_dir() {
+    let dir = fixtures_dir();
+    let from_readers = DatasetBuilder::new()
+        .read(
+            File::open(dir.join(REGISTRATIONS_FILENAME)).unwrap(),
+            File::open(dir.join(CONTACTS_FILENAME)).unwrap(),
+        )
+        .unwrap();
+    let from_dir = dataset();
+    assert_eq!(
+        from_readers.hpd.name_nodes.len(),
+        from_dir.hpd.name_nodes.len()
+    );
+    assert_eq!(
+        from_readers.portfolios().id_snapshot(&from_readers.regs),
+        from_dir.portfolios().id_snapshot(&from_dir.regs)
+    );
+}
+
+#[test]
+fn test_portfolio_queries_work() {
+    let dataset = dataset();
+    let portfolio = dataset.portfolio_with_name("BLAP SMITH").unwrap();
+    assert_eq!(portfolio.name().as_str(), "BOOP JONES's portfolio");
+    assert_eq!(
+        portfolio.building_count(&dataset.regs, CountBy::Registrations),
+        5
+    );
+    assert_eq!(
+        portfolio.names().into_iter().collect::<Vec<_>>(),
+        vec!["BLAP SMITH", "BOOP JONES", "FLIM FLAM"]
+    );
+
+    let ranking =
+        dataset
+            .portfolios()
+            .rank_by_building_count(&dataset.regs, CountBy::Registrations, 2);
+    let names: Vec<_> = ranking.iter().map(|(p, _)| p.name()).collect();
+    assert_eq!(names.len(), 4);
+    assert_eq!(names[0].as_str(), "BOOP JONES's portfolio");
+}
+
+#[test]
+fn test_include_corps_adds_corporation_names() {
+    let dataset = DatasetBuilder::new()
+        .include_corps(true)
+        .read_dir(fixtures_dir())
+        .unwrap();
+    assert!(dataset.find_name("BOOP LLC").is_ok());
+    assert!(matches!(
+        self::dataset().find_name("BOOP LLC"),
+        Err(HpdError::NotFound { .. })
+    ));
+}
+
+#[test]
+fn test_lookups_report_ambiguous_matches() {
+    match dataset().find_name("ZAP") {
+        Err(HpdError::AmbiguousMatch { candidates, .. }) => {
+            assert_eq!(candidates, vec!["ZIP ZAP", "ZORP ZAP"]);
+        }
+        _ => panic!("expected an ambiguous match"),
+    }
+}
+
+#[test]
+fn test_previous_ids_are_carried_forward() {
+    let old = dataset();
+    let snapshot = PortfolioIdSnapshot::from_portfolios(&[(
+        std::sync::Arc::new("my-id".to_owned()),
+        old.portfolio_with_name("BOOP JONES")
+            .unwrap()
+            .bbls(&old.regs),
+    )]);
+    let new = DatasetBuilder::new()
+        .previous_ids(snapshot)
+        .read_dir(fixtures_dir())
+        .unwrap();
+    assert_eq!(
+        new.portfolio_with_name("BOOP JONES").unwrap().id().as_str(),
+        "my-id"
+    );
+}
+
+#[test]
+fn test_serializers_work() {
+    let dataset = dataset();
+    let portfolio = dataset.portfolio_with_name("BOOP JONES").unwrap();
+    let json: serde_json::Value = serde_json::from_str(&portfolio.json(
+        &dataset.regs,
+        CountBy::Registrations,
+        dataset.options(),
+    ))
+    .unwrap();
+    assert_eq!(json["title"], "BOOP JONES's portfolio");
+    assert!(portfolio.dot_graph().contains("graph {"));
+}
+
+#[test]
+fn test_types_can_be_shared_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Dataset>();
+    assert_send_sync::<PortfolioMap>();
+    assert_send_sync::<Portfolio>();
+}
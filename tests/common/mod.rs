@@ -0,0 +1,32 @@
+// Not every test file uses every helper.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+pub fn fixtures_dir() -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures"]
+        .iter()
+        .collect()
+}
+
+/// The time hpd is told it is, so that timestamps in its output are
+/// reproducible.
+pub const SOURCE_DATE_EPOCH: &str = "1600000000";
+
+pub fn run_hpd_in<P: AsRef<Path>>(dir: P, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hpd"))
+        .args(args)
+        .env("SOURCE_DATE_EPOCH", SOURCE_DATE_EPOCH)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// Runs hpd on the fixture data, asserting that it succeeds and returning
+/// its standard output.
+pub fn run_hpd(args: &[&str]) -> Vec<u8> {
+    let output = run_hpd_in(fixtures_dir(), args);
+    assert!(output.status.success(), "hpd {:?} failed", args);
+    output.stdout
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::{fixtures_dir, run_hpd, run_hpd_in};
+use serde_json::Value;
+
+const CONDITIONS_ARGS: &[&str] = &[
+    "--pluto",
+    "pluto.csv",
+    "--violations",
+    "violations.csv",
+    "--complaints",
+    "complaints.csv",
+    "--complaints-since",
+    "2020-01-01",
+];
+
+fn run_hpd_with_conditions(args: &[&str]) -> Vec<u8> {
+    let args: Vec<&str> = CONDITIONS_ARGS.iter().chain(args).copied().collect();
+    run_hpd(&args)
+}
+
+fn run_hpd_json(args: &[&str]) -> Value {
+    serde_json::from_slice(&run_hpd_with_conditions(args)).unwrap()
+}
+
+#[test]
+fn test_info_describes_conditions() {
+    let info = String::from_utf8(run_hpd_with_conditions(&["info", "BOOP JONES"])).unwrap();
+    assert!(info.contains("It has 4 open class C violations (0.19 per unit).\n"));
+    assert!(info.contains("It has had 3 complaints since 2020-01-01 (0.60 per building).\n"));
+}
+
+#[test]
+fn test_portfolio_json_includes_conditions() {
+    let json = run_hpd_json(&["json", "BOOP JONES"]);
+    let conditions = &json["conditions"];
+    assert_eq!(conditions["open_class_c_violations"], 4);
+    let per_unit = conditions["open_class_c_violations_per_unit"]
+        .as_f64()
+        .unwrap();
+    assert!((per_unit - 4.0 / 21.0).abs() < 1e-9);
+    assert_eq!(conditions["recent_complaints"], 3);
+    assert_eq!(conditions["recent_complaints_per_building"], 0.6);
+    assert_eq!(conditions["complaints_since"], "2020-01-01");
+}
+
+#[test]
+fn test_ranking_includes_conditions() {
+    let ranking = String::from_utf8(run_hpd_with_conditions(&["ranking", "-f", "csv"])).unwrap();
+    let mut lines = ranking.lines();
+    assert!(lines.next().unwrap().ends_with(
+        ",residential_units,open_class_c_violations,open_class_c_violations_per_unit,\
+         recent_complaints,recent_complaints_per_building,local_bridges,top_addresses,top_names"
+    ));
+    assert!(lines
+        .next()
+        .unwrap()
+        .contains(",BOOP JONES's portfolio,5,0,2,3,0,0,21,4,0.19047619047619047,3,0.6,3,"));
+
+    let ranking = String::from_utf8(run_hpd_with_conditions(&["ranking"])).unwrap();
+    assert!(ranking.starts_with(
+        "1. BOOP JONES's portfolio - 5 buildings, 21 units, \
+         4 open class C violations, 3 recent complaints\n"
+    ));
+}
+
+#[test]
+fn test_ranking_csv_leaves_unknown_conditions_blank() {
+    let ranking = String::from_utf8(run_hpd(&[
+        "--violations",
+        "violations.csv",
+        "ranking",
+        "-f",
+        "csv",
+    ]))
+    .unwrap();
+    let mut lines = ranking.lines();
+    assert!(lines.next().unwrap().ends_with(
+        ",staten_island,open_class_c_violations,open_class_c_violations_per_unit,\
+         recent_complaints,recent_complaints_per_building,local_bridges,top_addresses,top_names"
+    ));
+    assert!(lines.next().unwrap().contains(",0,0,4,,,,3,"));
+}
+
+#[test]
+fn test_complaints_default_to_the_last_year() {
+    // Every complaint in the fixture is from 2019 or 2020.
+    let json: Value = serde_json::from_slice(&run_hpd(&[
+        "--complaints",
+        "complaints.csv",
+        "json",
+        "ZIP ZAP",
+    ]))
+    .unwrap();
+    assert_eq!(json["conditions"]["recent_complaints"], 0);
+    assert_eq!(json["conditions"]["open_class_c_violations"], Value::Null);
+}
+
+#[test]
+fn test_conditions_are_only_included_when_loaded() {
+    let json: Value = serde_json::from_slice(&run_hpd(&["json", "BOOP JONES"])).unwrap();
+    assert_eq!(json.get("conditions"), None);
+    let ranking = String::from_utf8(run_hpd(&["ranking", "-f", "csv"])).unwrap();
+    assert!(!ranking.contains("violations"));
+}
+
+#[test]
+fn test_complaints_since_requires_complaints() {
+    let output = run_hpd_in(fixtures_dir(), &["--complaints-since", "2020-01-01", "ids"]);
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{fixtures_dir, run_hpd_in};
+use serde_json::Value;
+
+/// Returns the number of buildings in the only portfolio of the `count_by`
+/// fixture, which has:
+///
+/// * a condo, registered as its billing lot and two of its unit lots, all
+///   with the same BIN;
+/// * another condo on the same block, registered as its billing lot and one
+///   of its unit lots, both with another BIN;
+/// * a registration for two buildings on two neighboring lots, one of
+///   which is registered again;
+/// * another building on the first of those lots.
+fn building_count(args: &[&str]) -> Value {
+    let output = run_hpd_in(
+        fixtures_dir().join("count_by"),
+        &[args, &["ranking", "-f", "json"]].concat(),
+    );
+    assert!(output.status.success());
+    let ranking: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(ranking.as_array().unwrap().len(), 1);
+    ranking[0]["buildings"].clone()
+}
+
+#[test]
+fn test_registrations_are_counted_by_default() {
+    let output = run_hpd_in(fixtures_dir().join("count_by"), &["ranking"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "1. CONDO KING's portfolio - 8 buildings\n"
+    );
+    assert_eq!(building_count(&["--count-by", "registrations"]), 8);
+}
+
+#[test]
+fn test_bins_are_counted_once() {
+    assert_eq!(building_count(&["--count-by", "bins"]), 5);
+}
+
+#[test]
+fn test_bbls_are_counted_once() {
+    assert_eq!(building_count(&["--count-by", "bbls"]), 7);
+}
+
+#[test]
+fn test_condo_lots_are_collapsed_by_condo_number() {
+    // The two condos, and the two lots in Brooklyn.
+    assert_eq!(
+        building_count(&["--pluto", "pluto.csv", "--count-by", "tax-lots"]),
+        4
+    );
+}
+
+#[test]
+fn test_condo_lots_are_not_collapsed_without_pluto() {
+    assert_eq!(building_count(&["--count-by", "tax-lots"]), 7);
+}
+
+#[test]
+fn test_mode_is_recorded_in_json() {
+    let output = run_hpd_in(
+        fixtures_dir().join("count_by"),
+        &["--count-by", "bins", "json", "CONDO KING"],
+    );
+    assert!(output.status.success());
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["options"]["count_by"], "bins");
+}
+
+#[test]
+fn test_unknown_mode_is_rejected() {
+    let output = run_hpd_in(fixtures_dir(), &["--count-by", "floors", "ranking"]);
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::run_hpd;
+
+#[test]
+fn test_output_is_identical_across_runs() {
+    let commands: &[&[&str]] = &[
+        &["json", "BOOP JONES"],
+        &["dot", "BOOP JONES"],
+        &["ranking"],
+        &["--include-corps", "ranking"],
+        &["info", "ZIP ZAP"],
+        &["longpaths", "-m", "1"],
+        &["ids"],
+        &["ranking", "-f", "csv"],
+        &["info", "-f", "json", "BOOP JONES"],
+        &["longpaths", "-m", "1", "-f", "json"],
+        &["export", "-f", "gexf"],
+        &["export", "-f", "cytoscape", "BOOP JONES"],
+        &["buildings", "BOOP JONES"],
+    ];
+
+    for args in commands {
+        let first = run_hpd(args);
+        assert!(!first.is_empty(), "hpd {:?} printed nothing", args);
+        for _ in 0..4 {
+            assert_eq!(run_hpd(args), first, "hpd {:?} is nondeterministic", args);
+        }
+    }
+}
+
+#[test]
+fn test_ranking_ties_are_sorted_by_name() {
+    let ranking = String::from_utf8(run_hpd(&["ranking"])).unwrap();
+    assert_eq!(
+        ranking,
+        "1. BOOP JONES's portfolio - 5 buildings
+2. HARBOR VIEW's portfolio - 2 buildings
+3. QUUX QUUX's portfolio - 2 buildings
+4. ZIP ZAP's portfolio - 2 buildings
+"
+    );
+}
+
+#[test]
+fn test_output_does_not_depend_on_thread_count() {
+    for args in &[
+        &["ranking"][..],
+        &["ids"][..],
+        &["json", "BOOP JONES"][..],
+        &["export"][..],
+    ] {
+        let single: Vec<&str> = ["--threads", "1"]
+            .iter()
+            .chain(args.iter())
+            .copied()
+            .collect();
+        let many: Vec<&str> = ["--threads", "4"]
+            .iter()
+            .chain(args.iter())
+            .copied()
+            .collect();
+        assert_eq!(
+            run_hpd(&single),
+            run_hpd(&many),
+            "hpd {:?} depends on thread count",
+            args
+        );
+    }
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{fixtures_dir, run_hpd_in};
+
+fn exit_code_and_stderr(args: &[&str]) -> (i32, String) {
+    let output = run_hpd_in(fixtures_dir(), args);
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_unknown_name_is_not_found() {
+    let (code, stderr) = exit_code_and_stderr(&["json", "NOBODY"]);
+    assert_eq!(code, 3);
+    assert!(stderr.contains("Unable to find a name matching 'NOBODY'."));
+}
+
+#[test]
+fn test_partial_name_matching_several_names_is_ambiguous() {
+    let (code, stderr) = exit_code_and_stderr(&["dot", "ZAP"]);
+    assert_eq!(code, 4);
+    assert!(stderr.contains("ZIP ZAP\n  ZORP ZAP"));
+}
+
+#[test]
+fn test_missing_data_is_io_error() {
+    let output = run_hpd_in(env!("CARGO_MANIFEST_DIR"), &["ranking"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Multiple_Dwelling_Registrations.csv"));
+}
+
+#[test]
+fn test_corrupt_data_is_csv_error() {
+    let output = run_hpd_in(
+        fixtures_dir(),
+        &["--previous-ids", "Registration_Contacts.csv", "ranking"],
+    );
+    assert_eq!(output.status.code(), Some(6));
+}
+
+#[test]
+fn test_unknown_bbl_is_not_found() {
+    let (code, stderr) = exit_code_and_stderr(&["json", "--bbl", "Queens 1 1"]);
+    assert_eq!(code, 3);
+    assert!(stderr.contains("4000010001"));
+}
+
+#[test]
+fn test_malformed_bbl_is_invalid_argument() {
+    let (code, stderr) = exit_code_and_stderr(&["dot", "--bbl", "3-1234-123456"]);
+    assert_eq!(code, 1);
+    assert!(stderr.contains("Invalid BBL: 3-1234-123456"));
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{fixtures_dir, run_hpd, run_hpd_in};
+use serde_json::Value;
+
+const EVICTIONS_ARGS: &[&str] = &[
+    "--pluto",
+    "pluto.csv",
+    "--evictions",
+    "evictions.csv",
+    "--evictions-since",
+    "2020-01-01",
+];
+
+fn run_hpd_with_evictions(args: &[&str]) -> String {
+    let args: Vec<&str> = EVICTIONS_ARGS.iter().chain(args).copied().collect();
+    String::from_utf8(run_hpd(&args)).unwrap()
+}
+
+#[test]
+fn test_ranking_by_evictions_works() {
+    assert_eq!(
+        run_hpd_with_evictions(&["ranking", "--rank-by", "evictions"]),
+        "1. BOOP JONES's portfolio - 5 buildings, 21 units, 3 evictions
+2. QUUX QUUX's portfolio - 2 buildings, 40 units, 1 evictions
+3. HARBOR VIEW's portfolio - 2 buildings, 2 units, 0 evictions
+4. ZIP ZAP's portfolio - 2 buildings, 210 units, 0 evictions
+"
+    );
+    assert_eq!(
+        run_hpd_with_evictions(&["ranking", "--sort-by", "evictions"]),
+        run_hpd_with_evictions(&["ranking", "--rank-by", "evictions"])
+    );
+}
+
+#[test]
+fn test_ranking_by_evictions_requires_evictions() {
+    let output = run_hpd_in(fixtures_dir(), &["ranking", "--rank-by", "evictions"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_info_counts_evictions_by_bbl_and_bin() {
+    // Two evictions are matched by BBL and one, which has no BBL, by BIN.
+    let info = run_hpd_with_evictions(&["info", "BOOP JONES"]);
+    assert!(info
+        .contains("It has had 3 evictions since 2020-01-01 (0.14 per unit, 0.60 per building).\n"));
+
+    let info: Value = serde_json::from_str(&run_hpd_with_evictions(&[
+        "info",
+        "-f",
+        "json",
+        "BOOP JONES",
+    ]))
+    .unwrap();
+    assert_eq!(info["portfolio"]["evictions"]["evictions"], 3);
+    assert_eq!(info["portfolio"]["evictions"]["since"], "2020-01-01");
+}
+
+#[test]
+fn test_ranking_csv_includes_evictions() {
+    let ranking = String::from_utf8(run_hpd(&[
+        "--evictions",
+        "evictions.csv",
+        "--evictions-since",
+        "2020-01-01",
+        "ranking",
+        "-f",
+        "csv",
+    ]))
+    .unwrap();
+    let mut lines = ranking.lines();
+    assert!(lines
+        .next()
+        .unwrap()
+        .contains(",staten_island,evictions,evictions_per_unit,evictions_per_building,"));
+    assert!(lines.next().unwrap().contains(",0,2,3,0,0,3,,0.6,"));
+}
+
+#[test]
+fn test_evictions_default_to_the_last_year() {
+    // Every eviction in the fixture is from 2019 or 2020.
+    let info: Value = serde_json::from_slice(&run_hpd(&[
+        "--evictions",
+        "evictions.csv",
+        "info",
+        "-f",
+        "json",
+        "BOOP JONES",
+    ]))
+    .unwrap();
+    assert_eq!(info["portfolio"]["evictions"]["evictions"], 0);
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use common::run_hpd;
+use serde_json::Value;
+
+fn run_hpd_str(args: &[&str]) -> String {
+    String::from_utf8(run_hpd(args)).unwrap()
+}
+
+#[test]
+fn test_cytoscape_export_of_portfolio_works() {
+    let json: Value =
+        serde_json::from_slice(&run_hpd(&["export", "--format", "cytoscape", "BOOP JONES"]))
+            .unwrap();
+    let portfolio: Value = serde_json::from_slice(&run_hpd(&["json", "BOOP JONES"])).unwrap();
+
+    let nodes = json["elements"]["nodes"].as_array().unwrap();
+    let edges = json["elements"]["edges"].as_array().unwrap();
+    assert_eq!(nodes.len(), portfolio["nodes"].as_array().unwrap().len());
+    assert_eq!(edges.len(), portfolio["edges"].as_array().unwrap().len());
+    assert!(nodes
+        .iter()
+        .all(|node| node["data"]["portfolio"] == portfolio["id"]));
+    assert!(nodes
+        .iter()
+        .any(|node| node["data"]["label"] == "BOOP JONES" && node["data"]["type"] == "name"));
+    assert!(edges
+        .iter()
+        .all(|edge| edge["data"]["weight"].as_u64() >= Some(1)));
+    assert!(edges.iter().any(|edge| edge["data"]["bbls"]
+        .as_array()
+        .unwrap()
+        .contains(&Value::from("3012340010"))));
+}
+
+#[test]
+fn test_whole_graph_export_includes_every_portfolio() {
+    let json: Value = serde_json::from_slice(&run_hpd(&["export", "-f", "cytoscape"])).unwrap();
+    let nodes = json["elements"]["nodes"].as_array().unwrap();
+    let ids = run_hpd_str(&["ids"]);
+    let mut portfolio_ids: Vec<&str> = ids
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').next().unwrap())
+        .collect();
+    portfolio_ids.dedup();
+
+    for id in portfolio_ids {
+        assert!(
+            nodes.iter().any(|node| node["data"]["portfolio"] == id),
+            "portfolio {} is missing",
+            id
+        );
+    }
+}
+
+#[test]
+fn test_xml_exports_agree_with_cytoscape_export() {
+    let json: Value = serde_json::from_slice(&run_hpd(&["export", "-f", "cytoscape"])).unwrap();
+    let node_count = json["elements"]["nodes"].as_array().unwrap().len();
+    let edge_count = json["elements"]["edges"].as_array().unwrap().len();
+
+    let graphml = run_hpd_str(&["export", "-f", "graphml"]);
+    assert!(graphml.contains("<graph id=\"G\" edgedefault=\"undirected\">"));
+    assert_eq!(graphml.matches("<node ").count(), node_count);
+    assert_eq!(graphml.matches("<edge ").count(), edge_count);
+    assert!(graphml.contains("<data key=\"label\">BOOP JONES</data>"));
+
+    let gexf = run_hpd_str(&["export", "-f", "gexf"]);
+    assert!(gexf.contains("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">"));
+    assert_eq!(gexf.matches("<node ").count(), node_count);
+    assert_eq!(gexf.matches("<edge ").count(), edge_count);
+    assert!(gexf.contains("label=\"BOOP JONES\""));
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("hpd-{}-{}", name, std::process::id()))
+}
+
+fn read_csv(path: std::path::PathBuf) -> Vec<csv::StringRecord> {
+    csv::Reader::from_path(path)
+        .unwrap()
+        .records()
+        .map(|record| record.unwrap())
+        .collect()
+}
+
+#[test]
+fn test_export_tables_works() {
+    let dir = temp_dir("tables");
+    run_hpd(&["export-tables", dir.to_str().unwrap()]);
+    let nodes = read_csv(dir.join("nodes.csv"));
+    let edges = read_csv(dir.join("edges.csv"));
+    let edge_regs = read_csv(dir.join("edge_registrations.csv"));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let json: Value = serde_json::from_slice(&run_hpd(&["export", "-f", "cytoscape"])).unwrap();
+    assert_eq!(
+        nodes.len(),
+        json["elements"]["nodes"].as_array().unwrap().len()
+    );
+    assert_eq!(
+        edges.len(),
+        json["elements"]["edges"].as_array().unwrap().len()
+    );
+
+    let boop = nodes.iter().find(|row| &row[2] == "BOOP JONES").unwrap();
+    assert_eq!(&boop[1], "name");
+    let degree: usize = boop[4].parse().unwrap();
+    let boop_edges = edges
+        .iter()
+        .filter(|row| row[1] == boop[0] || row[2] == boop[0])
+        .count();
+    assert_eq!(degree, boop_edges);
+
+    let reg_contacts: usize = edges
+        .iter()
+        .map(|row| row[3].parse::<usize>().unwrap())
+        .sum();
+    assert_eq!(edge_regs.len(), reg_contacts);
+    assert!(edge_regs.iter().any(|row| &row[3] == "3012340010"));
+}
+
+#[test]
+#[cfg(not(feature = "parquet"))]
+fn test_export_tables_without_parquet_support_fails() {
+    let output = common::run_hpd_in(
+        common::fixtures_dir(),
+        &[
+            "export-tables",
+            "-f",
+            "parquet",
+            temp_dir("no-parquet").to_str().unwrap(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+#[cfg(feature = "parquet")]
+fn test_export_tables_as_parquet_works() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let dir = temp_dir("parquet");
+    run_hpd(&["export-tables", "-f", "parquet", dir.to_str().unwrap()]);
+    let row_count = |name: &str| {
+        let file = std::fs::File::open(dir.join(name)).unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        reader.metadata().file_metadata().num_rows() as usize
+    };
+    let counts = (
+        row_count("nodes.parquet"),
+        row_count("edges.parquet"),
+        row_count("edge_registrations.parquet"),
+    );
+    run_hpd(&["export-tables", dir.to_str().unwrap()]);
+    let csv_counts = (
+        read_csv(dir.join("nodes.csv")).len(),
+        read_csv(dir.join("edges.csv")).len(),
+        read_csv(dir.join("edge_registrations.csv")).len(),
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(counts, csv_counts);
+}
+
+#[test]
+fn test_export_sqlite_works() {
+    let path = temp_dir("sqlite").with_extension("db");
+    let path_str = path.to_str().unwrap();
+    run_hpd(&["export-sqlite", path_str]);
+    // Exporting again should replace the database rather than fail.
+    run_hpd(&["export-sqlite", path_str]);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let (name, buildings): (String, u32) = conn
+        .query_row(
+            "SELECT DISTINCT p.name, p.building_count
+             FROM registrations r
+             JOIN contacts c ON c.registration_id = r.id
+             JOIN edges e ON e.id = c.edge_id
+             JOIN nodes n ON n.id = e.from_node
+             JOIN portfolios p ON p.id = n.portfolio_id
+             WHERE r.bbl = '3012340010'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(name, "BOOP JONES's portfolio");
+    assert_eq!(buildings, 5);
+
+    let count = |sql: &str| -> u32 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+    assert_eq!(
+        count("SELECT SUM(reg_contact_count) FROM edges"),
+        count("SELECT COUNT(*) FROM contacts")
+    );
+    assert_eq!(
+        count("SELECT COUNT(*) FROM nodes WHERE label = 'BOOP JONES' AND kind = 'name'"),
+        1
+    );
+    assert_eq!(
+        count("SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name IN ('registrations_bbl', 'registrations_bin', 'nodes_label')"),
+        3
+    );
+
+    drop(conn);
+    std::fs::remove_file(&path).unwrap();
+}
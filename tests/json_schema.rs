@@ -0,0 +1,106 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::{fixtures_dir, run_hpd};
+use hpd::conditions::{ComplaintCounts, ViolationCounts};
+use hpd::dataset::open_csv;
+use hpd::json::portfolio_json_schema;
+use hpd::pluto::PlutoMap;
+use hpd::stabilization::StabilizedUnitCounts;
+use hpd::{CountBy, DatasetBuilder};
+use serde_json::{json, Value};
+
+fn checked_in_schema() -> Value {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("schema")
+        .join("portfolio.schema.json");
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn test_checked_in_schema_is_up_to_date() {
+    let generated: Value = serde_json::from_str(&portfolio_json_schema()).unwrap();
+    assert!(
+        generated == checked_in_schema(),
+        "schema/portfolio.schema.json is out of date; regenerate it with \
+         'cargo run -- json-schema > schema/portfolio.schema.json'"
+    );
+}
+
+#[test]
+fn test_portfolio_json_matches_schema() {
+    let validator = jsonschema::validator_for(&checked_in_schema()).unwrap();
+    for (include_corps, pluto, conditions) in [
+        (false, false, false),
+        (true, false, false),
+        (false, true, false),
+        (false, false, true),
+        (false, true, true),
+    ] {
+        let mut builder = DatasetBuilder::new().include_corps(include_corps);
+        if pluto {
+            let path = fixtures_dir().join("pluto.csv");
+            builder = builder.pluto(PlutoMap::from_csv(open_csv(path).unwrap()).unwrap());
+        }
+        if conditions {
+            let path = fixtures_dir().join("violations.csv");
+            builder =
+                builder.violations(ViolationCounts::from_csv(open_csv(path).unwrap()).unwrap());
+            let path = fixtures_dir().join("complaints.csv");
+            let since = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+            builder = builder
+                .complaints(ComplaintCounts::from_csv(open_csv(path).unwrap(), since).unwrap());
+            let path = fixtures_dir().join("rentstab.csv");
+            builder = builder
+                .stabilized_units(StabilizedUnitCounts::from_csv(open_csv(path).unwrap()).unwrap());
+        }
+        let dataset = builder.read_dir(fixtures_dir()).unwrap();
+        for portfolio in dataset.portfolios().iter() {
+            let json = portfolio.json(&dataset.regs, CountBy::default(), dataset.options());
+            let json: Value = serde_json::from_str(&json).unwrap();
+            let errors: Vec<String> = validator
+                .iter_errors(&json)
+                .map(|e| e.to_string())
+                .collect();
+            assert!(errors.is_empty(), "{}: {:?}", portfolio.name(), errors);
+            assert_eq!(json["options"]["include_corps"], include_corps);
+            assert_eq!(json["options"]["pluto"], pluto);
+            assert_eq!(json["options"]["violations"], conditions);
+            assert_eq!(
+                json["options"]["complaints_since"],
+                if conditions {
+                    json!("2020-01-01")
+                } else {
+                    Value::Null
+                }
+            );
+        }
+    }
+}
+
+#[test]
+fn test_portfolio_json_lists_every_bbl() {
+    let json: Value = serde_json::from_slice(&run_hpd(&["json", "BOOP JONES"])).unwrap();
+    assert_eq!(json["schema_version"], 2);
+    assert_eq!(json["generated_at"], "2020-09-13T12:26:40Z");
+    assert_eq!(json["options"]["max_expiration_age_days"], 90);
+    assert_eq!(json["options"]["count_by"], "registrations");
+    assert_eq!(json["options"]["evictions_since"], Value::Null);
+
+    let buildings: Vec<&Value> = json["buildings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|building| &building["bbl"])
+        .collect();
+    assert_eq!(buildings.len(), 5);
+    let mut edge_bbls: Vec<&Value> = json["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|edge| edge["bbls"].as_array().unwrap())
+        .collect();
+    edge_bbls.sort_by_key(|bbl| bbl.as_str());
+    edge_bbls.dedup();
+    assert_eq!(edge_bbls, buildings);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{fixtures_dir, run_hpd, run_hpd_in};
+use serde_json::{json, Value};
+
+fn run_hpd_json(args: &[&str]) -> Value {
+    serde_json::from_slice(&run_hpd(args)).unwrap()
+}
+
+#[test]
+fn test_ranking_by_units_works() {
+    let ranking = String::from_utf8(run_hpd(&[
+        "--pluto",
+        "pluto.csv",
+        "ranking",
+        "--rank-by",
+        "units",
+    ]))
+    .unwrap();
+    assert_eq!(
+        ranking,
+        "1. ZIP ZAP's portfolio - 2 buildings, 210 units
+2. QUUX QUUX's portfolio - 2 buildings, 40 units
+3. BOOP JONES's portfolio - 5 buildings, 21 units
+4. HARBOR VIEW's portfolio - 2 buildings, 2 units
+"
+    );
+
+    let ranking = run_hpd_json(&["--pluto", "pluto.csv", "ranking", "-f", "json"]);
+    assert_eq!(ranking[0]["name"], "BOOP JONES's portfolio");
+    assert_eq!(ranking[0]["residential_units"], 21);
+}
+
+#[test]
+fn test_ranking_by_units_requires_pluto() {
+    let output = run_hpd_in(fixtures_dir(), &["ranking", "--rank-by", "units"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_units_are_only_included_with_pluto() {
+    let info = run_hpd_json(&["info", "-f", "json", "BOOP JONES"]);
+    assert_eq!(info["portfolio"].get("residential_units"), None);
+    let json = run_hpd_json(&["json", "BOOP JONES"]);
+    assert_eq!(json.get("residential_units"), None);
+    assert_eq!(json["buildings"][0].get("pluto"), None);
+}
+
+#[test]
+fn test_portfolio_output_includes_pluto() {
+    let info = run_hpd_json(&["--pluto", "pluto.csv", "info", "-f", "json", "BOOP JONES"]);
+    assert_eq!(info["portfolio"]["residential_units"], 21);
+
+    let json = run_hpd_json(&["--pluto", "pluto.csv", "json", "BOOP JONES"]);
+    assert_eq!(json["residential_units"], 21);
+    let buildings = json["buildings"].as_array().unwrap();
+    assert_eq!(
+        buildings[0]["pluto"],
+        json!({"residential_units": 9, "year_built": 1927, "building_class": "D7", "condo_number": null})
+    );
+    // 2023450002 isn't in the fixture, so it's left out of the total.
+    assert_eq!(buildings[1]["bbl"], "2023450002");
+    assert_eq!(buildings[1].get("pluto"), None);
+    assert_eq!(
+        buildings[4]["pluto"],
+        json!({"residential_units": 6, "year_built": null, "building_class": "C2", "condo_number": null})
+    );
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::OnceLock;
+
+use common::fixtures_dir;
+use hpd::server::ApiServer;
+use hpd::DatasetBuilder;
+use serde_json::Value;
+
+/// Starts a server on the fixture data (once, for all tests) and returns
+/// its address.
+fn server_addr() -> SocketAddr {
+    static ADDR: OnceLock<SocketAddr> = OnceLock::new();
+    *ADDR.get_or_init(|| {
+        let api = ApiServer::new(DatasetBuilder::new().read_dir(fixtures_dir()).unwrap());
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || api.serve(&server));
+        addr
+    })
+}
+
+fn get(path: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(server_addr()).unwrap();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[test]
+fn test_portfolio_by_name_works() {
+    let (status, json) = get("/portfolio?name=BLAP+SMITH");
+    assert_eq!(status, 200);
+    assert_eq!(json["title"], "BOOP JONES's portfolio");
+    assert!(json["nodes"].as_array().unwrap().len() > 3);
+
+    let (status, json) = get("/portfolio?name=blap+smith");
+    assert_eq!(status, 200);
+    assert_eq!(json["title"], "BOOP JONES's portfolio");
+}
+
+#[test]
+fn test_portfolio_by_bbl_works() {
+    let (status, json) = get("/portfolio?bbl=3012340010");
+    assert_eq!(status, 200);
+    assert_eq!(json["title"], "BOOP JONES's portfolio");
+
+    assert_eq!(get("/portfolio?bbl=1000000001").0, 404);
+    assert_eq!(get("/portfolio?bbl=nope").0, 400);
+}
+
+#[test]
+fn test_portfolio_errors_are_json() {
+    let (status, json) = get("/portfolio?name=ZAP");
+    assert_eq!(status, 400);
+    assert_eq!(
+        json["candidates"],
+        serde_json::json!(["ZIP ZAP", "ZORP ZAP"])
+    );
+
+    let (status, json) = get("/portfolio?name=NOBODY");
+    assert_eq!(status, 404);
+    assert_eq!(json["error"], "Unable to find a name matching 'NOBODY'.");
+
+    assert_eq!(get("/portfolio").0, 400);
+    assert_eq!(get("/nope").0, 404);
+}
+
+#[test]
+fn test_search_works() {
+    let (status, json) = get("/search?q=zap");
+    assert_eq!(status, 200);
+    let names: Vec<&str> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["ZIP ZAP", "ZORP ZAP"]);
+    assert_eq!(json[0]["portfolio_id"], json[1]["portfolio_id"]);
+}
+
+#[test]
+fn test_path_works() {
+    let (status, json) = get("/path?from=BLAP%20SMITH&to=FLIM+FLAM");
+    assert_eq!(status, 200);
+    let path = json["path"].as_array().unwrap();
+    assert_eq!(path.first().unwrap()["label"], "BLAP SMITH");
+    assert_eq!(path.last().unwrap()["label"], "FLIM FLAM");
+    assert_eq!(path[1]["kind"], "address");
+    assert_eq!(json["length"], path.len() - 1);
+    assert_eq!(
+        json["links"],
+        serde_json::json!(["registration", "registration"])
+    );
+
+    let (status, json) = get("/path?from=blap+smith&to=Flim+Flam");
+    assert_eq!(status, 200);
+    assert_eq!(json["length"], path.len() - 1);
+
+    assert_eq!(get("/path?from=BOOP+JONES&to=QUUX+QUUX").0, 404);
+}
+
+#[test]
+fn test_ranking_works() {
+    let (status, json) = get("/ranking?min_buildings=2&limit=2&top=1");
+    assert_eq!(status, 200);
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {
+                "rank": 1, "id": json[0]["id"], "name": "BOOP JONES's portfolio", "buildings": 5,
+                "manhattan": 0, "bronx": 2, "brooklyn": 3, "queens": 0, "staten_island": 0,
+                "top_addresses": [{"value": "1 BOOP STREET, NEW YORK NY", "reg_contacts": 3}],
+                "top_names": [{"value": "BLAP SMITH", "reg_contacts": 2}],
+                "local_bridges": 3,
+            },
+            {
+                "rank": 2, "id": json[1]["id"], "name": "HARBOR VIEW's portfolio", "buildings": 2,
+                "manhattan": 0, "bronx": 0, "brooklyn": 0, "queens": 0, "staten_island": 2,
+                "top_addresses": [{"value": "1 BAY STREET, STATEN ISLAND NY", "reg_contacts": json[1]["top_addresses"][0]["reg_contacts"]}],
+                "top_names": [{"value": "HARBOR VIEW", "reg_contacts": json[1]["top_names"][0]["reg_contacts"]}],
+                "local_bridges": 0,
+            },
+        ])
+    );
+
+    let (status, json) = get("/ranking?boro=QN");
+    assert_eq!(status, 200);
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["name"], "QUUX QUUX's portfolio");
+    assert_eq!(get("/ranking?boro=Narnia").0, 400);
+    assert_eq!(get("/ranking?rank_by=evictions").0, 400);
+    assert_eq!(get("/ranking?rank_by=stabilized-units").0, 400);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{fixtures_dir, run_hpd, run_hpd_in};
+use serde_json::{json, Value};
+
+fn run_hpd_with_stabilized_units(args: &[&str]) -> String {
+    let args: Vec<&str> = ["--rent-stabilization", "rentstab.csv"]
+        .iter()
+        .chain(args)
+        .copied()
+        .collect();
+    String::from_utf8(run_hpd(&args)).unwrap()
+}
+
+#[test]
+fn test_ranking_by_stabilized_units_works() {
+    assert_eq!(
+        run_hpd_with_stabilized_units(&["ranking", "--rank-by", "stabilized-units"]),
+        "1. ZIP ZAP's portfolio - 2 buildings, 60 stabilized units (0 lost)
+2. QUUX QUUX's portfolio - 2 buildings, 38 stabilized units (2 lost)
+3. BOOP JONES's portfolio - 5 buildings, 11 stabilized units (4 lost)
+4. HARBOR VIEW's portfolio - 2 buildings, 0 stabilized units (0 lost)
+"
+    );
+}
+
+#[test]
+fn test_ranking_by_stabilized_units_lost_works() {
+    let ranking = run_hpd_with_stabilized_units(&[
+        "ranking",
+        "--rank-by",
+        "stabilized-units-lost",
+        "-f",
+        "csv",
+    ]);
+    let mut lines = ranking.lines();
+    assert!(lines
+        .next()
+        .unwrap()
+        .contains(",staten_island,stabilized_units,stabilized_units_lost,"));
+    assert!(lines
+        .next()
+        .unwrap()
+        .contains(",BOOP JONES's portfolio,5,0,2,3,0,0,11,4,"));
+    assert!(lines
+        .next()
+        .unwrap()
+        .contains(",QUUX QUUX's portfolio,2,0,0,0,2,0,38,2,"));
+}
+
+#[test]
+fn test_ranking_by_stabilized_units_requires_them() {
+    let output = run_hpd_in(
+        fixtures_dir(),
+        &["ranking", "--rank-by", "stabilized-units"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_info_describes_stabilized_units() {
+    let info = run_hpd_with_stabilized_units(&["info", "BOOP JONES"]);
+    assert!(info.contains("It had 11 rent-stabilized units in 2020, 4 fewer than in 2019.\n"));
+}
+
+#[test]
+fn test_portfolio_json_includes_stabilized_units() {
+    let json: Value =
+        serde_json::from_str(&run_hpd_with_stabilized_units(&["json", "BOOP JONES"])).unwrap();
+    assert_eq!(
+        json["stabilized_units"],
+        json!({
+            "stabilized_units": 11,
+            "year": 2020,
+            "stabilized_units_lost": 4,
+            "history": [
+                {"year": 2018, "stabilized_units": 15},
+                {"year": 2019, "stabilized_units": 15},
+                {"year": 2020, "stabilized_units": 11},
+            ],
+        })
+    );
+
+    let json: Value = serde_json::from_slice(&run_hpd(&["json", "BOOP JONES"])).unwrap();
+    assert_eq!(json.get("stabilized_units"), None);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::run_hpd;
+use serde_json::{json, Value};
+
+fn run_hpd_json(args: &[&str]) -> Value {
+    serde_json::from_slice(&run_hpd(args)).unwrap()
+}
+
+fn run_hpd_csv(args: &[&str]) -> Vec<csv::StringRecord> {
+    csv::Reader::from_reader(&run_hpd(args)[..])
+        .records()
+        .map(|record| record.unwrap())
+        .collect()
+}
+
+#[test]
+fn test_ranking_formats_agree() {
+    let ranking = run_hpd_json(&["ranking", "-f", "json", "-b", "2", "-t", "2"]);
+    assert_eq!(ranking.as_array().unwrap().len(), 4);
+    assert_eq!(
+        ranking[0],
+        json!({
+            "rank": 1, "id": ranking[0]["id"], "name": "BOOP JONES's portfolio", "buildings": 5,
+            "manhattan": 0, "bronx": 2, "brooklyn": 3, "queens": 0, "staten_island": 0,
+            "top_addresses": [
+                {"value": "1 BOOP STREET, NEW YORK NY", "reg_contacts": 3},
+                {"value": "2 BLAP AVENUE STE 5, BROOKLYN NY", "reg_contacts": 2},
+            ],
+            "top_names": [
+                {"value": "BLAP SMITH", "reg_contacts": 2},
+                {"value": "BOOP JONES", "reg_contacts": 2},
+            ],
+            "local_bridges": 3,
+        })
+    );
+
+    let rows = run_hpd_csv(&["ranking", "--format", "csv", "-b", "2", "-t", "2"]);
+    assert_eq!(rows.len(), 4);
+    for (row, entry) in rows.iter().zip(ranking.as_array().unwrap()) {
+        assert_eq!(row[0], entry["rank"].to_string());
+        assert_eq!(row[1], entry["id"]);
+        assert_eq!(row[2], entry["name"]);
+        assert_eq!(row[3], entry["buildings"].to_string());
+        assert_eq!(row[5], entry["bronx"].to_string());
+        assert_eq!(row[9], entry["local_bridges"].to_string());
+    }
+    assert_eq!(
+        &rows[0][10],
+        "1 BOOP STREET, NEW YORK NY;2 BLAP AVENUE STE 5, BROOKLYN NY"
+    );
+    assert_eq!(&rows[0][11], "BLAP SMITH;BOOP JONES");
+}
+
+#[test]
+fn test_ranking_can_be_limited_to_one_borough() {
+    let ranking = run_hpd_json(&["ranking", "-f", "json", "--boro", "Staten Island"]);
+    assert_eq!(ranking.as_array().unwrap().len(), 1);
+    assert_eq!(ranking[0]["rank"], 1);
+    assert_eq!(ranking[0]["name"], "HARBOR VIEW's portfolio");
+
+    // BOOP JONES's portfolio is partly in the Bronx, so it isn't included.
+    let ranking = run_hpd_json(&["ranking", "-f", "json", "--boro", "BX"]);
+    assert_eq!(ranking, json!([]));
+}
+
+#[test]
+fn test_info_formats_work() {
+    let info = run_hpd_json(&["info", "-f", "json", "-t", "2", "BOOP JONES"]);
+    assert_eq!(info["names"], 7);
+    assert_eq!(info["connected_components"], 4);
+    let portfolio = &info["portfolio"];
+    assert_eq!(portfolio["name"], "BOOP JONES's portfolio");
+    assert_eq!(portfolio["buildings"], 5);
+    assert_eq!(portfolio["local_bridges"], 3);
+    assert_eq!(
+        portfolio["boroughs"],
+        json!([
+            {"boro": "Bronx", "buildings": 2, "min_block": 2345, "max_block": 2345},
+            {"boro": "Brooklyn", "buildings": 3, "min_block": 1234, "max_block": 1234},
+        ])
+    );
+    assert_eq!(
+        portfolio["community_districts"],
+        json!([
+            {"community_district": 204, "buildings": 2},
+            {"community_district": 302, "buildings": 3},
+        ])
+    );
+    assert_eq!(
+        portfolio["top_addresses"][0],
+        json!({"value": "1 BOOP STREET, NEW YORK NY", "reg_contacts": 3})
+    );
+    assert_eq!(portfolio["top_names"].as_array().unwrap().len(), 2);
+
+    assert_eq!(
+        run_hpd_json(&["info", "-f", "json"])["portfolio"],
+        Value::Null
+    );
+
+    let rows = run_hpd_csv(&["info", "-f", "csv", "ZIP ZAP"]);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(&rows[0][4], "ZIP ZAP's portfolio");
+    assert_eq!(&rows[0][6], "2");
+    assert_eq!(&rows[0][13], "ZIP ZAP;ZORP ZAP");
+}
+
+#[test]
+fn test_longpaths_formats_work() {
+    let paths = run_hpd_json(&["longpaths", "-m", "2", "-f", "json"]);
+    let zip_zap = paths
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|path| path["nodes"][0]["label"] == "ZIP ZAP")
+        .unwrap();
+    assert_eq!(zip_zap["length"], 2);
+    assert_eq!(zip_zap["nodes"][1]["kind"], "address");
+    assert_eq!(zip_zap["links"], json!(["registration", "registration"]));
+
+    let rows = run_hpd_csv(&["longpaths", "-m", "2", "-f", "csv"]);
+    assert!(rows.iter().any(|row| &row[0] == "2"
+        && &row[1] == "ZIP ZAP;9 ZIP ROAD, NEW YORK NY;ZORP ZAP"
+        && &row[2] == "registration;registration"));
+}
+
+#[test]
+fn test_info_accepts_bbl_in_any_form() {
+    for bbl in &["3012340010", "3-1234-10", "Brooklyn 1234 10", "BK 1234 10"] {
+        let info = run_hpd_json(&["info", "-f", "json", "--bbl", bbl]);
+        assert_eq!(
+            info["portfolio"]["name"], "BOOP JONES's portfolio",
+            "{}",
+            bbl
+        );
+    }
+}
+
+#[test]
+fn test_buildings_formats_agree() {
+    let buildings = run_hpd_json(&["buildings", "-f", "json", "BOOP JONES"]);
+    assert_eq!(buildings.as_array().unwrap().len(), 5);
+    assert_eq!(
+        buildings[1],
+        json!({
+            "bbl": "2023450002",
+            "bin": 2000002,
+            "registration_id": 100005,
+            "registration_end_date": "2099-09-01",
+            "names": ["FLIM FLAM"],
+            "addresses": ["2 BLAP AVENUE STE 5, BROOKLYN NY", "3 FLIM PLACE, QUEENS NY"],
+        })
+    );
+
+    let rows = run_hpd_csv(&["buildings", "--bbl", "2-2345-2"]);
+    assert_eq!(rows.len(), 5);
+    assert_eq!(
+        rows[1].iter().collect::<Vec<_>>(),
+        vec![
+            "2023450002",
+            "2000002",
+            "100005",
+            "2099-09-01",
+            "FLIM FLAM",
+            "2 BLAP AVENUE STE 5, BROOKLYN NY;3 FLIM PLACE, QUEENS NY",
+        ]
+    );
+}
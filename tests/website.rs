@@ -0,0 +1,39 @@
+mod common;
+
+use common::fixtures_dir;
+use hpd::website::SearchIndexEntry;
+use hpd::DatasetBuilder;
+use serde_json::{json, Value};
+
+#[test]
+fn test_search_index_entry_includes_names_addresses_and_bbls() {
+    let dataset = DatasetBuilder::new().read_dir(fixtures_dir()).unwrap();
+    let portfolio = dataset.portfolio_with_name("BOOP JONES").unwrap();
+    let entry = SearchIndexEntry::new(&portfolio, &dataset.regs);
+    assert_eq!(entry.href, format!("{}.html", portfolio.id()));
+
+    let json: Value = serde_json::to_value(&entry).unwrap();
+    assert_eq!(json["title"], "BOOP JONES's portfolio");
+    assert_eq!(
+        json["names"],
+        json!(["BLAP SMITH", "BOOP JONES", "FLIM FLAM"])
+    );
+    assert_eq!(
+        json["addresses"],
+        json!([
+            "1 BOOP STREET, NEW YORK NY",
+            "2 BLAP AVENUE STE 5, BROOKLYN NY",
+            "3 FLIM PLACE, QUEENS NY",
+        ])
+    );
+    assert_eq!(
+        json["bbls"],
+        json!([
+            "2023450001",
+            "2023450002",
+            "3012340010",
+            "3012340011",
+            "3012340012",
+        ])
+    );
+}
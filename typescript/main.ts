@@ -1,5 +1,6 @@
 import { assertNotNull, getHTMLElement } from '@justfixnyc/util';
 import ForceGraph, { GraphData, LinkObject, NodeObject } from 'force-graph';
+import { searchMain } from './search';
 
 const NAME_COLOR = 'crimson';
 
@@ -37,7 +38,7 @@ function getEdgeColor(edge: PortfolioEdge): string {
 function getEdgeLabel(edge: PortfolioEdge): string {
   const parts = [
     `${edge.reg_contacts} HPD registration${edge.reg_contacts === 1 ? '' : 's'} ` +
-    `(e.g. BBL ${edge.bbl})`
+    `(e.g. BBL ${edge.bbls[0]})`
   ];
 
   if (edge.is_bridge) {
@@ -51,8 +52,8 @@ function portfolioToGraphData(p: Portfolio): GraphData {
   return {
     nodes: p.nodes.map((node): NodeObject => ({
       id: node.id,
-      name: 'Name' in node.value ? node.value.Name : node.value.BizAddr,
-      color: 'Name' in node.value ? NAME_COLOR : BIZADDR_COLOR,
+      name: node.label,
+      color: node.kind === 'name' ? NAME_COLOR : BIZADDR_COLOR,
       val: 10,
     })),
     links: p.edges.map((edge): LinkObject => ({
@@ -65,7 +66,7 @@ function portfolioToGraphData(p: Portfolio): GraphData {
   };
 }
 
-function main() {
+function portfolioMain() {
   const graphEl = getHTMLElement('div', '#graph');
   const messageEl = getHTMLElement('p', '#message');
   const searchForm = getHTMLElement("form", "#search-form");
@@ -97,7 +98,7 @@ function main() {
       link => link.edge.is_bridge && link.edge.reg_contacts === 1 ? [5, 5] : null
     )
     .onLinkClick(link => {
-      window.open(wowLink(link.edge.bbl), '_blank');
+      window.open(wowLink(link.edge.bbls[0]), '_blank');
     });
 
   searchForm.addEventListener("submit", (e) => {
@@ -132,4 +133,14 @@ function main() {
   });
 }
 
+function main() {
+  // The same bundle is used by the index page, which only has a search
+  // form, and by each portfolio's page.
+  if (document.getElementById('portfolio')) {
+    portfolioMain();
+  } else {
+    searchMain();
+  }
+}
+
 window.addEventListener("load", main);
@@ -1,6 +1,10 @@
+// This mirrors schema/portfolio.schema.json, which is generated by
+// `hpd json-schema`; keep the two in sync.
+
 type PortfolioNode = {
   id: number,
-  value: { Name: string }|{ BizAddr: string },
+  kind: 'name'|'address',
+  label: string,
 };
 
 type PortfolioEdge = {
@@ -8,11 +12,78 @@ type PortfolioEdge = {
   to: number,
   reg_contacts: number,
   is_bridge: boolean,
+  bbls: string[],
+  deeds?: PortfolioDeed[],
+};
+
+type PortfolioDeed = {
+  document_id: string,
+  doc_type: string,
+  document_date: string,
+  bbls: string[],
+};
+
+type PlutoLot = {
+  residential_units: number,
+  year_built: number|null,
+  building_class: string|null,
+  condo_number: number|null,
+};
+
+type PortfolioBuilding = {
   bbl: string,
+  bin: number|null,
+  registration_id: number,
+  registration_end_date: string,
+  names: string[],
+  addresses: string[],
+  pluto?: PlutoLot,
+};
+
+type PortfolioConditions = {
+  open_class_c_violations: number|null,
+  open_class_c_violations_per_unit: number|null,
+  recent_complaints: number|null,
+  recent_complaints_per_building: number|null,
+  complaints_since: string|null,
+};
+
+type StabilizedUnitsInYear = {
+  year: number,
+  stabilized_units: number,
+};
+
+type PortfolioStabilizedUnits = {
+  stabilized_units: number,
+  year: number,
+  stabilized_units_lost: number|null,
+  history: StabilizedUnitsInYear[],
+};
+
+type DatasetOptions = {
+  max_expiration_age_days: number,
+  include_corps: boolean,
+  keep_expired: boolean,
+  link_deeds: boolean,
+  count_by: "registrations"|"bins"|"bbls"|"tax-lots",
+  pluto: boolean,
+  violations: boolean,
+  complaints_since: string|null,
+  evictions_since: string|null,
+  stabilized_units: boolean,
+  previous_ids: boolean,
 };
 
 type Portfolio = {
+  schema_version: 2,
+  generated_at: string,
+  options: DatasetOptions,
+  id: string,
   title: string,
+  buildings: PortfolioBuilding[],
+  residential_units?: number,
+  conditions?: PortfolioConditions,
+  stabilized_units?: PortfolioStabilizedUnits,
   nodes: PortfolioNode[],
   edges: PortfolioEdge[],
 };
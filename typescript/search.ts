@@ -0,0 +1,119 @@
+import { getHTMLElement } from '@justfixnyc/util';
+
+// This mirrors `SearchIndexEntry` in src/website.rs.
+type SearchIndexEntry = {
+  href: string,
+  title: string,
+  names: string[],
+  addresses: string[],
+  bbls: string[],
+};
+
+type SearchResult = {
+  entry: SearchIndexEntry,
+  matches: string[],
+};
+
+const SEARCH_INDEX_URL = 'search-index.json';
+
+const MAX_RESULTS = 50;
+
+/**
+ * Returns the ten-digit BBL the query is written as, if it's written as
+ * ten digits (`3012340010`) or with dashes (`3-1234-10`).
+ */
+function parseBBL(query: string): string|null {
+  if (/^[1-5]\d{9}$/.test(query)) {
+    return query;
+  }
+  const dashed = query.match(/^([1-5])\s*-\s*(\d{1,5})\s*-\s*(\d{1,4})$/);
+  if (dashed) {
+    return dashed[1] + dashed[2].padStart(5, '0') + dashed[3].padStart(4, '0');
+  }
+  return null;
+}
+
+/**
+ * Finds the portfolios that own the BBL the query is written as, or
+ * otherwise those with a name or business address containing it.
+ */
+function search(index: SearchIndexEntry[], query: string): SearchResult[] {
+  const bbl = parseBBL(query);
+  const results: SearchResult[] = [];
+
+  for (let entry of index) {
+    const matches = bbl
+      ? entry.bbls.filter(b => b === bbl)
+      : [...entry.names, ...entry.addresses].filter(text => text.includes(query));
+    if (matches.length > 0) {
+      results.push({ entry, matches });
+    }
+  }
+
+  return results;
+}
+
+export function searchMain() {
+  const searchForm = getHTMLElement('form', '#index-search-form');
+  const searchInput = getHTMLElement('input', '#index-search-input');
+  const messageEl = getHTMLElement('p', '#message');
+  const resultsEl = getHTMLElement('ol', '#search-results');
+  let index: Promise<SearchIndexEntry[]>|null = null;
+
+  // The index can be large, so we only load it once someone searches.
+  function loadIndex(): Promise<SearchIndexEntry[]> {
+    if (!index) {
+      messageEl.textContent = "Loading the search index...";
+      index = fetch(SEARCH_INDEX_URL).then(res => {
+        if (!res.ok) {
+          throw new Error(`Got HTTP ${res.status}`);
+        }
+        return res.json();
+      });
+    }
+    return index;
+  }
+
+  searchInput.value = "";
+
+  searchForm.addEventListener("submit", async (e) => {
+    e.preventDefault();
+    const query = searchInput.value.trim().toUpperCase();
+
+    resultsEl.innerHTML = "";
+
+    if (!query) {
+      messageEl.textContent = "";
+      return;
+    }
+
+    let results: SearchResult[];
+    try {
+      results = search(await loadIndex(), query);
+    } catch (e) {
+      // Try loading it again next time.
+      index = null;
+      messageEl.textContent = `Unable to load the search index (${e}).`;
+      return;
+    }
+
+    if (results.length === 0) {
+      messageEl.textContent = `Unable to find any portfolios with the text "${query}".`;
+      return;
+    }
+
+    messageEl.textContent = results.length > MAX_RESULTS
+      ? `Found ${results.length} portfolios with the text "${query}"; showing the first ${MAX_RESULTS}.`
+      : `Found ${results.length} portfolio(s) with the text "${query}".`;
+
+    for (let { entry, matches } of results.slice(0, MAX_RESULTS)) {
+      const li = document.createElement('li');
+      const a = document.createElement('a');
+      a.href = entry.href;
+      a.textContent = entry.title;
+      li.appendChild(a);
+      li.appendChild(document.createTextNode(` (${matches.join('; ')})`));
+      resultsEl.appendChild(li);
+    }
+  });
+}